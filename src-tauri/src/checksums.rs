@@ -0,0 +1,54 @@
+//! SHA-256 checksums for extracted model files
+
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Manifest of per-file hashes written next to the extracted model
+pub const MODEL_MANIFEST_FILE: &str = ".checksums.json";
+
+/// Relative file path -> lowercase hex SHA-256
+pub type ChecksumManifest = BTreeMap<String, String>;
+
+/// Hashes an in-memory buffer
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Hashes a file without loading it fully into memory
+pub fn sha256_file(path: &Path) -> Result<String, String> {
+    let mut file =
+        std::fs::File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn load_manifest(dir: &Path) -> Result<Option<ChecksumManifest>, String> {
+    let manifest_path = dir.join(MODEL_MANIFEST_FILE);
+    if !manifest_path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&manifest_path)
+        .map_err(|e| format!("Failed to read checksum manifest: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse checksum manifest: {}", e))
+}
+
+pub fn save_manifest(dir: &Path, manifest: &ChecksumManifest) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(manifest)
+        .map_err(|e| format!("Failed to serialize checksum manifest: {}", e))?;
+    std::fs::write(dir.join(MODEL_MANIFEST_FILE), content)
+        .map_err(|e| format!("Failed to save checksum manifest: {}", e))
+}
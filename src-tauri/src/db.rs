@@ -1,11 +1,117 @@
 //! Database operations for chat history
 
-use crate::models::ChatMessage;
-use crate::paths::get_db_path;
-use rusqlite::{params, Connection};
+use crate::models::{
+    ChatMessage, ChatSession, DatabaseStats, DbPoolStats, DeepResearchRecord, EventHook,
+    HourlyCount, SearchResult, SortDirection, VacuumStats,
+};
+use crate::paths::{get_db_path, get_wal_autocheckpoint_path};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::sync::Mutex;
+
+/// Schema version this build expects the database to be at
+const CURRENT_SCHEMA_VERSION: u32 = 8;
+
+/// Session that holds messages from before sessions existed; it can't be deleted
+pub const DEFAULT_SESSION_ID: &str = "default";
+
+/// Sequential migrations as (from_version, sql) pairs; each runs in its own transaction
+const MIGRATIONS: &[(u32, &str)] = &[
+    // Version 1: baseline schema
+    (
+        0,
+        "CREATE TABLE IF NOT EXISTS chat_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp TEXT NOT NULL,
+            role TEXT NOT NULL,
+            content TEXT NOT NULL,
+            context_level INTEGER DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS hooks (
+            id TEXT PRIMARY KEY,
+            event_name TEXT NOT NULL,
+            webhook_url TEXT NOT NULL,
+            method TEXT NOT NULL DEFAULT 'POST',
+            enabled INTEGER NOT NULL DEFAULT 1
+        );",
+    ),
+    // Version 2: message metadata (ids are the rowid alias, which every table already has)
+    (1, "ALTER TABLE chat_history ADD COLUMN metadata TEXT;"),
+    // Version 3: per-message token usage
+    (
+        2,
+        "ALTER TABLE chat_history ADD COLUMN token_count INTEGER;",
+    ),
+    // Version 4: full-text search index over message content, kept in sync by triggers
+    (
+        3,
+        "CREATE VIRTUAL TABLE IF NOT EXISTS chat_fts USING fts5(
+            content, content='chat_history', content_rowid='id'
+        );
+        CREATE TRIGGER IF NOT EXISTS chat_fts_insert AFTER INSERT ON chat_history BEGIN
+            INSERT INTO chat_fts(rowid, content) VALUES (new.id, new.content);
+        END;
+        CREATE TRIGGER IF NOT EXISTS chat_fts_delete AFTER DELETE ON chat_history BEGIN
+            INSERT INTO chat_fts(chat_fts, rowid, content) VALUES ('delete', old.id, old.content);
+        END;
+        INSERT INTO chat_fts(chat_fts) VALUES ('rebuild');",
+    ),
+    // Version 5: user bookmarks
+    (
+        4,
+        "ALTER TABLE chat_history ADD COLUMN bookmarked INTEGER NOT NULL DEFAULT 0;",
+    ),
+    // Version 6: named conversation sessions; existing messages move to the default session
+    (
+        5,
+        "CREATE TABLE IF NOT EXISTS chat_sessions (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        INSERT OR IGNORE INTO chat_sessions (id, name, created_at)
+            VALUES ('default', 'Default', strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+        ALTER TABLE chat_history ADD COLUMN session_id TEXT NOT NULL DEFAULT 'default';
+        CREATE INDEX IF NOT EXISTS idx_chat_history_session ON chat_history(session_id);",
+    ),
+    // Version 7: branch tracking for sessions forked from another session
+    (
+        6,
+        "ALTER TABLE chat_sessions ADD COLUMN forked_from_session_id TEXT;",
+    ),
+    // Version 8: deep research results kept apart from the chat history
+    (
+        7,
+        "CREATE TABLE IF NOT EXISTS deep_research_results (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            prompt_used TEXT NOT NULL,
+            insights TEXT NOT NULL,
+            token_count INTEGER
+        );",
+    ),
+];
+
+/// Pool of SQLite connections shared by every database function
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+const DB_POOL_MAX_SIZE: u32 = 4;
+const DB_POOL_CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Slot for the connection pool, kept in `AppState`. The pool is opened on first use
+/// and reset by `close_db_pool` so the file can be deleted.
+#[derive(Default)]
+pub struct DbPoolSlot(Mutex<Option<DbPool>>);
+
+/// Opens the connection pool, migrating the schema to the current version if needed
+pub fn init_db_pool(db: &DbPoolSlot) -> Result<DbPool, String> {
+    let mut pool_slot = db.0.lock().unwrap();
+    if let Some(pool) = pool_slot.as_ref() {
+        return Ok(pool.clone());
+    }
 
-/// Initializes the SQLite database, creating tables if needed
-pub fn init_database() -> Result<Connection, String> {
     let db_path = get_db_path()?;
 
     // Ensure parent directory exists
@@ -14,61 +120,369 @@ pub fn init_database() -> Result<Connection, String> {
             .map_err(|e| format!("Failed to create database directory: {}", e))?;
     }
 
-    let conn = Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    // Wait for locks held by other pooled connections instead of failing with SQLITE_BUSY.
+    // synchronous=NORMAL is safe under WAL and avoids a full sync on every commit
+    let autocheckpoint = load_wal_autocheckpoint();
+    let manager = SqliteConnectionManager::file(&db_path).with_init(move |conn| {
+        conn.execute_batch(&format!(
+            "PRAGMA busy_timeout = 5000;
+            PRAGMA synchronous = NORMAL;
+            PRAGMA wal_autocheckpoint = {};",
+            autocheckpoint
+        ))
+    });
+    let pool = r2d2::Pool::builder()
+        .max_size(DB_POOL_MAX_SIZE)
+        .connection_timeout(DB_POOL_CONNECTION_TIMEOUT)
+        .build(manager)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+
+    let mut conn = pool
+        .get()
+        .map_err(|e| format!("Failed to get database connection: {}", e))?;
+
+    // WAL mode is stored in the database file, so setting it once covers every connection
+    let journal_mode: String = conn
+        .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to enable WAL mode: {}", e))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        return Err(format!(
+            "Failed to enable WAL mode: journal mode is {}",
+            journal_mode
+        ));
+    }
+
+    migrate_schema(&mut conn)?;
+
+    *pool_slot = Some(pool.clone());
+    Ok(pool)
+}
+
+/// Default number of WAL pages written before SQLite checkpoints automatically
+pub const DEFAULT_WAL_AUTOCHECKPOINT: u32 = 1000;
+
+fn load_wal_autocheckpoint() -> u32 {
+    get_wal_autocheckpoint_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse().ok())
+        .unwrap_or(DEFAULT_WAL_AUTOCHECKPOINT)
+}
+
+/// Saves the WAL auto-checkpoint interval and reopens the pool so every connection uses it
+pub fn set_wal_autocheckpoint_internal(db: &DbPoolSlot, pages: u32) -> Result<(), String> {
+    let path = get_wal_autocheckpoint_path()?;
+    std::fs::write(&path, pages.to_string())
+        .map_err(|e| format!("Failed to save WAL auto-checkpoint: {}", e))?;
+    close_db_pool(db);
+    Ok(())
+}
+
+/// Pragmas `get_db_pragma_internal` may read. Some pragmas act when queried (`optimize`,
+/// `wal_checkpoint`, `shrink_memory`, `incremental_vacuum`), so only side-effect-free
+/// settings and counters are listed.
+const READABLE_PRAGMAS: &[&str] = &[
+    "application_id",
+    "auto_vacuum",
+    "busy_timeout",
+    "cache_size",
+    "encoding",
+    "foreign_keys",
+    "freelist_count",
+    "journal_mode",
+    "journal_size_limit",
+    "locking_mode",
+    "max_page_count",
+    "mmap_size",
+    "page_count",
+    "page_size",
+    "schema_version",
+    "synchronous",
+    "temp_store",
+    "user_version",
+    "wal_autocheckpoint",
+];
+
+/// Reads the value of a read-only pragma, e.g. `journal_mode`
+pub fn get_db_pragma_internal(db: &DbPoolSlot, name: &str) -> Result<String, String> {
+    // Pragma names can't be bound as parameters, and some pragmas have side effects
+    if !READABLE_PRAGMAS.contains(&name) {
+        return Err(format!("Pragma not allowed: {}", name));
+    }
+
+    let conn = connection(db)?;
+    let value: rusqlite::types::Value = conn
+        .query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+        .optional()
+        .map_err(|e| format!("Failed to read pragma {}: {}", name, e))?
+        .ok_or_else(|| format!("Unknown pragma: {}", name))?;
+
+    Ok(match value {
+        rusqlite::types::Value::Null => String::new(),
+        rusqlite::types::Value::Integer(i) => i.to_string(),
+        rusqlite::types::Value::Real(f) => f.to_string(),
+        rusqlite::types::Value::Text(s) => s,
+        rusqlite::types::Value::Blob(b) => format!("<{} bytes>", b.len()),
+    })
+}
+
+/// Borrows a connection from the pool
+fn connection(db: &DbPoolSlot) -> Result<DbConnection, String> {
+    init_db_pool(db)?
+        .get()
+        .map_err(|e| format!("Failed to get database connection: {}", e))
+}
 
+/// Drops the pool so the database file can be removed; it reopens on next use
+pub fn close_db_pool(db: &DbPoolSlot) {
+    db.0.lock().unwrap().take();
+}
+
+/// Connection usage of the pool, for diagnostics
+pub fn get_db_pool_stats_internal(db: &DbPoolSlot) -> Result<DbPoolStats, String> {
+    let pool = init_db_pool(db)?;
+    let state = pool.state();
+    Ok(DbPoolStats {
+        active: state.connections - state.idle_connections,
+        idle: state.idle_connections,
+        max: pool.max_size(),
+    })
+}
+
+/// Reads the stored schema version and runs any pending migrations in order
+fn migrate_schema(conn: &mut Connection) -> Result<(), String> {
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS chat_history (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            timestamp TEXT NOT NULL,
-            role TEXT NOT NULL,
-            content TEXT NOT NULL,
-            context_level INTEGER DEFAULT 0
-        )",
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
         [],
     )
-    .map_err(|e| format!("Failed to create table: {}", e))?;
+    .map_err(|e| format!("Failed to create schema_version table: {}", e))?;
 
-    // Migration: Add context_level column if it doesn't exist (for existing databases)
-    let _ = conn.execute(
-        "ALTER TABLE chat_history ADD COLUMN context_level INTEGER DEFAULT 0",
-        [],
-    ); // Ignore error if column already exists
+    let stored: Option<u32> = conn
+        .query_row("SELECT version FROM schema_version LIMIT 1", [], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+
+    let mut version = match stored {
+        Some(version) => version,
+        None => {
+            conn.execute("INSERT INTO schema_version (version) VALUES (0)", [])
+                .map_err(|e| format!("Failed to initialize schema version: {}", e))?;
+            0
+        }
+    };
+
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "Database schema version {} is newer than this build supports ({})",
+            version, CURRENT_SCHEMA_VERSION
+        ));
+    }
 
-    Ok(conn)
+    if version == 0 {
+        // Databases from before versioning may predate the context_level column
+        let _ = conn.execute(
+            "ALTER TABLE chat_history ADD COLUMN context_level INTEGER DEFAULT 0",
+            [],
+        ); // Ignore error if the table or column doesn't exist yet
+    }
+
+    for (from_version, sql) in MIGRATIONS {
+        if *from_version != version {
+            continue;
+        }
+
+        // Dropping the transaction without committing rolls it back
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start migration: {}", e))?;
+        tx.execute_batch(sql).map_err(|e| {
+            format!(
+                "Failed to migrate schema from version {}: {}",
+                from_version, e
+            )
+        })?;
+        tx.execute(
+            "UPDATE schema_version SET version = ?1",
+            params![from_version + 1],
+        )
+        .map_err(|e| format!("Failed to update schema version: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit migration: {}", e))?;
+
+        version = from_version + 1;
+    }
+
+    Ok(())
 }
 
-/// Stores a chat message in the database
+/// Stores a chat message in the database, returning its row ID
 pub fn store_chat_message(
+    db: &DbPoolSlot,
+    session_id: &str,
     timestamp: &str,
     role: &str,
     content: &str,
     context_level: u8,
-) -> Result<(), String> {
-    let conn = init_database()?;
+    token_count: Option<u32>,
+    metadata: Option<&str>,
+) -> Result<i64, String> {
+    let conn = connection(db)?;
     conn.execute(
-        "INSERT INTO chat_history (timestamp, role, content, context_level) VALUES (?1, ?2, ?3, ?4)",
-        params![timestamp, role, content, context_level],
+        "INSERT INTO chat_history (session_id, timestamp, role, content, context_level, token_count, metadata) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![session_id, timestamp, role, content, context_level, token_count, metadata],
     ).map_err(|e| format!("Failed to store message: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Stores imported messages, given as (timestamp, role, content, context_level), in a
+/// single transaction so a failed import leaves the session untouched. Returns the count.
+pub fn store_chat_messages(
+    db: &DbPoolSlot,
+    session_id: &str,
+    messages: &[(String, String, String, u8)],
+) -> Result<u32, String> {
+    let mut conn = connection(db)?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO chat_history (session_id, timestamp, role, content, context_level) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        for (timestamp, role, content, context_level) in messages {
+            stmt.execute(params![session_id, timestamp, role, content, context_level])
+                .map_err(|e| format!("Failed to store message: {}", e))?;
+        }
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit import: {}", e))?;
+    Ok(messages.len() as u32)
+}
+
+/// Parses a stored metadata column; malformed JSON is treated as absent
+fn parse_metadata(metadata: Option<String>) -> Option<serde_json::Value> {
+    metadata.and_then(|m| serde_json::from_str(&m).ok())
+}
+
+/// Builds a `ChatMessage` from a row selecting id, timestamp, role, content,
+/// context_level, token_count, bookmarked and metadata, in that order
+fn row_to_message(row: &Row) -> rusqlite::Result<ChatMessage> {
+    Ok(ChatMessage {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        context_level: row.get::<_, i64>(4)? as u8,
+        token_count: row.get(5)?,
+        bookmarked: row.get(6)?,
+        metadata: parse_metadata(row.get(7)?),
+    })
+}
+
+/// Replaces the JSON metadata attached to a message
+pub fn update_message_metadata_internal(
+    db: &DbPoolSlot,
+    id: i64,
+    metadata: &str,
+) -> Result<(), String> {
+    let conn = connection(db)?;
+    let updated = conn
+        .execute(
+            "UPDATE chat_history SET metadata = ?1 WHERE rowid = ?2",
+            params![metadata, id],
+        )
+        .map_err(|e| format!("Failed to update message metadata: {}", e))?;
+    if updated == 0 {
+        return Err(format!("Message {} not found", id));
+    }
     Ok(())
 }
 
-/// Retrieves chat history from the database
-pub fn get_chat_history_internal(limit: i64) -> Result<Vec<ChatMessage>, String> {
-    let conn = init_database()?;
+/// Retrieves a session's chat history from the database
+pub fn get_chat_history_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
     let mut stmt = conn.prepare(
-        "SELECT id, timestamp, role, content, COALESCE(context_level, 0) FROM chat_history ORDER BY id DESC LIMIT ?1"
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 ORDER BY id DESC LIMIT ?2"
     ).map_err(|e| format!("Failed to prepare query: {}", e))?;
 
     let messages = stmt
-        .query_map(params![limit], |row| {
-            Ok(ChatMessage {
-                id: Some(row.get(0)?),
-                timestamp: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                context_level: row.get::<_, i64>(4)? as u8,
-            })
-        })
+        .query_map(params![session_id, limit], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    let mut result: Vec<ChatMessage> = messages.filter_map(|m| m.ok()).collect();
+
+    // Reverse to get chronological order
+    result.reverse();
+    Ok(result)
+}
+
+/// Retrieves a page of a session's chat history in the requested order
+pub fn get_chat_history_page_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    offset: i64,
+    limit: i64,
+    direction: SortDirection,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    // Direction comes from a fixed enum, never from user text
+    let query = format!(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 ORDER BY timestamp {0}, id {0} LIMIT ?2 OFFSET ?3",
+        direction.as_sql()
+    );
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, limit, offset], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(messages.filter_map(|m| m.ok()).collect())
+}
+
+/// Retrieves a session's messages with timestamps between `from` and `to` (inclusive), oldest first
+pub fn get_chat_history_range_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    from: &str,
+    to: &str,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn.prepare(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 AND timestamp BETWEEN ?2 AND ?3 ORDER BY timestamp ASC, id ASC LIMIT ?4"
+    ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, from, to, limit], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(messages.filter_map(|m| m.ok()).collect())
+}
+
+/// Retrieves a session's most recent messages with the given role, in chronological order
+pub fn get_chat_history_by_role_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    role: &str,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn.prepare(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 AND role = ?2 ORDER BY id DESC LIMIT ?3"
+    ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, role, limit], row_to_message)
         .map_err(|e| format!("Failed to query: {}", e))?;
 
     let mut result: Vec<ChatMessage> = messages.filter_map(|m| m.ok()).collect();
@@ -78,10 +492,690 @@ pub fn get_chat_history_internal(limit: i64) -> Result<Vec<ChatMessage>, String>
     Ok(result)
 }
 
-/// Clears all chat history from the database
-pub fn clear_chat_history_internal() -> Result<(), String> {
-    let conn = init_database()?;
-    conn.execute("DELETE FROM chat_history", [])
-        .map_err(|e| format!("Failed to clear history: {}", e))?;
+/// Retrieves a session's most recent messages at the given context level, in chronological order
+pub fn get_chat_history_by_level_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    level: u8,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn.prepare(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 AND COALESCE(context_level, 0) = ?2 ORDER BY id DESC LIMIT ?3"
+    ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, level, limit], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    let mut result: Vec<ChatMessage> = messages.filter_map(|m| m.ok()).collect();
+
+    // Reverse to get chronological order
+    result.reverse();
+    Ok(result)
+}
+
+/// Maps a user-facing sort field to its SQL expression; anything else is rejected
+fn sort_column(sort_by: &str) -> Result<&'static str, String> {
+    match sort_by {
+        "timestamp" => Ok("timestamp"),
+        "role" => Ok("role"),
+        "level" => Ok("COALESCE(context_level, 0)"),
+        other => Err(format!(
+            "Invalid sort field: {} (expected timestamp, role or level)",
+            other
+        )),
+    }
+}
+
+/// Retrieves a session's messages ordered by `sort_by`, ties broken by insertion order
+pub fn get_chat_history_sorted_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    sort_by: &str,
+    direction: SortDirection,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    // Both the column and direction come from fixed allowlists, never from user text
+    let query = format!(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 ORDER BY {0} {1}, id {1} LIMIT ?2",
+        sort_column(sort_by)?,
+        direction.as_sql()
+    );
+    let mut stmt = conn
+        .prepare(&query)
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, limit], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(messages.filter_map(|m| m.ok()).collect())
+}
+
+/// Sums token usage per day (YYYY-MM-DD), oldest first
+pub fn get_daily_token_usage_internal(db: &DbPoolSlot) -> Result<Vec<(String, u64)>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT date(timestamp), SUM(token_count) FROM chat_history
+            WHERE token_count IS NOT NULL
+            GROUP BY date(timestamp)
+            ORDER BY date(timestamp)",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let days = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(days.filter_map(|d| d.ok()).collect())
+}
+
+/// Number of words of context kept around a search hit
+const SNIPPET_WORDS: usize = 20;
+
+/// Searches a session's message content, newest first. Uses the FTS5 index when available and
+/// falls back to a LIKE scan otherwise.
+pub fn search_chat_history_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<SearchResult>, String> {
+    let conn = connection(db)?;
+
+    let has_fts: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'chat_fts')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to check search index: {}", e))?;
+
+    let row_to_result = |row: &rusqlite::Row| -> rusqlite::Result<SearchResult> {
+        let message = row_to_message(row)?;
+        let snippet = match row.get::<_, Option<String>>(8)? {
+            Some(snippet) => snippet,
+            None => build_snippet(&message.content, query),
+        };
+        Ok(SearchResult { message, snippet })
+    };
+
+    let results = if has_fts {
+        // Quote the query as a phrase so FTS syntax characters are matched literally
+        let fts_query = format!("\"{}\"", query.replace('"', "\"\""));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT c.id, c.timestamp, c.role, c.content, COALESCE(c.context_level, 0), c.token_count, c.bookmarked, c.metadata,
+                snippet(chat_fts, 0, '', '', '…', {})
+            FROM chat_fts JOIN chat_history c ON c.id = chat_fts.rowid
+            WHERE chat_fts MATCH ?1 AND c.session_id = ?3 ORDER BY c.id DESC LIMIT ?2",
+            SNIPPET_WORDS
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params![fts_query, limit, session_id], row_to_result)
+            .map_err(|e| format!("Failed to query: {}", e))?;
+        rows.filter_map(|r| r.ok()).collect()
+    } else {
+        let pattern = format!(
+            "%{}%",
+            query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata, NULL
+            FROM chat_history WHERE content LIKE ?1 ESCAPE '\\' AND session_id = ?3 ORDER BY id DESC LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let rows = stmt
+            .query_map(params![pattern, limit, session_id], row_to_result)
+            .map_err(|e| format!("Failed to query: {}", e))?;
+        rows.filter_map(|r| r.ok()).collect()
+    };
+
+    Ok(results)
+}
+
+/// Builds a snippet of about `SNIPPET_WORDS` words centered on the first match of `query`
+fn build_snippet(content: &str, query: &str) -> String {
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let query_lower = query.to_lowercase();
+    let first_query_word = query_lower.split_whitespace().next().unwrap_or_default();
+
+    let hit = words
+        .iter()
+        .position(|w| w.to_lowercase().contains(first_query_word))
+        .unwrap_or(0);
+    let start = hit.saturating_sub(SNIPPET_WORDS / 2);
+    let end = (start + SNIPPET_WORDS).min(words.len());
+
+    let mut snippet = words[start..end].join(" ");
+    if start > 0 {
+        snippet.insert_str(0, "… ");
+    }
+    if end < words.len() {
+        snippet.push_str(" …");
+    }
+    snippet
+}
+
+/// Flips a message's bookmark flag, returning the new value
+pub fn toggle_bookmark_internal(db: &DbPoolSlot, id: i64) -> Result<bool, String> {
+    let conn = connection(db)?;
+    conn.query_row(
+        "UPDATE chat_history SET bookmarked = NOT bookmarked WHERE rowid = ?1 RETURNING bookmarked",
+        params![id],
+        |row| row.get(0),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => format!("Message {} not found", id),
+        e => format!("Failed to toggle bookmark: {}", e),
+    })
+}
+
+/// Retrieves a session's most recently bookmarked messages, newest first
+pub fn get_bookmarked_messages_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    limit: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn.prepare(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 AND bookmarked = 1 ORDER BY id DESC LIMIT ?2"
+    ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, limit], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(messages.filter_map(|m| m.ok()).collect())
+}
+
+/// Counts the messages in a session
+pub fn count_chat_messages_internal(db: &DbPoolSlot, session_id: &str) -> Result<u32, String> {
+    let conn = connection(db)?;
+    conn.query_row(
+        "SELECT COUNT(*) FROM chat_history WHERE session_id = ?1",
+        params![session_id],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count as u32)
+    .map_err(|e| format!("Failed to count messages: {}", e))
+}
+
+/// Retrieves every message in a session except the most recent `keep_recent`, oldest first
+pub fn get_messages_before_recent_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    keep_recent: i64,
+) -> Result<Vec<ChatMessage>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn.prepare(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history
+        WHERE session_id = ?1 AND bookmarked = 0 AND id NOT IN (
+            SELECT id FROM chat_history WHERE session_id = ?1 ORDER BY id DESC LIMIT ?2
+        )
+        ORDER BY id ASC"
+    ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let messages = stmt
+        .query_map(params![session_id, keep_recent], row_to_message)
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(messages.filter_map(|m| m.ok()).collect())
+}
+
+/// Retrieves the newest summary message of a session
+pub fn get_latest_summary_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+) -> Result<Option<ChatMessage>, String> {
+    let conn = connection(db)?;
+    conn.query_row(
+        "SELECT rowid AS id, timestamp, role, content, COALESCE(context_level, 0), token_count, bookmarked, metadata FROM chat_history WHERE session_id = ?1 AND role = 'summary' ORDER BY id DESC LIMIT 1",
+        params![session_id],
+        row_to_message,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to query summary: {}", e))
+}
+
+/// Replaces the given messages with a single summary message in one transaction
+pub fn replace_with_summary_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    ids: &[i64],
+    timestamp: &str,
+    summary: &str,
+) -> Result<i64, String> {
+    let mut conn = connection(db)?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    for id in ids {
+        // Bookmarked messages are kept verbatim
+        tx.execute(
+            "DELETE FROM chat_history WHERE rowid = ?1 AND bookmarked = 0",
+            params![id],
+        )
+        .map_err(|e| format!("Failed to delete summarized message: {}", e))?;
+    }
+    tx.execute(
+        "INSERT INTO chat_history (session_id, timestamp, role, content, context_level) VALUES (?1, ?2, 'summary', ?3, 0)",
+        params![session_id, timestamp, summary],
+    )
+    .map_err(|e| format!("Failed to store summary: {}", e))?;
+    let summary_id = tx.last_insert_rowid();
+    tx.commit()
+        .map_err(|e| format!("Failed to commit summary: {}", e))?;
+    Ok(summary_id)
+}
+
+/// Clears a session's chat history
+pub fn clear_chat_history_internal(db: &DbPoolSlot, session_id: &str) -> Result<(), String> {
+    let conn = connection(db)?;
+    conn.execute(
+        "DELETE FROM chat_history WHERE session_id = ?1",
+        params![session_id],
+    )
+    .map_err(|e| format!("Failed to clear history: {}", e))?;
+    Ok(())
+}
+
+/// Deletes a single chat message by its row ID
+pub fn delete_chat_message_internal(db: &DbPoolSlot, id: i64) -> Result<(), String> {
+    let conn = connection(db)?;
+    conn.execute("DELETE FROM chat_history WHERE rowid = ?1", params![id])
+        .map_err(|e| format!("Failed to delete message: {}", e))?;
+    Ok(())
+}
+
+/// Deletes a session's chat messages older than the given timestamp, returning how many
+/// were removed
+pub fn delete_chat_messages_before_internal(
+    db: &DbPoolSlot,
+    session_id: &str,
+    timestamp: &str,
+) -> Result<usize, String> {
+    let conn = connection(db)?;
+    conn.execute(
+        "DELETE FROM chat_history WHERE session_id = ?1 AND timestamp < ?2",
+        params![session_id, timestamp],
+    )
+    .map_err(|e| format!("Failed to delete messages: {}", e))
+}
+
+/// Stores a deep research result, returning its ID
+pub fn store_deep_research_result(
+    db: &DbPoolSlot,
+    session_id: &str,
+    timestamp: &str,
+    prompt_used: &str,
+    insights: &str,
+    token_count: Option<u32>,
+) -> Result<i64, String> {
+    let conn = connection(db)?;
+    conn.execute(
+        "INSERT INTO deep_research_results (session_id, timestamp, prompt_used, insights, token_count) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![session_id, timestamp, prompt_used, insights, token_count],
+    )
+    .map_err(|e| format!("Failed to store deep research result: {}", e))?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Retrieves the most recent deep research results, newest first
+pub fn get_deep_research_history_internal(
+    db: &DbPoolSlot,
+    limit: i64,
+) -> Result<Vec<DeepResearchRecord>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, session_id, timestamp, prompt_used, insights, token_count FROM deep_research_results ORDER BY timestamp DESC, id DESC LIMIT ?1",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let records = stmt
+        .query_map(params![limit], |row| {
+            Ok(DeepResearchRecord {
+                id: row.get(0)?,
+                session_id: row.get(1)?,
+                timestamp: row.get(2)?,
+                prompt_used: row.get(3)?,
+                insights: row.get(4)?,
+                token_count: row.get(5)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(records.filter_map(|r| r.ok()).collect())
+}
+
+/// Deletes a deep research result by ID
+pub fn delete_deep_research_record_internal(db: &DbPoolSlot, id: i64) -> Result<(), String> {
+    let conn = connection(db)?;
+    let deleted = conn
+        .execute(
+            "DELETE FROM deep_research_results WHERE id = ?1",
+            params![id],
+        )
+        .map_err(|e| format!("Failed to delete deep research result: {}", e))?;
+    if deleted == 0 {
+        return Err(format!("Deep research result {} not found", id));
+    }
+    Ok(())
+}
+
+/// Creates a named conversation session
+pub fn create_session_internal(
+    db: &DbPoolSlot,
+    id: &str,
+    name: &str,
+    created_at: &str,
+) -> Result<(), String> {
+    let conn = connection(db)?;
+    conn.execute(
+        "INSERT INTO chat_sessions (id, name, created_at) VALUES (?1, ?2, ?3)",
+        params![id, name, created_at],
+    )
+    .map_err(|e| format!("Failed to create session: {}", e))?;
+    Ok(())
+}
+
+/// Lists all sessions with their message counts, most recently active first
+pub fn list_sessions_internal(db: &DbPoolSlot) -> Result<Vec<ChatSession>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT s.id, s.name, COUNT(c.id), COALESCE(MAX(c.timestamp), s.created_at) AS last_message_at,
+                s.forked_from_session_id
+            FROM chat_sessions s LEFT JOIN chat_history c ON c.session_id = s.id
+            GROUP BY s.id
+            ORDER BY last_message_at DESC",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let sessions = stmt
+        .query_map([], |row| {
+            Ok(ChatSession {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                message_count: row.get::<_, i64>(2)? as u32,
+                last_message_at: row.get(3)?,
+                forked_from_session_id: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(sessions.filter_map(|s| s.ok()).collect())
+}
+
+/// Creates `new_id` as a copy of `source_id` up to and including `message_id`.
+/// Returns the number of copied messages.
+pub fn fork_session_internal(
+    db: &DbPoolSlot,
+    source_id: &str,
+    message_id: i64,
+    new_id: &str,
+    created_at: &str,
+) -> Result<usize, String> {
+    let mut conn = connection(db)?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    let in_session: bool = tx
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM chat_history WHERE id = ?1 AND session_id = ?2)",
+            params![message_id, source_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to look up message: {}", e))?;
+    if !in_session {
+        return Err(format!(
+            "Message {} not found in session {}",
+            message_id, source_id
+        ));
+    }
+
+    let source_name: String = tx
+        .query_row(
+            "SELECT name FROM chat_sessions WHERE id = ?1",
+            params![source_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to look up session: {}", e))?;
+    tx.execute(
+        "INSERT INTO chat_sessions (id, name, created_at, forked_from_session_id)
+        VALUES (?1, ?2, ?3, ?4)",
+        params![
+            new_id,
+            format!("{} (fork)", source_name),
+            created_at,
+            source_id
+        ],
+    )
+    .map_err(|e| format!("Failed to create session: {}", e))?;
+
+    let copied = tx
+        .execute(
+            "INSERT INTO chat_history
+                (timestamp, role, content, context_level, metadata, token_count, bookmarked, session_id)
+            SELECT timestamp, role, content, context_level, metadata, token_count, bookmarked, ?1
+            FROM chat_history
+            WHERE session_id = ?2 AND id <= ?3
+            ORDER BY id",
+            params![new_id, source_id, message_id],
+        )
+        .map_err(|e| format!("Failed to copy messages: {}", e))?;
+
+    tx.commit()
+        .map_err(|e| format!("Failed to commit session fork: {}", e))?;
+    Ok(copied)
+}
+
+/// Returns true if a session with this ID exists
+pub fn session_exists_internal(db: &DbPoolSlot, id: &str) -> Result<bool, String> {
+    let conn = connection(db)?;
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM chat_sessions WHERE id = ?1)",
+        params![id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to look up session: {}", e))
+}
+
+/// Renames a session
+pub fn rename_session_internal(db: &DbPoolSlot, id: &str, name: &str) -> Result<(), String> {
+    let conn = connection(db)?;
+    let updated = conn
+        .execute(
+            "UPDATE chat_sessions SET name = ?1 WHERE id = ?2",
+            params![name, id],
+        )
+        .map_err(|e| format!("Failed to rename session: {}", e))?;
+    if updated == 0 {
+        return Err(format!("Session {} not found", id));
+    }
     Ok(())
 }
+
+/// Deletes a session together with all of its messages and research results
+pub fn delete_session_internal(db: &DbPoolSlot, id: &str) -> Result<(), String> {
+    let mut conn = connection(db)?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    tx.execute(
+        "DELETE FROM chat_history WHERE session_id = ?1",
+        params![id],
+    )
+    .map_err(|e| format!("Failed to delete session messages: {}", e))?;
+    tx.execute(
+        "DELETE FROM deep_research_results WHERE session_id = ?1",
+        params![id],
+    )
+    .map_err(|e| format!("Failed to delete session research results: {}", e))?;
+    tx.execute("DELETE FROM chat_sessions WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete session: {}", e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to commit session deletion: {}", e))
+}
+
+/// Counts user messages per hour of day (0-23), including hours with no messages
+pub fn get_hourly_message_distribution_internal(
+    db: &DbPoolSlot,
+) -> Result<Vec<HourlyCount>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE hours(hour) AS (
+                SELECT 0 UNION ALL SELECT hour + 1 FROM hours WHERE hour < 23
+            )
+            SELECT hours.hour, COUNT(chat_history.id)
+            FROM hours
+            LEFT JOIN chat_history
+                ON CAST(strftime('%H', chat_history.timestamp) AS INTEGER) = hours.hour
+                AND chat_history.role = 'user'
+            GROUP BY hours.hour
+            ORDER BY hours.hour",
+        )
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let counts = stmt
+        .query_map([], |row| {
+            Ok(HourlyCount {
+                hour: row.get::<_, i64>(0)? as u8,
+                count: row.get::<_, i64>(1)? as u64,
+            })
+        })
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(counts.filter_map(|c| c.ok()).collect())
+}
+
+/// Stores a new event hook
+pub fn insert_event_hook(db: &DbPoolSlot, hook: &EventHook) -> Result<(), String> {
+    let conn = connection(db)?;
+    conn.execute(
+        "INSERT INTO hooks (id, event_name, webhook_url, method, enabled) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![hook.id, hook.event_name, hook.webhook_url, hook.method, hook.enabled],
+    )
+    .map_err(|e| format!("Failed to store hook: {}", e))?;
+    Ok(())
+}
+
+/// Lists all registered event hooks
+pub fn list_event_hooks_internal(db: &DbPoolSlot) -> Result<Vec<EventHook>, String> {
+    let conn = connection(db)?;
+    let mut stmt = conn
+        .prepare("SELECT id, event_name, webhook_url, method, enabled FROM hooks ORDER BY rowid")
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+    let hooks = stmt
+        .query_map([], |row| {
+            Ok(EventHook {
+                id: row.get(0)?,
+                event_name: row.get(1)?,
+                webhook_url: row.get(2)?,
+                method: row.get(3)?,
+                enabled: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query: {}", e))?;
+
+    Ok(hooks.filter_map(|h| h.ok()).collect())
+}
+
+/// Deletes an event hook by ID
+pub fn delete_event_hook_internal(db: &DbPoolSlot, id: &str) -> Result<(), String> {
+    let conn = connection(db)?;
+    let deleted = conn
+        .execute("DELETE FROM hooks WHERE id = ?1", params![id])
+        .map_err(|e| format!("Failed to delete hook: {}", e))?;
+    if deleted == 0 {
+        return Err("Hook not found".to_string());
+    }
+    Ok(())
+}
+
+/// Flips the enabled flag of an event hook, returning the new value
+pub fn toggle_event_hook_internal(db: &DbPoolSlot, id: &str) -> Result<bool, String> {
+    let conn = connection(db)?;
+    let updated = conn
+        .execute(
+            "UPDATE hooks SET enabled = NOT enabled WHERE id = ?1",
+            params![id],
+        )
+        .map_err(|e| format!("Failed to toggle hook: {}", e))?;
+    if updated == 0 {
+        return Err("Hook not found".to_string());
+    }
+    conn.query_row(
+        "SELECT enabled FROM hooks WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to read hook: {}", e))
+}
+
+/// Size of the database file plus its write-ahead log, if any
+fn database_size_bytes() -> Result<u64, String> {
+    let db_path = get_db_path()?;
+    let mut wal_path = db_path.clone().into_os_string();
+    wal_path.push("-wal");
+    Ok([db_path.into_os_string(), wal_path]
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum())
+}
+
+/// Checkpoints the WAL and rebuilds the database file to release free pages
+pub fn vacuum_database_internal(db: &DbPoolSlot) -> Result<VacuumStats, String> {
+    let conn = connection(db)?;
+    let size_before_bytes = database_size_bytes()?;
+
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| format!("Failed to checkpoint database: {}", e))?;
+    conn.execute_batch("VACUUM")
+        .map_err(|e| format!("Failed to vacuum database: {}", e))?;
+
+    let size_after_bytes = database_size_bytes()?;
+    Ok(VacuumStats {
+        size_before_bytes,
+        size_after_bytes,
+        bytes_freed: size_before_bytes.saturating_sub(size_after_bytes),
+    })
+}
+
+/// Message counts, date range and on-disk size for the storage view
+pub fn get_database_stats_internal(db: &DbPoolSlot) -> Result<DatabaseStats, String> {
+    let conn = connection(db)?;
+    let (total_messages, oldest_message, newest_message) = conn
+        .query_row(
+            "SELECT COUNT(*), MIN(timestamp), MAX(timestamp) FROM chat_history",
+            [],
+            |row| Ok((row.get::<_, i64>(0)? as u32, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Failed to read message stats: {}", e))?;
+    let sessions_count = conn
+        .query_row("SELECT COUNT(*) FROM chat_sessions", [], |row| {
+            row.get::<_, i64>(0)
+        })
+        .map_err(|e| format!("Failed to count sessions: {}", e))? as u32;
+
+    Ok(DatabaseStats {
+        total_messages,
+        size_bytes: database_size_bytes()?,
+        oldest_message,
+        newest_message,
+        sessions_count,
+    })
+}
@@ -0,0 +1,63 @@
+//! Chat history export to Markdown and JSON
+
+use crate::models::ChatMessage;
+use std::path::Path;
+
+/// Supported export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+impl ExportFormat {
+    /// Parses the format name passed from the frontend
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "json" => Ok(ExportFormat::Json),
+            other => Err(format!("Unsupported export format: {}", other)),
+        }
+    }
+
+    /// File extension used for auto-generated export names
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Renders messages as Markdown, one section per message
+pub fn render_markdown(messages: &[ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|msg| {
+            format!(
+                "### {} ({})\n\n{}\n\n---\n",
+                msg.role, msg.timestamp, msg.content
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes messages to `path` in the given format
+pub fn write_export(
+    messages: &[ChatMessage],
+    format: ExportFormat,
+    path: &Path,
+) -> Result<(), String> {
+    let content = match format {
+        ExportFormat::Markdown => render_markdown(messages),
+        ExportFormat::Json => serde_json::to_string_pretty(messages)
+            .map_err(|e| format!("Failed to serialize chat history: {}", e))?,
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create export directory: {}", e))?;
+    }
+    std::fs::write(path, content).map_err(|e| format!("Failed to write export: {}", e))
+}
@@ -0,0 +1,43 @@
+//! Geometry helpers for hitbox polygons
+
+use crate::models::{Point2D, ZoneBounds};
+
+/// Ray-casting test: counts how many polygon edges a horizontal ray from `point` crosses.
+/// Polygons with fewer than three points contain nothing.
+pub fn point_in_polygon(point: &Point2D, polygon: &[Point2D]) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+    for i in 0..polygon.len() {
+        let (a, b) = (&polygon[i], &polygon[j]);
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Axis-aligned bounding box of a zone's polygon, or `None` if it has no points
+pub fn polygon_bounds(name: &str, polygon: &[Point2D]) -> Option<ZoneBounds> {
+    let first = polygon.first()?;
+    let initial = ZoneBounds {
+        name: name.to_string(),
+        min_x: first.x,
+        min_y: first.y,
+        max_x: first.x,
+        max_y: first.y,
+    };
+    Some(polygon.iter().fold(initial, |bounds, p| ZoneBounds {
+        min_x: bounds.min_x.min(p.x),
+        min_y: bounds.min_y.min(p.y),
+        max_x: bounds.max_x.max(p.x),
+        max_y: bounds.max_y.max(p.y),
+        ..bounds
+    }))
+}
@@ -0,0 +1,73 @@
+//! AES-GCM encryption at rest for stored API keys
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use sha2::Sha256;
+
+/// First line of an encrypted key file; its absence means the legacy plain-text format
+pub const ENCRYPTED_KEY_MAGIC: &str = "oto-encrypted-key version: 1";
+
+/// Fixed salt mixed with the hostname when deriving the encryption key
+const KEY_DERIVATION_SALT: &[u8] = b"oto-desktop-api-key-salt";
+const KEY_DERIVATION_ITERATIONS: u32 = 10_000;
+const NONCE_LEN: usize = 12;
+
+/// Derives the 256-bit file key from this machine's hostname
+fn derive_key() -> [u8; 32] {
+    let hostname = gethostname::gethostname();
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(
+        hostname.to_string_lossy().as_bytes(),
+        KEY_DERIVATION_SALT,
+        KEY_DERIVATION_ITERATIONS,
+        &mut key,
+    );
+    key
+}
+
+/// Returns true if `content` was written by `encrypt`
+pub fn is_encrypted(content: &str) -> bool {
+    content.lines().next() == Some(ENCRYPTED_KEY_MAGIC)
+}
+
+/// Encrypts `plaintext` into the versioned file format: the magic header line followed by
+/// the base64 nonce and ciphertext
+pub fn encrypt(plaintext: &str) -> Result<String, String> {
+    let cipher = Aes256Gcm::new_from_slice(&derive_key())
+        .map_err(|e| format!("Failed to initialize cipher: {}", e))?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| format!("Failed to encrypt API key: {}", e))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!(
+        "{}\n{}\n",
+        ENCRYPTED_KEY_MAGIC,
+        BASE64.encode(payload)
+    ))
+}
+
+/// Reverses `encrypt`; fails if the file was written on a machine with a different hostname
+pub fn decrypt(content: &str) -> Result<String, String> {
+    let encoded = content
+        .lines()
+        .nth(1)
+        .ok_or_else(|| "Encrypted key file is empty".to_string())?;
+    let payload = BASE64
+        .decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode encrypted key file: {}", e))?;
+    if payload.len() <= NONCE_LEN {
+        return Err("Encrypted key file is truncated".to_string());
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new_from_slice(&derive_key())
+        .map_err(|e| format!("Failed to initialize cipher: {}", e))?;
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Failed to decrypt API key; was it saved on another machine?".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| format!("Decrypted API key is not valid text: {}", e))
+}
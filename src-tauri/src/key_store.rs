@@ -0,0 +1,74 @@
+//! OS keyring storage for API keys. When no keyring is available (e.g. no Secret Service
+//! on Linux) keys stay in their encrypted files instead.
+
+use crate::paths::APP_DIR_NAME;
+
+/// Keys for services that are configured outside the key profiles
+#[derive(Debug, Clone, Copy)]
+pub enum ServiceKey {
+    Anthropic,
+    Search,
+}
+
+impl ServiceKey {
+    fn account(self) -> &'static str {
+        match self {
+            ServiceKey::Anthropic => "service-key/anthropic",
+            ServiceKey::Search => "service-key/search",
+        }
+    }
+}
+
+fn entry(account: &str) -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(APP_DIR_NAME, account)
+}
+
+fn profile_account(profile_name: &str) -> String {
+    format!("api-key/{}", profile_name)
+}
+
+fn set(account: &str, key: &str) -> bool {
+    entry(account)
+        .and_then(|entry| entry.set_password(key))
+        .is_ok()
+}
+
+fn get(account: &str) -> Option<String> {
+    entry(account).and_then(|entry| entry.get_password()).ok()
+}
+
+fn remove(account: &str) {
+    if let Ok(entry) = entry(account) {
+        let _ = entry.delete_credential();
+    }
+}
+
+/// Stores a profile's API key; returns false if the keyring is unavailable
+pub fn store(profile_name: &str, api_key: &str) -> bool {
+    set(&profile_account(profile_name), api_key)
+}
+
+/// Reads a profile's API key, or None if it has none or the keyring is unavailable
+pub fn load(profile_name: &str) -> Option<String> {
+    get(&profile_account(profile_name))
+}
+
+/// Removes a profile's API key, ignoring keys that were never stored
+pub fn delete(profile_name: &str) {
+    remove(&profile_account(profile_name))
+}
+
+/// Stores a service key; returns false if the keyring is unavailable
+pub fn store_service(service: ServiceKey, key: &str) -> bool {
+    set(service.account(), key)
+}
+
+/// Reads a service key, or None if it has none or the keyring is unavailable
+pub fn load_service(service: ServiceKey) -> Option<String> {
+    get(service.account())
+}
+
+/// Removes a service key, ignoring keys that were never stored
+pub fn delete_service(service: ServiceKey) {
+    remove(service.account())
+}
@@ -2,24 +2,54 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 // Module declarations
+mod checksums;
 mod db;
+mod export;
+mod hitbox;
+mod key_encryption;
+mod key_store;
 mod models;
 mod paths;
 mod prompts;
+mod settings_bundle;
 
 // Re-exports for internal use
-use db::{clear_chat_history_internal, get_chat_history_internal, store_chat_message};
-use models::{ChatMessage, ChatResponse, DeepResearchResponse, TextureVersion};
+use db::{
+    clear_chat_history_internal, count_chat_messages_internal, create_session_internal,
+    delete_chat_message_internal, delete_chat_messages_before_internal,
+    delete_deep_research_record_internal, delete_event_hook_internal, delete_session_internal,
+    fork_session_internal, get_bookmarked_messages_internal, get_chat_history_by_level_internal,
+    get_chat_history_by_role_internal, get_chat_history_internal, get_chat_history_page_internal,
+    get_chat_history_range_internal, get_chat_history_sorted_internal,
+    get_daily_token_usage_internal, get_database_stats_internal,
+    get_deep_research_history_internal, get_hourly_message_distribution_internal,
+    get_latest_summary_internal, get_messages_before_recent_internal, insert_event_hook,
+    list_event_hooks_internal, list_sessions_internal, rename_session_internal,
+    replace_with_summary_internal, search_chat_history_internal, session_exists_internal,
+    store_chat_message, store_chat_messages, store_deep_research_result, toggle_bookmark_internal,
+    toggle_event_hook_internal, update_message_metadata_internal, vacuum_database_internal,
+    DEFAULT_SESSION_ID,
+};
+use models::{
+    ApiBackend, AppError, AppEvent, BoundingBox, CharacterModel, ChatMessage, ChatResponse,
+    ChatSession, ClearResult, DatabaseStats, DbPoolStats, DeepResearchRecord, DeepResearchResponse,
+    DownloadStatus, EventHook, GazeTransform, GenerationLock, HitboxBounds, HitboxZone,
+    HourlyCount, ImportResult, KeyProfile, ModelHealthReport, MonitorInfo, OverlayGeometry,
+    PermissionStatus, PlatformInfo, Point2D, PromptPreset, ScreenRegion, SearchResult,
+    ShortcutConfig, SortDirection, StreamChunk, TextureDiff, TextureVersion, TokenStats,
+    VacuumStats,
+};
 use paths::*;
 use prompts::*;
 
 use rdev::{listen, Event, EventType};
 use serde::Serialize;
 use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{Read, Write as IoWrite};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tauri::menu::{Menu, MenuItem};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder};
 use tauri::{command, AppHandle, Emitter, Manager};
@@ -38,10 +68,11 @@ use objc2_app_kit::{NSWindow, NSWindowCollectionBehavior};
 
 // Windows-specific imports
 #[cfg(target_os = "windows")]
-use windows::Win32::Foundation::HWND;
+use windows::Win32::Foundation::{COLORREF, HWND};
 #[cfg(target_os = "windows")]
 use windows::Win32::UI::WindowsAndMessaging::{
-    SetWindowPos, HWND_TOPMOST, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+    GetWindowLongPtrW, SetLayeredWindowAttributes, SetWindowLongPtrW, SetWindowPos, GWL_EXSTYLE,
+    HWND_TOPMOST, LWA_ALPHA, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE, WS_EX_LAYERED,
 };
 
 // Path helper functions are in paths.rs module
@@ -53,12 +84,92 @@ use windows::Win32::UI::WindowsAndMessaging::{
 
 // ============ Download Helpers ============
 
-async fn download_and_extract_zip(url: &str, dest_dir: &PathBuf) -> Result<(), String> {
-    // Download to temp file
-    let response = reqwest::get(url)
+/// Minimum number of new bytes between `download-progress` events
+const DOWNLOAD_PROGRESS_STEP: u64 = 256 * 1024;
+
+/// Path of the in-progress download for `dest_path`, kept so it can be resumed
+fn partial_download_path(dest_path: &Path) -> PathBuf {
+    let mut path = dest_path.as_os_str().to_owned();
+    path.push(".partial");
+    PathBuf::from(path)
+}
+
+/// Path of the ETag or Last-Modified value the `.partial` file was downloaded under
+fn partial_validator_path(partial_path: &Path) -> PathBuf {
+    let mut path = partial_path.as_os_str().to_owned();
+    path.push(".validator");
+    PathBuf::from(path)
+}
+
+/// Total size from a `Content-Range: bytes */<total>` header
+fn content_range_total(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?
+        .rsplit_once('/')?
+        .1
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Downloads `url` to `dest_path`, resuming from the `.partial` file of an interrupted attempt
+/// as long as the remote file is unchanged
+async fn download_with_resume(
+    url: &str,
+    dest_path: &PathBuf,
+    app: Option<&AppHandle>,
+) -> Result<(), String> {
+    use futures_util::StreamExt;
+
+    let partial_path = partial_download_path(dest_path);
+    let validator_path = partial_validator_path(&partial_path);
+    let validator = std::fs::read_to_string(&validator_path)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    // Without a validator there is no way to tell if the remote file changed
+    let mut existing_bytes = match validator {
+        Some(_) => std::fs::metadata(&partial_path)
+            .map(|m| m.len())
+            .unwrap_or(0),
+        None => 0,
+    };
+
+    let mut request = reqwest::Client::new().get(url);
+    if let Some(validator) = validator.as_deref().filter(|_| existing_bytes > 0) {
+        if let Some(app) = app {
+            emit_event(
+                app,
+                "download-resume",
+                json!({ "url": url, "downloaded_bytes": existing_bytes }),
+            );
+        }
+        // If-Range makes the server send the whole file instead if it changed
+        request = request
+            .header(reqwest::header::RANGE, format!("bytes={}-", existing_bytes))
+            .header(reqwest::header::IF_RANGE, validator);
+    }
+
+    let response = request
+        .send()
         .await
         .map_err(|e| format!("Download failed: {}", e))?;
 
+    if response.status() == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // A partial file that already holds every byte just wasn't renamed yet
+        if content_range_total(&response) == Some(existing_bytes) {
+            let _ = std::fs::remove_file(&validator_path);
+            return std::fs::rename(&partial_path, dest_path)
+                .map_err(|e| format!("Failed to finish download: {}", e));
+        }
+        // The partial file doesn't match the remote file; start over on the next attempt
+        let _ = std::fs::remove_file(&partial_path);
+        let _ = std::fs::remove_file(&validator_path);
+        return Err("Partial download is no longer valid, please retry".to_string());
+    }
     if !response.status().is_success() {
         return Err(format!(
             "Download failed with status: {}",
@@ -66,27 +177,174 @@ async fn download_and_extract_zip(url: &str, dest_dir: &PathBuf) -> Result<(), S
         ));
     }
 
-    let bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+    // The server sends the whole file again if it ignores the range or the file changed
+    let resumed = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    if !resumed {
+        existing_bytes = 0;
+        let headers = response.headers();
+        let new_validator = headers
+            .get(reqwest::header::ETAG)
+            .or_else(|| headers.get(reqwest::header::LAST_MODIFIED))
+            .and_then(|v| v.to_str().ok());
+        match new_validator {
+            Some(v) => {
+                if let Some(parent) = validator_path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create directory: {}", e))?;
+                }
+                std::fs::write(&validator_path, v)
+                    .map_err(|e| format!("Failed to save download validator: {}", e))?;
+            }
+            None => {
+                let _ = std::fs::remove_file(&validator_path);
+            }
+        }
+    }
+
+    if let Some(parent) = partial_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&partial_path)
+        .map_err(|e| format!("Failed to open partial download: {}", e))?;
+
+    let total_bytes = response.content_length().map(|len| len + existing_bytes);
+    let update_status = |downloaded_bytes: u64| {
+        let Some(app) = app else {
+            return;
+        };
+        app.state::<AppState>()
+            .download_status
+            .lock()
+            .unwrap()
+            .insert(
+                url.to_string(),
+                DownloadStatus {
+                    in_progress: true,
+                    downloaded_bytes,
+                    total_bytes,
+                },
+            );
+        let percent = total_bytes
+            .filter(|&total| total > 0)
+            .map(|total| (downloaded_bytes as f64 / total as f64 * 100.0) as f32);
+        emit_event(
+            app,
+            "download-progress",
+            json!({
+                "url": url,
+                "downloaded_bytes": downloaded_bytes,
+                "total_bytes": total_bytes,
+                "percent": percent
+            }),
+        );
+    };
+
+    let mut downloaded = existing_bytes;
+    let mut last_reported = downloaded;
+    update_status(downloaded);
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Failed to read response: {}", e))?;
+        file.write_all(&chunk)
+            .map_err(|e| format!("Failed to write partial download: {}", e))?;
+
+        downloaded += chunk.len() as u64;
+        if downloaded - last_reported >= DOWNLOAD_PROGRESS_STEP {
+            last_reported = downloaded;
+            update_status(downloaded);
+        }
+    }
+    update_status(downloaded);
+    drop(file);
+
+    let _ = std::fs::remove_file(&validator_path);
+    std::fs::rename(&partial_path, dest_path)
+        .map_err(|e| format!("Failed to finish download: {}", e))
+}
+
+async fn download_and_extract_zip(
+    url: &str,
+    dest_dir: &PathBuf,
+    app: Option<AppHandle>,
+) -> Result<(), String> {
+    let bytes = download_zip(url, app.as_ref()).await?;
+    extract_zip(bytes, dest_dir, app.as_ref())
+}
+
+/// Downloads a zip archive, returning the archive bytes
+async fn download_zip(url: &str, app: Option<&AppHandle>) -> Result<Vec<u8>, String> {
+    // Download to disk first so an interrupted download can be resumed
+    let archive_path = paths::get_downloads_dir()?.join(format!(
+        "{}.zip",
+        &checksums::sha256_hex(url.as_bytes())[..16]
+    ));
+    let result = download_with_resume(url, &archive_path, app).await;
+    if let Some(app) = app {
+        if let Some(status) = app
+            .state::<AppState>()
+            .download_status
+            .lock()
+            .unwrap()
+            .get_mut(url)
+        {
+            status.in_progress = false;
+        }
+    }
+    result?;
+
+    let bytes =
+        std::fs::read(&archive_path).map_err(|e| format!("Failed to read download: {}", e))?;
+    // Extraction works from memory; the archive is no longer needed on disk
+    let _ = std::fs::remove_file(&archive_path);
+
+    // No digest is published for the archives, so this is informational only; integrity
+    // checks use the per-file manifest written at extraction
+    info!(
+        "[download] {} sha256: {}",
+        url,
+        checksums::sha256_hex(&bytes)
+    );
+    Ok(bytes)
+}
 
+/// Extracts zip archive bytes into `dest_dir` and records a checksum manifest
+fn extract_zip(bytes: Vec<u8>, dest_dir: &PathBuf, app: Option<&AppHandle>) -> Result<(), String> {
     // Create destination directory
     std::fs::create_dir_all(dest_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
 
     // Extract zip
-    let cursor = std::io::Cursor::new(bytes.to_vec());
+    let cursor = std::io::Cursor::new(bytes);
     let mut archive =
         zip::ZipArchive::new(cursor).map_err(|e| format!("Failed to read zip: {}", e))?;
 
-    for i in 0..archive.len() {
+    let mut manifest = checksums::ChecksumManifest::new();
+    let total_entries = archive.len();
+    for i in 0..total_entries {
         let mut file = archive
             .by_index(i)
             .map_err(|e| format!("Failed to read zip entry: {}", e))?;
 
-        let outpath = dest_dir.join(file.name());
+        if let Some(app) = app {
+            emit_event(
+                app,
+                "extract-progress",
+                json!({ "entry": file.name(), "index": i, "total": total_entries }),
+            );
+        }
+
+        // Entries with absolute paths or `..` components would land outside dest_dir
+        let outpath = match file.enclosed_name() {
+            Some(relative) => dest_dir.join(relative),
+            None => return Err(format!("Unsafe path in zip archive: {}", file.name())),
+        };
 
-        if file.name().ends_with('/') {
+        if file.is_dir() {
             std::fs::create_dir_all(&outpath)
                 .map_err(|e| format!("Failed to create directory: {}", e))?;
         } else {
@@ -102,12 +360,42 @@ async fn download_and_extract_zip(url: &str, dest_dir: &PathBuf) -> Result<(), S
             outfile
                 .write_all(&buffer)
                 .map_err(|e| format!("Failed to write file: {}", e))?;
+            manifest.insert(file.name().to_string(), checksums::sha256_hex(&buffer));
         }
     }
 
+    // Record per-file hashes so the installation can be verified later
+    checksums::save_manifest(dest_dir, &manifest)?;
+
     Ok(())
 }
 
+/// Adds a phase duration to the startup metrics, summing repeated phases such as
+/// downloads of several models
+fn record_startup_metric(state: &AppState, key: &str, elapsed: std::time::Duration) {
+    *state
+        .startup_metrics
+        .lock()
+        .unwrap()
+        .entry(key.to_string())
+        .or_insert(0) += elapsed.as_millis() as u64;
+}
+
+#[command]
+async fn get_startup_metrics(
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, u64>, String> {
+    Ok(state.startup_metrics.lock().unwrap().clone())
+}
+
+/// Progress of each model download this session, keyed by URL
+#[command]
+async fn get_download_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, DownloadStatus>, String> {
+    Ok(state.download_status.lock().unwrap().clone())
+}
+
 // ============ Tauri Commands ============
 
 #[derive(Serialize)]
@@ -115,6 +403,8 @@ pub struct InitStatus {
     pub ready: bool,
     pub message: String,
     pub models_path: String,
+    /// Models fetched during this init rather than already on disk
+    pub downloaded_models: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -206,6 +496,213 @@ fn load_transform_config() -> Result<TransformConfig, String> {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DisplaySettings {
+    pub overlay_min_width: u32,
+    pub overlay_min_height: u32,
+    pub talking_speed_wpm: u32,
+    pub overlay_event_log: bool,
+    pub auto_hide_on_fullscreen: bool,
+    /// Periodically re-assert the overlay's topmost state (Windows only)
+    pub always_on_top_refresh: bool,
+    pub always_on_top_refresh_ms: u64,
+    /// Move the overlay to the primary monitor when monitors are added, removed or swapped
+    pub auto_reposition_on_monitor_change: bool,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            overlay_min_width: paths::DEFAULT_OVERLAY_MIN_WIDTH,
+            overlay_min_height: paths::DEFAULT_OVERLAY_MIN_HEIGHT,
+            talking_speed_wpm: DEFAULT_TALKING_SPEED_WPM,
+            overlay_event_log: false,
+            auto_hide_on_fullscreen: false,
+            always_on_top_refresh: true,
+            always_on_top_refresh_ms: DEFAULT_ALWAYS_ON_TOP_REFRESH_MS,
+            auto_reposition_on_monitor_change: false,
+        }
+    }
+}
+
+/// Speaking rate that maps to a TTS speed multiplier of 1.0
+const DEFAULT_TALKING_SPEED_WPM: u32 = 150;
+
+/// Converts a words-per-minute talking speed into a TTS speed multiplier
+fn talking_speed_multiplier(wpm: u32) -> f32 {
+    (wpm as f32 / DEFAULT_TALKING_SPEED_WPM as f32).clamp(0.25, 4.0)
+}
+
+fn load_display_settings() -> Result<DisplaySettings, String> {
+    let settings_path = paths::get_display_settings_path()?;
+    if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read display settings: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse display settings: {}", e))
+    } else {
+        Ok(DisplaySettings::default())
+    }
+}
+
+fn save_display_settings(settings: &DisplaySettings) -> Result<(), String> {
+    let settings_path = paths::get_display_settings_path()?;
+    if let Some(parent) = settings_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize display settings: {}", e))?;
+    std::fs::write(&settings_path, content)
+        .map_err(|e| format!("Failed to save display settings: {}", e))
+}
+
+/// Actions that can be bound to a left click on the tray icon
+const TRAY_LEFT_CLICK_ACTIONS: &[&str] = &[
+    "toggle_overlay",
+    "show_overlay",
+    "open_settings",
+    "open_chat",
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct TraySettings {
+    pub left_click_action: String,
+    pub right_click_shows_menu: bool,
+}
+
+impl Default for TraySettings {
+    fn default() -> Self {
+        Self {
+            left_click_action: "toggle_overlay".to_string(),
+            right_click_shows_menu: true,
+        }
+    }
+}
+
+fn load_tray_settings() -> Result<TraySettings, String> {
+    let settings_path = paths::get_tray_settings_path()?;
+    if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read tray settings: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse tray settings: {}", e))
+    } else {
+        Ok(TraySettings::default())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ScreenshotSettings {
+    pub exclude_overlay: bool,
+    pub max_count: u32,
+    pub auto_cleanup: bool,
+}
+
+impl Default for ScreenshotSettings {
+    fn default() -> Self {
+        Self {
+            exclude_overlay: true,
+            max_count: 500,
+            auto_cleanup: false,
+        }
+    }
+}
+
+fn load_screenshot_settings() -> Result<ScreenshotSettings, String> {
+    let settings_path = paths::get_screenshot_settings_path()?;
+    if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read screenshot settings: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse screenshot settings: {}", e))
+    } else {
+        Ok(ScreenshotSettings::default())
+    }
+}
+
+fn save_screenshot_settings(settings: &ScreenshotSettings) -> Result<(), String> {
+    let settings_path = paths::get_screenshot_settings_path()?;
+    if let Some(parent) = settings_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize screenshot settings: {}", e))?;
+    std::fs::write(&settings_path, content)
+        .map_err(|e| format!("Failed to save screenshot settings: {}", e))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ChatSettings {
+    pub history_sort: SortDirection,
+    pub generate_followups: bool,
+    pub followup_model: String,
+}
+
+impl Default for ChatSettings {
+    fn default() -> Self {
+        Self {
+            history_sort: SortDirection::default(),
+            generate_followups: false,
+            followup_model: "gpt-4.1-mini".to_string(),
+        }
+    }
+}
+
+fn load_chat_settings() -> Result<ChatSettings, String> {
+    let settings_path = paths::get_chat_settings_path()?;
+    if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read chat settings: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse chat settings: {}", e))
+    } else {
+        Ok(ChatSettings::default())
+    }
+}
+
+fn save_chat_settings(settings: &ChatSettings) -> Result<(), String> {
+    let settings_path = paths::get_chat_settings_path()?;
+    if let Some(parent) = settings_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize chat settings: {}", e))?;
+    std::fs::write(&settings_path, content)
+        .map_err(|e| format!("Failed to save chat settings: {}", e))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct OllamaConfig {
+    pub base_url: String,
+    pub model: String,
+}
+
+impl Default for OllamaConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:11434".to_string(),
+            model: "llama3.2".to_string(),
+        }
+    }
+}
+
+fn load_ollama_config() -> Result<OllamaConfig, String> {
+    let config_path = paths::get_ollama_config_path()?;
+    if config_path.exists() {
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read Ollama config: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse Ollama config: {}", e))
+    } else {
+        Ok(OllamaConfig::default())
+    }
+}
+
 /// Maximum depth to search for model files in nested directories
 const MAX_MODEL_SEARCH_DEPTH: u32 = 3;
 
@@ -348,55 +845,78 @@ fn find_texture_folder(model_dir: &PathBuf) -> Option<String> {
     None
 }
 
+/// Default number of model downloads allowed to run at once during init
+const DEFAULT_INIT_PARALLELISM: u8 = 2;
+
+/// Load saved init download parallelism (returns the default if not saved)
+fn load_init_parallelism() -> u8 {
+    paths::get_init_parallelism_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse::<u8>().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(DEFAULT_INIT_PARALLELISM)
+}
+
+#[command]
+async fn set_init_parallelism(limit: u8) -> Result<(), String> {
+    if limit == 0 {
+        return Err("Parallelism must be at least 1".to_string());
+    }
+
+    let path = paths::get_init_parallelism_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, limit.to_string())
+        .map_err(|e| format!("Failed to save init parallelism: {}", e))
+}
+
+#[command]
+async fn get_init_parallelism() -> Result<u8, String> {
+    Ok(load_init_parallelism())
+}
+
 #[command]
 async fn init_app(app: AppHandle) -> Result<InitStatus, String> {
     let models_dir = get_models_dir()?;
+    let state = app.state::<AppState>();
+    // The database is opened during setup, so keep its timing
+    state
+        .startup_metrics
+        .lock()
+        .unwrap()
+        .retain(|key, _| key == "db_init_ms");
 
-    println!("[init_app] Starting initialization...");
-    println!("[init_app] Models dir: {:?}", models_dir);
+    info!("[init_app] Starting initialization...");
+    info!("[init_app] Models dir: {:?}", models_dir);
 
     // Load or create model config
     let mut config = load_model_config().unwrap_or_default();
 
     // Emit progress events to frontend
     let emit_progress = |step: &str, message: &str| {
-        println!("[init_app] {}: {}", step, message);
-        let _ = app.emit("init-progress", json!({ "step": step, "message": message }));
+        info!("[init_app] {}: {}", step, message);
+        emit_event(
+            &app,
+            "init-progress",
+            json!({ "step": step, "message": message }),
+        );
     };
 
-    // Check if model exists
+    // Collect every model that still needs downloading as (name, url, dest_dir)
+    let t = std::time::Instant::now();
+    let mut pending: Vec<(String, String, PathBuf)> = Vec::new();
     let model_dir = models_dir.join(&config.folder);
     if !model_dir.exists() {
-        emit_progress("model", "Downloading model...");
-        match download_and_extract_zip(&config.url, &models_dir).await {
-            Ok(_) => {
-                // Auto-detect model structure after download
-                match detect_model_structure(&models_dir) {
-                    Ok((folder, model_file, texture_folder)) => {
-                        config.folder = folder;
-                        config.model_file = model_file;
-                        config.texture_folder = texture_folder;
-                        save_model_config(&config)?;
-                        emit_progress("model", "Model ready!");
-                    }
-                    Err(e) => {
-                        println!(
-                            "[init_app] WARNING: Could not detect model structure: {}",
-                            e
-                        );
-                        // Use defaults for the default model
-                        save_model_config(&config)?;
-                        emit_progress("model", "Model ready!");
-                    }
-                }
-            }
-            Err(e) => {
-                println!("[init_app] ERROR downloading model: {}", e);
-                return Err(format!("Failed to download model: {}", e));
-            }
-        }
+        pending.push((
+            config.folder.clone(),
+            config.url.clone(),
+            models_dir.clone(),
+        ));
     } else {
-        println!(
+        info!(
             "[init_app] Model already exists at {:?}, skipping download",
             model_dir
         );
@@ -405,14 +925,98 @@ async fn init_app(app: AppHandle) -> Result<InitStatus, String> {
             save_model_config(&config)?;
         }
     }
+    record_startup_metric(&state, "model_check_ms", t.elapsed());
+
+    if !pending.is_empty() {
+        emit_progress("model", "Downloading model...");
+    }
+
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(load_init_parallelism() as usize));
+    let downloads = pending.into_iter().map(|(name, url, dest_dir)| {
+        let app = app.clone();
+        let semaphore = semaphore.clone();
+        async move {
+            // The semaphore is never closed, so a permit is always granted
+            let _permit = semaphore.acquire().await.ok();
+            emit_event(
+                &app,
+                "init-progress",
+                json!({ "model": name, "step": "downloading" }),
+            );
+            let state = app.state::<AppState>();
+            let t = std::time::Instant::now();
+            let downloaded = download_zip(&url, Some(&app)).await;
+            record_startup_metric(&state, "model_download_ms", t.elapsed());
+            let result = downloaded.and_then(|bytes| {
+                let t = std::time::Instant::now();
+                let extracted = extract_zip(bytes, &dest_dir, Some(&app));
+                record_startup_metric(&state, "model_extraction_ms", t.elapsed());
+                extracted
+            });
+            let step = if result.is_ok() { "done" } else { "failed" };
+            emit_event(
+                &app,
+                "init-progress",
+                json!({ "model": name, "step": step }),
+            );
+            (name, dest_dir, result)
+        }
+    });
+    let results = futures_util::future::join_all(downloads).await;
+
+    let mut downloaded_models = Vec::new();
+    let mut failures = Vec::new();
+    for (name, dest_dir, result) in results {
+        match result {
+            Ok(()) => {
+                if name == config.folder {
+                    // Auto-detect model structure after download
+                    match detect_model_structure(&dest_dir) {
+                        Ok((folder, model_file, texture_folder)) => {
+                            config.folder = folder;
+                            config.model_file = model_file;
+                            config.texture_folder = texture_folder;
+                        }
+                        Err(e) => {
+                            warn!("[init_app] Could not detect model structure: {}", e);
+                            // Use defaults for the default model
+                        }
+                    }
+                    save_model_config(&config)?;
+                    emit_progress("model", "Model ready!");
+                }
+                downloaded_models.push(name);
+            }
+            Err(e) => {
+                error!("[init_app] Error downloading model {}: {}", name, e);
+                failures.push(format!("{}: {}", name, e));
+            }
+        }
+    }
+
+    // Only fail outright when nothing usable was downloaded
+    if !failures.is_empty() && downloaded_models.is_empty() {
+        return Err(format!("Failed to download model: {}", failures.join("; ")));
+    }
 
     emit_progress("done", "All ready!");
-    println!("[init_app] Initialization complete!");
+    info!("[init_app] Initialization complete!");
+    let metrics = state.startup_metrics.lock().unwrap().clone();
+    emit_event(&app, "startup-complete", json!(metrics));
 
+    let message = if failures.is_empty() {
+        "Ready".to_string()
+    } else {
+        format!(
+            "Ready, but some models failed to download: {}",
+            failures.join("; ")
+        )
+    };
     Ok(InitStatus {
         ready: true,
-        message: "Ready".to_string(),
+        message,
         models_path: models_dir.to_string_lossy().to_string(),
+        downloaded_models,
     })
 }
 
@@ -426,16 +1030,70 @@ async fn get_paths() -> Result<String, String> {
 
 #[command]
 async fn read_file_as_text(path: String) -> Result<String, String> {
-    tokio::fs::read_to_string(&path)
+    let file_path = normalize_read_path(&path)?;
+    tokio::fs::read_to_string(&file_path)
         .await
         .map_err(|e| format!("Failed to read file {}: {}", path, e))
 }
 
+/// Largest file `read_file_as_bytes` will load into memory
+const DEFAULT_MAX_READ_BYTES: usize = 50 * 1024 * 1024;
+
+/// Resolves a path from the frontend, which may only read files inside the app data
+/// directory. Canonicalizing first means `..` components and symlinks can't escape it.
+fn normalize_read_path(path: &str) -> Result<PathBuf, String> {
+    let base = get_app_data_dir()?
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve app data directory: {}", e))?;
+    let resolved = Path::new(path)
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve {}: {}", path, e))?;
+    if !resolved.starts_with(&base) {
+        return Err(format!(
+            "Reading outside the app data directory is not allowed: {}",
+            path
+        ));
+    }
+    Ok(resolved)
+}
+
 #[command]
 async fn read_file_as_bytes(path: String) -> Result<Vec<u8>, String> {
-    tokio::fs::read(&path)
+    read_file_as_bytes_limited(path, DEFAULT_MAX_READ_BYTES).await
+}
+
+#[command]
+async fn read_file_as_bytes_limited(path: String, max_bytes: usize) -> Result<Vec<u8>, String> {
+    use tokio::io::AsyncReadExt;
+
+    let file_path = normalize_read_path(&path)?;
+    let size = get_file_size(path.clone()).await?;
+    if size > max_bytes as u64 {
+        return Err(format!("File too large: {} bytes", size));
+    }
+
+    let file = tokio::fs::File::open(&file_path)
         .await
-        .map_err(|e| format!("Failed to read file {}: {}", path, e))
+        .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+    // Read one byte past the limit to catch files that grew after the size check
+    let mut bytes = Vec::with_capacity(size as usize);
+    file.take(max_bytes as u64 + 1)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|e| format!("Failed to read file {}: {}", path, e))?;
+    if bytes.len() > max_bytes {
+        return Err(format!("File too large: more than {} bytes", max_bytes));
+    }
+    Ok(bytes)
+}
+
+#[command]
+async fn get_file_size(path: String) -> Result<u64, String> {
+    let file_path = normalize_read_path(&path)?;
+    tokio::fs::metadata(&file_path)
+        .await
+        .map(|metadata| metadata.len())
+        .map_err(|e| format!("Failed to read metadata for {}: {}", path, e))
 }
 
 #[command]
@@ -447,49 +1105,193 @@ async fn is_initialized() -> Result<bool, String> {
 
 // ============ Model Config Commands ============
 
-#[command]
-async fn get_model_config() -> Result<ModelConfig, String> {
-    let config = load_model_config()?;
-    info!(
-        "[get_model_config] Loaded config - folder: {}, model_file: {}",
-        config.folder, config.model_file
-    );
-    Ok(config)
+/// Finds a texture file whose name contains `pattern` (e.g. "texture_00") in a directory
+fn find_texture_file(texture_dir: &Path, pattern: &str) -> Option<PathBuf> {
+    std::fs::read_dir(texture_dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| {
+            p.is_file()
+                && p.extension().is_some_and(|ext| ext == "png")
+                && p.file_name()
+                    .is_some_and(|name| name.to_string_lossy().contains(pattern))
+        })
 }
 
 #[command]
-async fn change_model(app: AppHandle, url: String) -> Result<ModelConfig, String> {
-    let models_dir = get_models_dir()?;
+async fn check_model_health() -> Result<ModelHealthReport, String> {
+    let config = load_model_config().unwrap_or_default();
+    let model_dir = get_models_dir()?.join(&config.folder);
+    let model_file = model_dir.join(&config.model_file);
 
-    println!("[change_model] Changing model to: {}", url);
+    let mut missing_files = Vec::new();
+    if !model_dir.exists() {
+        missing_files.push(model_dir.to_string_lossy().to_string());
+    }
+    if !model_file.exists() {
+        missing_files.push(model_file.to_string_lossy().to_string());
+    }
 
-    // Reset zoom to 100% for new model
-    save_overlay_scale_to_file(1.0)?;
+    let (texture_00, texture_01, has_original_backup, texture_version_count) =
+        match &config.texture_folder {
+            Some(texture_folder) => {
+                let texture_dir = get_texture_dir_for_model(&config.folder, texture_folder)?;
+                let originals_dir = get_originals_dir_for_model(&config.folder, texture_folder)?;
+                let versions_dir = get_versions_dir_for_model(&config.folder, texture_folder)?;
+
+                let texture_00 = find_texture_file(&texture_dir, "texture_00");
+                let texture_01 = find_texture_file(&texture_dir, "texture_01");
+                if texture_00.is_none() {
+                    missing_files.push(
+                        texture_dir
+                            .join("texture_00.png")
+                            .to_string_lossy()
+                            .to_string(),
+                    );
+                }
+                if texture_01.is_none() {
+                    missing_files.push(
+                        texture_dir
+                            .join("texture_01.png")
+                            .to_string_lossy()
+                            .to_string(),
+                    );
+                }
 
-    // Emit progress
-    let _ = app.emit(
-        "model-change-progress",
-        json!({ "status": "downloading", "message": "Downloading new model..." }),
-    );
+                let has_original_backup = std::fs::read_dir(&originals_dir)
+                    .map(|entries| {
+                        entries
+                            .filter_map(|e| e.ok())
+                            .any(|e| e.path().extension().is_some_and(|ext| ext == "png"))
+                    })
+                    .unwrap_or(false);
+
+                let texture_version_count = std::fs::read_dir(&versions_dir)
+                    .map(|entries| {
+                        entries
+                            .filter_map(|e| e.ok())
+                            .filter(|e| e.path().is_dir())
+                            .count()
+                    })
+                    .unwrap_or(0);
 
-    // Clear existing models
-    if models_dir.exists() {
-        std::fs::remove_dir_all(&models_dir)
-            .map_err(|e| format!("Failed to clear models directory: {}", e))?;
-    }
-    std::fs::create_dir_all(&models_dir)
-        .map_err(|e| format!("Failed to create models directory: {}", e))?;
+                (
+                    texture_00,
+                    texture_01,
+                    has_original_backup,
+                    texture_version_count,
+                )
+            }
+            None => (None, None, false, 0),
+        };
 
-    // Download and extract new model
-    download_and_extract_zip(&url, &models_dir).await?;
+    let hitbox_count = read_hitbox_file()
+        .ok()
+        .flatten()
+        .map(|hitbox| hitbox.zones.len())
+        .unwrap_or(0);
 
-    let _ = app.emit(
-        "model-change-progress",
-        json!({ "status": "detecting", "message": "Detecting model structure..." }),
-    );
+    let file_size = |path: &Option<PathBuf>| {
+        path.as_ref()
+            .and_then(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+    };
+
+    Ok(ModelHealthReport {
+        model_dir_exists: model_dir.exists(),
+        model3_json_exists: model_file.exists(),
+        texture_00_exists: texture_00.is_some(),
+        texture_01_exists: texture_01.is_some(),
+        texture_00_size: file_size(&texture_00),
+        texture_01_size: file_size(&texture_01),
+        has_original_backup,
+        texture_version_count,
+        hitbox_count,
+        missing_files,
+    })
+}
+
+/// Re-hashes the extracted model files against the manifest recorded at download time
+#[command]
+async fn verify_model_integrity(app: AppHandle) -> Result<bool, String> {
+    let models_dir = get_models_dir()?;
+    let manifest = checksums::load_manifest(&models_dir)?.ok_or_else(|| {
+        "No checksum manifest found; re-download the model to enable verification".to_string()
+    })?;
+
+    let total = manifest.len();
+    let mut intact = true;
+    for (index, (file, expected)) in manifest.iter().enumerate() {
+        emit_event(
+            &app,
+            "verify-progress",
+            json!({ "file": file, "index": index, "total": total }),
+        );
+
+        let path = models_dir.join(file);
+        if !path.exists() {
+            warn!("[verify_model_integrity] Missing file: {}", file);
+            intact = false;
+            continue;
+        }
+        let actual = checksums::sha256_file(&path)?;
+        if &actual != expected {
+            warn!("[verify_model_integrity] Checksum mismatch: {}", file);
+            intact = false;
+        }
+    }
+
+    info!(
+        "[verify_model_integrity] Checked {} files, intact: {}",
+        total, intact
+    );
+    Ok(intact)
+}
+
+#[command]
+async fn get_model_config() -> Result<ModelConfig, String> {
+    let config = load_model_config()?;
+    info!(
+        "[get_model_config] Loaded config - folder: {}, model_file: {}",
+        config.folder, config.model_file
+    );
+    Ok(config)
+}
+
+#[command]
+async fn change_model(app: AppHandle, url: String) -> Result<ModelConfig, String> {
+    let models_dir = get_models_dir()?;
+
+    info!("[change_model] Changing model to: {}", url);
+
+    // Reset zoom to 100% for new model
+    persist_overlay_scale(&app.state::<AppState>(), 1.0)?;
+
+    // Emit progress
+    emit_event(
+        &app,
+        "model-change-progress",
+        json!({ "status": "downloading", "message": "Downloading new model..." }),
+    );
+
+    // Download and extract new model next to the installed ones
+    let staging = create_staging_dir(&models_dir, "model")?;
+    if let Err(e) = download_and_extract_zip(&url, &staging, Some(app.clone())).await {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    emit_event(
+        &app,
+        "model-change-progress",
+        json!({ "status": "detecting", "message": "Detecting model structure..." }),
+    );
 
-    // Detect model structure
-    let (folder, model_file, texture_folder) = detect_model_structure(&models_dir)?;
+    // Detect model structure and replace only that model's folder
+    let (folder, model_file, texture_folder) = install_staged_model(&models_dir, &staging)?;
+    // The new model takes over from any selected character
+    let _ = get_active_model_path().map(std::fs::remove_file);
 
     // Save new config
     let config = ModelConfig {
@@ -500,15 +1302,16 @@ async fn change_model(app: AppHandle, url: String) -> Result<ModelConfig, String
     };
     save_model_config(&config)?;
 
-    let _ = app.emit(
+    emit_event(
+        &app,
         "model-change-progress",
         json!({ "status": "complete", "message": "Model changed successfully!" }),
     );
 
     // Notify frontend of scale reset
-    let _ = app.emit("overlay-scale-reset", json!({ "scale": 1.0 }));
+    emit_overlay_event(&app, "overlay-scale-reset", json!({ "scale": 1.0 }));
 
-    println!("[change_model] Model changed successfully: {:?}", config);
+    info!("[change_model] Model changed successfully: {:?}", config);
 
     Ok(config)
 }
@@ -527,7 +1330,7 @@ async fn load_model_from_folder(
     let models_dir = get_models_dir()?;
     let source_path = PathBuf::from(&folder_path);
 
-    println!("[load_model_from_folder] Loading from: {}", folder_path);
+    info!("[load_model_from_folder] Loading from: {}", folder_path);
 
     // Validate source folder exists
     if !source_path.exists() || !source_path.is_dir() {
@@ -559,32 +1362,32 @@ async fn load_model_from_folder(
     }
 
     // Reset zoom to 100% for new model
-    save_overlay_scale_to_file(1.0)?;
+    persist_overlay_scale(&app.state::<AppState>(), 1.0)?;
 
     // Emit progress
-    let _ = app.emit(
+    emit_event(
+        &app,
         "model-change-progress",
         json!({ "status": "copying", "message": "Copying model files..." }),
     );
 
-    // Clear existing models
-    if models_dir.exists() {
-        std::fs::remove_dir_all(&models_dir)
-            .map_err(|e| format!("Failed to clear models directory: {}", e))?;
+    // Copy entire folder next to the installed models
+    let staging = create_staging_dir(&models_dir, "model")?;
+    if let Err(e) = copy_dir_recursive(&source_path, &staging) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
     }
-    std::fs::create_dir_all(&models_dir)
-        .map_err(|e| format!("Failed to create models directory: {}", e))?;
-
-    // Copy entire folder to models directory
-    copy_dir_recursive(&source_path, &models_dir)?;
 
-    let _ = app.emit(
+    emit_event(
+        &app,
         "model-change-progress",
         json!({ "status": "detecting", "message": "Detecting model structure..." }),
     );
 
-    // Detect model structure (reuses existing function)
-    let (folder, model_file, texture_folder) = detect_model_structure(&models_dir)?;
+    // Detect model structure and replace only that model's folder
+    let (folder, model_file, texture_folder) = install_staged_model(&models_dir, &staging)?;
+    // The new model takes over from any selected character
+    let _ = get_active_model_path().map(std::fs::remove_file);
 
     // Save config with "local:" prefix to indicate local source
     let config = ModelConfig {
@@ -595,18 +1398,167 @@ async fn load_model_from_folder(
     };
     save_model_config(&config)?;
 
-    let _ = app.emit(
+    emit_event(
+        &app,
         "model-change-progress",
         json!({ "status": "complete", "message": "Model loaded successfully!" }),
     );
 
-    let _ = app.emit("overlay-scale-reset", json!({ "scale": 1.0 }));
+    emit_overlay_event(&app, "overlay-scale-reset", json!({ "scale": 1.0 }));
 
-    println!("[load_model_from_folder] Model loaded: {:?}", config);
+    info!("[load_model_from_folder] Model loaded: {:?}", config);
 
     Ok(config)
 }
 
+/// Creates an empty hidden directory under the models dir to extract into.
+/// Character names can't start with a dot, so it never clashes with a model.
+fn create_staging_dir(models_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let staging = models_dir.join(format!(".{}.tmp", name));
+    if staging.exists() {
+        std::fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to clear staging directory: {}", e))?;
+    }
+    std::fs::create_dir_all(&staging)
+        .map_err(|e| format!("Failed to create staging directory: {}", e))?;
+    Ok(staging)
+}
+
+/// Replaces `target` with the fully extracted `staged` directory
+fn swap_into_place(staged: &Path, target: &Path) -> Result<(), String> {
+    if target.exists() {
+        std::fs::remove_dir_all(target)
+            .map_err(|e| format!("Failed to remove old model {:?}: {}", target, e))?;
+    }
+    std::fs::rename(staged, target).map_err(|e| format!("Failed to move model into place: {}", e))
+}
+
+/// Detects the model in `staging` and moves its top-level folder into the
+/// models dir, leaving every other installed model alone
+fn install_staged_model(
+    models_dir: &Path,
+    staging: &PathBuf,
+) -> Result<(String, String, Option<String>), String> {
+    let result =
+        detect_model_structure(staging).and_then(|(folder, model_file, texture_folder)| {
+            let top = Path::new(&folder)
+                .components()
+                .next()
+                .map(|c| c.as_os_str().to_owned())
+                .ok_or_else(|| "No Live2D model found in extracted files".to_string())?;
+            swap_into_place(&staging.join(&top), &models_dir.join(&top))?;
+            Ok((folder, model_file, texture_folder))
+        });
+    let _ = std::fs::remove_dir_all(staging);
+    result
+}
+
+/// Rejects character names that would escape the models directory
+fn validate_character_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && !name.starts_with('.');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid character model name: {}", name))
+    }
+}
+
+/// Downloads another character into its own folder, keeping the installed ones
+#[command]
+async fn download_character_model(app: AppHandle, name: String, url: String) -> Result<(), String> {
+    validate_character_name(&name)?;
+    let models_dir = get_models_dir()?;
+    let model_dir = models_dir.join(&name);
+
+    // Keep the installed copy until the new one has fully arrived
+    let staging = create_staging_dir(&models_dir, &name)?;
+    if let Err(e) = download_and_extract_zip(&url, &staging, Some(app.clone())).await {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    if find_model_file_recursive(&staging, MAX_MODEL_SEARCH_DEPTH).is_none() {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(format!("No Live2D model (.model3.json) found in {}", url));
+    }
+
+    if let Err(e) = swap_into_place(&staging, &model_dir) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+
+    info!("[download_character_model] Installed {} from {}", name, url);
+    Ok(())
+}
+
+#[command]
+async fn list_available_character_models() -> Result<Vec<CharacterModel>, String> {
+    let models_dir = get_models_dir()?;
+    if !models_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut models: Vec<CharacterModel> = std::fs::read_dir(&models_dir)
+        .map_err(|e| format!("Failed to read models directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir() && !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|entry| {
+            let (model_dir, model_file) =
+                find_model_file_recursive(&entry.path(), MAX_MODEL_SEARCH_DEPTH)?;
+            Some(CharacterModel {
+                name: entry.file_name().to_string_lossy().to_string(),
+                path: model_dir.join(model_file).to_string_lossy().to_string(),
+                has_textures: find_texture_folder(&model_dir).is_some(),
+            })
+        })
+        .collect();
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
+
+/// Switches to an installed character; takes effect on the next `reload_character`
+#[command]
+async fn set_active_character_model(name: String) -> Result<(), String> {
+    validate_character_name(&name)?;
+    let models_dir = get_models_dir()?;
+    let (model_dir, model_file) =
+        find_model_file_recursive(&models_dir.join(&name), MAX_MODEL_SEARCH_DEPTH)
+            .ok_or_else(|| format!("Character model {} is not installed", name))?;
+
+    // Point the model config at the new character so textures and hitboxes follow it
+    let mut config = load_model_config().unwrap_or_default();
+    config.folder = model_dir
+        .strip_prefix(&models_dir)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| name.clone());
+    config.model_file = model_file;
+    config.texture_folder = find_texture_folder(&model_dir);
+    save_model_config(&config)?;
+
+    let active_path = get_active_model_path()?;
+    if let Some(parent) = active_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&active_path, &name)
+        .map_err(|e| format!("Failed to save active model: {}", e))?;
+
+    info!("[set_active_character_model] Active model set to {}", name);
+    Ok(())
+}
+
+fn load_active_character_model() -> Option<String> {
+    get_active_model_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
 /// Recursively copy a directory
 fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
     for entry in std::fs::read_dir(src).map_err(|e| format!("Failed to read dir: {}", e))? {
@@ -628,155 +1580,616 @@ fn copy_dir_recursive(src: &Path, dst: &Path) -> Result<(), String> {
 
 // ============ API Key Commands ============
 
+/// Checks an OpenAI key against the models endpoint (200 = valid, 401 = rejected)
+async fn check_openai_key(client: &reqwest::Client, key: &str) -> Result<bool, String> {
+    let response = client
+        .get("https://api.openai.com/v1/models")
+        .header("Authorization", format!("Bearer {}", key.trim()))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach OpenAI: {}", e))?;
+
+    match response.status() {
+        reqwest::StatusCode::OK => Ok(true),
+        reqwest::StatusCode::UNAUTHORIZED => Ok(false),
+        status => Err(format!("Unexpected response from OpenAI: {}", status)),
+    }
+}
+
+/// Host probed before API calls so offline requests fail fast
+const CONNECTIVITY_PROBE_URL: &str = "https://api.openai.com";
+const CONNECTIVITY_PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+/// How long a probe result is reused before the network is checked again
+const CONNECTIVITY_RECHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Probes the network, reusing the last result for `CONNECTIVITY_RECHECK_INTERVAL`
+async fn probe_network_connectivity(app: &AppHandle, state: &AppState) -> bool {
+    {
+        let last_check = state.last_connectivity_check.lock().unwrap();
+        let last_result = *state.network_online.lock().unwrap();
+        if let (Some(checked_at), Some(online)) = (*last_check, last_result) {
+            if checked_at.elapsed() < CONNECTIVITY_RECHECK_INTERVAL {
+                return online;
+            }
+        }
+    }
+
+    let online = state
+        .http_client
+        .head(CONNECTIVITY_PROBE_URL)
+        .timeout(CONNECTIVITY_PROBE_TIMEOUT)
+        .send()
+        .await
+        .is_ok();
+
+    *state.last_connectivity_check.lock().unwrap() = Some(std::time::Instant::now());
+    let previous = state.network_online.lock().unwrap().replace(online);
+    if previous != Some(online) {
+        info!("[network] Connectivity changed: online={}", online);
+        emit_event(app, "connectivity-changed", json!({ "online": online }));
+    }
+    online
+}
+
+#[command]
+async fn check_network_connectivity(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<bool, String> {
+    Ok(probe_network_connectivity(&app, &state).await)
+}
+
+#[command]
+async fn validate_api_key(state: tauri::State<'_, AppState>, key: String) -> Result<bool, String> {
+    check_openai_key(&state.http_client, &key).await
+}
+
 #[command]
-async fn save_api_key(key: String) -> Result<(), String> {
+async fn save_api_key(
+    state: tauri::State<'_, AppState>,
+    key: String,
+    validate: Option<bool>,
+) -> Result<(), String> {
     info!("[save_api_key] Starting to save API key");
-    let key_path = get_api_key_path()?;
-    info!("[save_api_key] Key path: {:?}", key_path);
-
-    // Ensure parent directory exists
-    if let Some(parent) = key_path.parent() {
-        info!("[save_api_key] Creating parent directory: {:?}", parent);
-        std::fs::create_dir_all(parent).map_err(|e| {
-            error!("[save_api_key] Failed to create directory: {}", e);
-            format!("Failed to create directory: {}", e)
-        })?;
+
+    if validate.unwrap_or(false) && !check_openai_key(&state.http_client, &key).await? {
+        warn!("[save_api_key] API key was rejected by OpenAI");
+        return Err("Invalid API key".to_string());
     }
 
-    std::fs::write(&key_path, &key).map_err(|e| {
-        error!("[save_api_key] Failed to save API key: {}", e);
-        format!("Failed to save API key: {}", e)
+    // The key belongs to whichever profile is active
+    let key = key.trim().to_string();
+    let active_name = load_active_profile_name();
+    let mut profiles = load_key_profiles()?;
+    match profiles.iter_mut().find(|p| p.name == active_name) {
+        Some(profile) => profile.api_key = key,
+        None => profiles.push(KeyProfile {
+            name: active_name,
+            api_key: key,
+            ..default_key_profile()
+        }),
+    }
+    save_key_profiles(&profiles).map_err(|e| {
+        error!("[save_api_key] {}", e);
+        e
     })?;
 
     info!("[save_api_key] API key saved successfully");
     Ok(())
 }
 
+/// Returns the active key profile's API key
 #[command]
 async fn get_api_key() -> Result<Option<String>, String> {
-    let key_path = get_api_key_path()?;
-
-    if key_path.exists() {
-        let key = std::fs::read_to_string(&key_path)
-            .map_err(|e| format!("Failed to read API key: {}", e))?;
-        Ok(Some(key.trim().to_string()))
-    } else {
-        Ok(None)
-    }
+    Ok(active_key_profile()?.map(|p| p.api_key))
 }
 
 #[command]
 async fn has_api_key() -> Result<bool, String> {
-    let key_path = get_api_key_path()?;
-    Ok(key_path.exists())
+    Ok(get_api_key().await?.is_some())
 }
 
-// ============ Prompt Commands ============
+// ============ Key Profile Commands ============
 
-#[command]
-async fn save_system_prompt(prompt: String) -> Result<(), String> {
-    let prompt_path = get_system_prompt_path()?;
+/// Profile used when no profile was picked
+const DEFAULT_PROFILE_NAME: &str = "default";
 
-    if let Some(parent) = prompt_path.parent() {
+fn default_key_profile() -> KeyProfile {
+    KeyProfile {
+        name: DEFAULT_PROFILE_NAME.to_string(),
+        api_key: String::new(),
+        model: "gpt-4.1-2025-04-14".to_string(),
+        max_tokens: 1000,
+    }
+}
+
+/// Loads all key profiles, seeding the default one from a legacy `.api_key` file
+fn load_key_profiles() -> Result<Vec<KeyProfile>, String> {
+    let profiles_path = get_key_profiles_path()?;
+    if profiles_path.exists() {
+        let mut content = std::fs::read_to_string(&profiles_path)
+            .map_err(|e| format!("Failed to read key profiles: {}", e))?;
+        // Files saved before encryption was added are still plain JSON
+        if key_encryption::is_encrypted(&content) {
+            content = key_encryption::decrypt(&content)?;
+        }
+        let mut profiles: Vec<KeyProfile> = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse key profiles: {}", e))?;
+        // Keys kept in the OS keyring are left blank in the file
+        for profile in profiles.iter_mut().filter(|p| p.api_key.is_empty()) {
+            if let Some(key) = key_store::load(&profile.name) {
+                profile.api_key = key;
+            }
+        }
+        return Ok(profiles);
+    }
+
+    let legacy_key_path = get_api_key_path()?;
+    if legacy_key_path.exists() {
+        let key = std::fs::read_to_string(&legacy_key_path)
+            .map_err(|e| format!("Failed to read API key: {}", e))?;
+        return Ok(vec![KeyProfile {
+            api_key: key.trim().to_string(),
+            ..default_key_profile()
+        }]);
+    }
+
+    Ok(Vec::new())
+}
+
+fn save_key_profiles(profiles: &[KeyProfile]) -> Result<(), String> {
+    let profiles_path = get_key_profiles_path()?;
+    if let Some(parent) = profiles_path.parent() {
         std::fs::create_dir_all(parent)
             .map_err(|e| format!("Failed to create directory: {}", e))?;
     }
+    // Keys go to the OS keyring when there is one; the file only keeps them otherwise
+    let stored: Vec<KeyProfile> = profiles
+        .iter()
+        .map(|profile| {
+            if profile.api_key.is_empty() {
+                key_store::delete(&profile.name);
+            }
+            let in_keyring =
+                !profile.api_key.is_empty() && key_store::store(&profile.name, &profile.api_key);
+            KeyProfile {
+                api_key: if in_keyring {
+                    String::new()
+                } else {
+                    profile.api_key.clone()
+                },
+                ..profile.clone()
+            }
+        })
+        .collect();
+    let content = serde_json::to_string_pretty(&stored)
+        .map_err(|e| format!("Failed to serialize key profiles: {}", e))?;
+    std::fs::write(&profiles_path, key_encryption::encrypt(&content)?)
+        .map_err(|e| format!("Failed to save key profiles: {}", e))
+}
 
-    std::fs::write(&prompt_path, &prompt)
-        .map_err(|e| format!("Failed to save system prompt: {}", e))?;
-
-    Ok(())
+/// Whether `path` exists and holds content that isn't encrypted yet
+fn is_plain_text_file(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| !key_encryption::is_encrypted(&content))
+        .unwrap_or(false)
 }
 
+/// Re-saves plain-text API keys, including the Anthropic and search keys, in encrypted
+/// storage. Returns false if there was nothing to migrate.
 #[command]
-async fn get_system_prompt() -> Result<String, String> {
-    let prompt_path = get_system_prompt_path()?;
+async fn migrate_api_key_to_encrypted() -> Result<bool, String> {
+    let mut migrated = false;
+
+    let profiles_path = get_key_profiles_path()?;
+    if !profiles_path.exists() || is_plain_text_file(&profiles_path) {
+        let profiles = load_key_profiles()?;
+        if !profiles.is_empty() {
+            save_key_profiles(&profiles)?;
+            migrated = true;
+
+            // The legacy single-key file is superseded by the encrypted profiles
+            let legacy_key_path = get_api_key_path()?;
+            if legacy_key_path.exists() {
+                std::fs::remove_file(&legacy_key_path)
+                    .map_err(|e| format!("Failed to remove plain-text API key: {}", e))?;
+            }
+        }
+    }
 
-    if prompt_path.exists() {
-        let prompt = std::fs::read_to_string(&prompt_path)
-            .map_err(|e| format!("Failed to read system prompt: {}", e))?;
-        let trimmed = prompt.trim().to_string();
-        if trimmed.is_empty() {
-            Ok(DEFAULT_SYSTEM_PROMPT.to_string())
-        } else {
-            Ok(trimmed)
+    for (service, key_path) in service_key_paths()? {
+        if !is_plain_text_file(&key_path) {
+            continue;
         }
-    } else {
-        Ok(DEFAULT_SYSTEM_PROMPT.to_string())
+        if let Some(key) = load_service_key(service, &key_path)? {
+            save_service_key(service, &key_path, &key)?;
+            migrated = true;
+        }
+    }
+
+    if migrated {
+        info!("[api_key] Migrated API keys to encrypted storage");
     }
+    Ok(migrated)
 }
 
-#[command]
-async fn save_character_prompt(prompt: String) -> Result<(), String> {
-    let prompt_path = get_character_prompt_path()?;
+/// Files the service keys fall back to when there is no OS keyring
+fn service_key_paths() -> Result<[(key_store::ServiceKey, PathBuf); 2], String> {
+    Ok([
+        (key_store::ServiceKey::Anthropic, get_anthropic_key_path()?),
+        (key_store::ServiceKey::Search, get_search_key_path()?),
+    ])
+}
 
-    if let Some(parent) = prompt_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
+/// Saves a service key to the OS keyring, or encrypted to `key_path` when there is none.
+/// An empty key removes it.
+fn save_service_key(
+    service: key_store::ServiceKey,
+    key_path: &Path,
+    key: &str,
+) -> Result<(), String> {
+    let key = key.trim();
+    if key.is_empty() {
+        key_store::delete_service(service);
+    } else if !key_store::store_service(service, key) {
+        if let Some(parent) = key_path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+        return std::fs::write(key_path, key_encryption::encrypt(key)?)
+            .map_err(|e| format!("Failed to save API key: {}", e));
     }
 
-    std::fs::write(&prompt_path, &prompt)
-        .map_err(|e| format!("Failed to save character prompt: {}", e))?;
-
+    // The keyring holds the key now, so no copy stays on disk
+    if key_path.exists() {
+        std::fs::remove_file(key_path).map_err(|e| format!("Failed to remove API key: {}", e))?;
+    }
     Ok(())
 }
 
-#[command]
-async fn get_character_prompt() -> Result<String, String> {
-    let prompt_path = get_character_prompt_path()?;
-
-    if prompt_path.exists() {
-        let prompt = std::fs::read_to_string(&prompt_path)
-            .map_err(|e| format!("Failed to read character prompt: {}", e))?;
-        let trimmed = prompt.trim().to_string();
-        if trimmed.is_empty() {
-            Ok(DEFAULT_CHARACTER_PROMPT.to_string())
-        } else {
-            Ok(trimmed)
-        }
-    } else {
-        Ok(DEFAULT_CHARACTER_PROMPT.to_string())
+/// Reads a service key from the OS keyring, falling back to `key_path`
+fn load_service_key(
+    service: key_store::ServiceKey,
+    key_path: &Path,
+) -> Result<Option<String>, String> {
+    if let Some(key) = key_store::load_service(service) {
+        return Ok(Some(key));
     }
+    if !key_path.exists() {
+        return Ok(None);
+    }
+    let mut key =
+        std::fs::read_to_string(key_path).map_err(|e| format!("Failed to read API key: {}", e))?;
+    // Keys saved before encryption was added are plain text
+    if key_encryption::is_encrypted(&key) {
+        key = key_encryption::decrypt(&key)?;
+    }
+    Ok(Some(key.trim().to_string()).filter(|key| !key.is_empty()))
 }
 
-#[command]
-async fn save_deep_research_prompt(prompt: String) -> Result<(), String> {
-    let prompt_path = get_deep_research_prompt_path()?;
+fn load_active_profile_name() -> String {
+    get_active_profile_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE_NAME.to_string())
+}
 
-    if let Some(parent) = prompt_path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
+#[command]
+async fn save_key_profile(profile: KeyProfile) -> Result<(), String> {
+    if profile.name.trim().is_empty() {
+        return Err("Profile name cannot be empty".to_string());
     }
 
-    std::fs::write(&prompt_path, &prompt)
-        .map_err(|e| format!("Failed to save deep research prompt: {}", e))?;
+    let mut profiles = load_key_profiles()?;
+    match profiles.iter_mut().find(|p| p.name == profile.name) {
+        Some(existing) => *existing = profile,
+        None => profiles.push(profile),
+    }
+    save_key_profiles(&profiles)
+}
 
-    Ok(())
+#[command]
+async fn get_key_profiles() -> Result<Vec<KeyProfile>, String> {
+    load_key_profiles()
 }
 
 #[command]
-async fn get_deep_research_prompt() -> Result<String, String> {
-    let prompt_path = get_deep_research_prompt_path()?;
+async fn delete_key_profile(name: String) -> Result<(), String> {
+    let mut profiles = load_key_profiles()?;
+    let before = profiles.len();
+    profiles.retain(|p| p.name != name);
+    if profiles.len() == before {
+        return Err(format!("Profile '{}' not found", name));
+    }
+    save_key_profiles(&profiles)?;
+    key_store::delete(&name);
 
-    if prompt_path.exists() {
-        let prompt = std::fs::read_to_string(&prompt_path)
-            .map_err(|e| format!("Failed to read deep research prompt: {}", e))?;
-        let trimmed = prompt.trim().to_string();
-        if trimmed.is_empty() {
-            Ok(DEFAULT_DEEP_RESEARCH_PROMPT.to_string())
-        } else {
-            Ok(trimmed)
-        }
-    } else {
-        Ok(DEFAULT_DEEP_RESEARCH_PROMPT.to_string())
+    // Fall back to the default profile if the active one was deleted
+    if load_active_profile_name() == name {
+        let active_path = get_active_profile_path()?;
+        let _ = std::fs::remove_file(active_path);
     }
+    Ok(())
 }
 
 #[command]
-async fn save_dialogue_prompt(prompt: String) -> Result<(), String> {
-    let prompt_path = get_dialogue_prompt_path()?;
+async fn set_active_profile(name: String) -> Result<(), String> {
+    if !load_key_profiles()?.iter().any(|p| p.name == name) {
+        return Err(format!("Profile '{}' not found", name));
+    }
 
-    if let Some(parent) = prompt_path.parent() {
+    let active_path = get_active_profile_path()?;
+    if let Some(parent) = active_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&active_path, &name).map_err(|e| format!("Failed to save active profile: {}", e))
+}
+
+/// The active key profile, falling back to the default profile; None if it has no key.
+/// Every API key lookup goes through this.
+fn active_key_profile() -> Result<Option<KeyProfile>, String> {
+    let profiles = load_key_profiles()?;
+    let active_name = load_active_profile_name();
+    Ok(profiles
+        .iter()
+        .find(|p| p.name == active_name)
+        .or_else(|| profiles.iter().find(|p| p.name == DEFAULT_PROFILE_NAME))
+        .cloned()
+        .filter(|p| !p.api_key.is_empty()))
+}
+
+/// Returns the active key profile, falling back to the default profile
+#[command]
+async fn get_active_profile() -> Result<KeyProfile, String> {
+    active_key_profile()?.ok_or_else(|| "API key not configured".to_string())
+}
+
+#[command]
+async fn save_anthropic_key(key: String) -> Result<(), String> {
+    save_service_key(
+        key_store::ServiceKey::Anthropic,
+        &get_anthropic_key_path()?,
+        &key,
+    )
+    .map_err(|e| format!("Failed to save Anthropic API key: {}", e))?;
+
+    info!("[save_anthropic_key] Anthropic API key saved successfully");
+    Ok(())
+}
+
+#[command]
+async fn get_anthropic_key() -> Result<Option<String>, String> {
+    load_service_key(key_store::ServiceKey::Anthropic, &get_anthropic_key_path()?)
+        .map_err(|e| format!("Failed to read Anthropic API key: {}", e))
+}
+
+#[command]
+async fn save_ollama_config(config: OllamaConfig) -> Result<(), String> {
+    let config_path = paths::get_ollama_config_path()?;
+
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize Ollama config: {}", e))?;
+    std::fs::write(&config_path, content)
+        .map_err(|e| format!("Failed to save Ollama config: {}", e))?;
+
+    info!(
+        "[save_ollama_config] Ollama config saved ({})",
+        config.base_url
+    );
+    Ok(())
+}
+
+#[command]
+async fn get_ollama_config() -> Result<OllamaConfig, String> {
+    load_ollama_config()
+}
+
+/// Quick reachability check against the configured Ollama server
+#[command]
+async fn is_ollama_available(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    let config = load_ollama_config()?;
+    let url = format!("{}/api/tags", config.base_url.trim_end_matches('/'));
+
+    let available = state
+        .http_client
+        .get(&url)
+        .timeout(std::time::Duration::from_secs(2))
+        .send()
+        .await
+        .map(|r| r.status().is_success())
+        .unwrap_or(false);
+
+    Ok(available)
+}
+
+// ============ Prompt Commands ============
+
+/// Longest prompt, in bytes, that can be saved
+const MAX_PROMPT_BYTES: usize = 8192;
+
+fn validate_prompt_length(prompt: &str) -> Result<(), String> {
+    if prompt.len() > MAX_PROMPT_BYTES {
+        return Err(format!(
+            "Prompt too long: {} bytes (max {})",
+            prompt.len(),
+            MAX_PROMPT_BYTES
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the tokenizer used by `model`, defaulting to the GPT-4 `cl100k_base` encoding
+fn tokenizer_for_model(model: &str) -> Result<&'static tiktoken_rs::CoreBPE, String> {
+    use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+
+    static CL100K: std::sync::OnceLock<tiktoken_rs::CoreBPE> = std::sync::OnceLock::new();
+    static O200K: std::sync::OnceLock<tiktoken_rs::CoreBPE> = std::sync::OnceLock::new();
+
+    let o200k = matches!(get_tokenizer(model), Some(Tokenizer::O200kBase));
+    let cell = if o200k { &O200K } else { &CL100K };
+    if let Some(bpe) = cell.get() {
+        return Ok(bpe);
+    }
+    let build = if o200k {
+        tiktoken_rs::o200k_base
+    } else {
+        tiktoken_rs::cl100k_base
+    };
+    let bpe = build().map_err(|e| format!("Failed to load tokenizer: {}", e))?;
+    Ok(cell.get_or_init(|| bpe))
+}
+
+fn count_tokens(text: &str, model: &str) -> Result<u32, String> {
+    let bpe = tokenizer_for_model(model)?;
+    Ok(bpe.encode_with_special_tokens(text).len() as u32)
+}
+
+/// Counts the tokens in `prompt` so the editor can show a live token counter
+#[command]
+async fn count_prompt_tokens(prompt: String, model: String) -> Result<u32, String> {
+    count_tokens(&prompt, &model)
+}
+
+/// Estimates the prompt and history tokens a chat message at `context_level` would send
+#[command]
+async fn estimate_context_tokens(
+    state: tauri::State<'_, AppState>,
+    context_level: u8,
+) -> Result<u32, String> {
+    let model = get_active_profile().await?.model;
+    let system_prompt = apply_prompt_variables(system_prompt_for_level(context_level).await?);
+    let history = context_history(
+        &state,
+        &active_session_id(&state),
+        DEFAULT_CHAT_HISTORY_LIMIT,
+    )?;
+
+    let mut total = count_tokens(&system_prompt, &model)?;
+    for msg in history
+        .iter()
+        .filter(|m| history_included_at_level(m, context_level))
+    {
+        total += count_tokens(&msg.content, &model)?;
+    }
+    Ok(total)
+}
+
+#[command]
+async fn save_system_prompt(prompt: String) -> Result<(), String> {
+    validate_prompt_length(&prompt)?;
+    let prompt_path = get_system_prompt_path()?;
+
+    if let Some(parent) = prompt_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    std::fs::write(&prompt_path, &prompt)
+        .map_err(|e| format!("Failed to save system prompt: {}", e))?;
+
+    Ok(())
+}
+
+#[command]
+async fn get_system_prompt() -> Result<String, String> {
+    let prompt_path = get_system_prompt_path()?;
+
+    if prompt_path.exists() {
+        let prompt = std::fs::read_to_string(&prompt_path)
+            .map_err(|e| format!("Failed to read system prompt: {}", e))?;
+        let trimmed = prompt.trim().to_string();
+        if trimmed.is_empty() {
+            Ok(DEFAULT_SYSTEM_PROMPT.to_string())
+        } else {
+            Ok(trimmed)
+        }
+    } else {
+        Ok(DEFAULT_SYSTEM_PROMPT.to_string())
+    }
+}
+
+#[command]
+async fn save_character_prompt(prompt: String) -> Result<(), String> {
+    validate_prompt_length(&prompt)?;
+    let prompt_path = get_character_prompt_path()?;
+
+    if let Some(parent) = prompt_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    std::fs::write(&prompt_path, &prompt)
+        .map_err(|e| format!("Failed to save character prompt: {}", e))?;
+
+    Ok(())
+}
+
+#[command]
+async fn get_character_prompt() -> Result<String, String> {
+    let prompt_path = get_character_prompt_path()?;
+
+    if prompt_path.exists() {
+        let prompt = std::fs::read_to_string(&prompt_path)
+            .map_err(|e| format!("Failed to read character prompt: {}", e))?;
+        let trimmed = prompt.trim().to_string();
+        if trimmed.is_empty() {
+            Ok(DEFAULT_CHARACTER_PROMPT.to_string())
+        } else {
+            Ok(trimmed)
+        }
+    } else {
+        Ok(DEFAULT_CHARACTER_PROMPT.to_string())
+    }
+}
+
+#[command]
+async fn save_deep_research_prompt(prompt: String) -> Result<(), String> {
+    validate_prompt_length(&prompt)?;
+    let prompt_path = get_deep_research_prompt_path()?;
+
+    if let Some(parent) = prompt_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    std::fs::write(&prompt_path, &prompt)
+        .map_err(|e| format!("Failed to save deep research prompt: {}", e))?;
+
+    Ok(())
+}
+
+#[command]
+async fn get_deep_research_prompt() -> Result<String, String> {
+    let prompt_path = get_deep_research_prompt_path()?;
+
+    if prompt_path.exists() {
+        let prompt = std::fs::read_to_string(&prompt_path)
+            .map_err(|e| format!("Failed to read deep research prompt: {}", e))?;
+        let trimmed = prompt.trim().to_string();
+        if trimmed.is_empty() {
+            Ok(DEFAULT_DEEP_RESEARCH_PROMPT.to_string())
+        } else {
+            Ok(trimmed)
+        }
+    } else {
+        Ok(DEFAULT_DEEP_RESEARCH_PROMPT.to_string())
+    }
+}
+
+#[command]
+async fn save_dialogue_prompt(prompt: String) -> Result<(), String> {
+    validate_prompt_length(&prompt)?;
+    let prompt_path = get_dialogue_prompt_path()?;
+
+    if let Some(parent) = prompt_path.parent() {
         std::fs::create_dir_all(parent)
             .map_err(|e| format!("Failed to create directory: {}", e))?;
     }
@@ -805,6 +2218,116 @@ async fn get_dialogue_prompt() -> Result<String, String> {
     }
 }
 
+// ============ Prompt Preset Commands ============
+
+fn load_prompt_presets() -> Result<Vec<PromptPreset>, String> {
+    let presets_path = get_prompt_presets_path()?;
+    if presets_path.exists() {
+        let content = std::fs::read_to_string(&presets_path)
+            .map_err(|e| format!("Failed to read prompt presets: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse prompt presets: {}", e))
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+fn save_prompt_presets(presets: &[PromptPreset]) -> Result<(), String> {
+    let presets_path = get_prompt_presets_path()?;
+    if let Some(parent) = presets_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(presets)
+        .map_err(|e| format!("Failed to serialize prompt presets: {}", e))?;
+    std::fs::write(&presets_path, content)
+        .map_err(|e| format!("Failed to save prompt presets: {}", e))
+}
+
+/// Writes a prompt to the slot named by `prompt_type`
+async fn save_prompt_by_type(prompt_type: &str, prompt: String) -> Result<(), String> {
+    match prompt_type {
+        "system" => save_system_prompt(prompt).await,
+        "character" => save_character_prompt(prompt).await,
+        "dialogue" => save_dialogue_prompt(prompt).await,
+        "deep_research" => save_deep_research_prompt(prompt).await,
+        other => Err(format!("Unknown prompt type: {}", other)),
+    }
+}
+
+/// Saves a new preset, or updates the preset with the given `id`
+#[command]
+async fn save_prompt_preset(
+    id: Option<String>,
+    name: String,
+    prompt_type: String,
+    content: String,
+) -> Result<PromptPreset, String> {
+    if !["system", "character", "dialogue", "deep_research"].contains(&prompt_type.as_str()) {
+        return Err(format!("Unknown prompt type: {}", prompt_type));
+    }
+
+    let mut presets = load_prompt_presets()?;
+    let preset = match id.and_then(|id| presets.iter_mut().find(|p| p.id == id)) {
+        Some(existing) => {
+            existing.name = name;
+            existing.prompt_type = prompt_type;
+            existing.content = content;
+            existing.clone()
+        }
+        None => {
+            let preset = PromptPreset {
+                id: uuid::Uuid::new_v4().to_string(),
+                name,
+                prompt_type,
+                content,
+                created_at: chrono::Utc::now().to_rfc3339(),
+            };
+            presets.push(preset.clone());
+            preset
+        }
+    };
+
+    save_prompt_presets(&presets)?;
+    Ok(preset)
+}
+
+#[command]
+async fn get_prompt_presets() -> Result<Vec<PromptPreset>, String> {
+    load_prompt_presets()
+}
+
+#[command]
+async fn delete_prompt_preset(id: String) -> Result<(), String> {
+    let mut presets = load_prompt_presets()?;
+    let before = presets.len();
+    presets.retain(|p| p.id != id);
+    if presets.len() == before {
+        return Err("Preset not found".to_string());
+    }
+    save_prompt_presets(&presets)
+}
+
+#[command]
+async fn apply_prompt_preset(id: String, prompt_type: String) -> Result<(), String> {
+    let preset = load_prompt_presets()?
+        .into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| "Preset not found".to_string())?;
+    save_prompt_by_type(&prompt_type, preset.content).await
+}
+
+#[command]
+async fn reset_prompt_to_default(prompt_type: String) -> Result<(), String> {
+    let default_prompt = match prompt_type.as_str() {
+        "system" => DEFAULT_SYSTEM_PROMPT,
+        "character" => DEFAULT_CHARACTER_PROMPT,
+        "dialogue" => DEFAULT_DIALOGUE_PROMPT,
+        "deep_research" => DEFAULT_DEEP_RESEARCH_PROMPT,
+        other => return Err(format!("Unknown prompt type: {}", other)),
+    };
+    save_prompt_by_type(&prompt_type, default_prompt.to_string()).await
+}
+
 // ============ Frontend Logging ============
 
 #[command]
@@ -819,18 +2342,42 @@ fn log_from_frontend(level: String, message: String) {
 // ============ Hitbox Commands ============
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-struct Point2D {
-    x: f64,
-    y: f64,
+struct HitboxData {
+    #[serde(default)]
+    zones: Vec<HitboxZone>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct HitboxData {
+/// Current on-disk hitbox format version
+const HITBOX_FILE_VERSION: u32 = 2;
+
+/// Hitbox file saved before zones existed: a single unnamed polygon
+#[derive(Deserialize)]
+struct HitboxFileV1 {
     points: Vec<Point2D>,
 }
 
+/// Hitbox file with named zones
+#[derive(Serialize, Deserialize)]
+struct HitboxFileV2 {
+    /// Files written before the version field was added are zone files too
+    #[serde(default = "hitbox_file_version")]
+    version: u32,
+    zones: Vec<HitboxZone>,
+}
+
+fn hitbox_file_version() -> u32 {
+    HITBOX_FILE_VERSION
+}
+
 #[command]
-async fn save_hitbox(points: Vec<Point2D>) -> Result<(), String> {
+async fn save_hitbox(zones: Vec<HitboxZone>) -> Result<(), String> {
+    let count = write_hitbox_file(zones)?;
+    info!("[Hitbox] Saved {} zones", count);
+    Ok(())
+}
+
+/// Writes the hitbox zones to disk, returning how many were saved
+fn write_hitbox_file(zones: Vec<HitboxZone>) -> Result<usize, String> {
     let hitbox_path = get_hitbox_path()?;
 
     if let Some(parent) = hitbox_path.parent() {
@@ -838,18 +2385,46 @@ async fn save_hitbox(points: Vec<Point2D>) -> Result<(), String> {
             .map_err(|e| format!("Failed to create directory: {}", e))?;
     }
 
-    let data = HitboxData { points };
+    let data = HitboxFileV2 {
+        version: HITBOX_FILE_VERSION,
+        zones,
+    };
     let json = serde_json::to_string_pretty(&data)
         .map_err(|e| format!("Failed to serialize hitbox: {}", e))?;
 
     std::fs::write(&hitbox_path, json).map_err(|e| format!("Failed to save hitbox: {}", e))?;
 
-    println!("[Hitbox] Saved {} points", data.points.len());
-    Ok(())
+    Ok(data.zones.len())
 }
 
+/// Loads the hitbox, upgrading a pre-zones file on disk and emitting `hitbox-migrated`
 #[command]
-async fn load_hitbox() -> Result<Option<HitboxData>, String> {
+async fn load_hitbox(app: AppHandle) -> Result<Option<HitboxData>, String> {
+    let Some((data, migrated)) = read_hitbox_file_versioned()? else {
+        return Ok(None);
+    };
+
+    if migrated {
+        write_hitbox_file(data.zones.clone())?;
+        info!("[Hitbox] Upgraded legacy hitbox to named zones");
+        emit_event(
+            &app,
+            "hitbox-migrated",
+            json!({ "zones": data.zones.len() }),
+        );
+    }
+
+    info!("[Hitbox] Loaded {} zones", data.zones.len());
+    Ok(Some(data))
+}
+
+/// Reads the hitbox file; legacy files are converted in memory only
+fn read_hitbox_file() -> Result<Option<HitboxData>, String> {
+    Ok(read_hitbox_file_versioned()?.map(|(data, _)| data))
+}
+
+/// Reads the hitbox file, returning the data and whether it was in the legacy format
+fn read_hitbox_file_versioned() -> Result<Option<(HitboxData, bool)>, String> {
     let hitbox_path = get_hitbox_path()?;
 
     if !hitbox_path.exists() {
@@ -859,11 +2434,19 @@ async fn load_hitbox() -> Result<Option<HitboxData>, String> {
     let json = std::fs::read_to_string(&hitbox_path)
         .map_err(|e| format!("Failed to read hitbox: {}", e))?;
 
-    let data: HitboxData =
-        serde_json::from_str(&json).map_err(|e| format!("Failed to parse hitbox: {}", e))?;
+    if let Ok(file) = serde_json::from_str::<HitboxFileV2>(&json) {
+        return Ok(Some((HitboxData { zones: file.zones }, false)));
+    }
 
-    println!("[Hitbox] Loaded {} points", data.points.len());
-    Ok(Some(data))
+    // Treat a legacy flat point list as a single default click zone
+    let legacy: HitboxFileV1 =
+        serde_json::from_str(&json).map_err(|e| format!("Failed to parse hitbox: {}", e))?;
+    let zones = vec![HitboxZone {
+        name: "default".to_string(),
+        interaction_type: "click".to_string(),
+        points: legacy.points,
+    }];
+    Ok(Some((HitboxData { zones }, true)))
 }
 
 #[command]
@@ -872,1189 +2455,7163 @@ async fn clear_hitbox() -> Result<(), String> {
 
     if hitbox_path.exists() {
         std::fs::remove_file(&hitbox_path).map_err(|e| format!("Failed to clear hitbox: {}", e))?;
-        println!("[Hitbox] Cleared hitbox");
+        info!("[Hitbox] Cleared hitbox");
     }
 
     Ok(())
 }
 
-// ============ Chat Commands ============
+/// Tests whether `(x, y)` lies inside the named zone, or the first zone if no name is given
+#[command]
+async fn test_point_in_hitbox(x: f64, y: f64, zone_name: Option<String>) -> Result<bool, String> {
+    let data = read_hitbox_file()?.ok_or_else(|| "No hitbox configured".to_string())?;
+    let zone = match zone_name {
+        Some(name) => data
+            .zones
+            .iter()
+            .find(|zone| zone.name == name)
+            .ok_or_else(|| format!("Hitbox zone {} not found", name))?,
+        None => data
+            .zones
+            .first()
+            .ok_or_else(|| "Hitbox has no zones".to_string())?,
+    };
+    Ok(hitbox::point_in_polygon(&Point2D { x, y }, &zone.points))
+}
 
 #[command]
-async fn send_chat_message(
-    app: AppHandle,
-    message: String,
-    include_screenshot: bool,
-    context_level: u8,
-) -> Result<ChatResponse, String> {
-    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+async fn test_hitbox_bounds() -> Result<HitboxBounds, String> {
+    let zones = read_hitbox_file()?
+        .map(|data| {
+            data.zones
+                .iter()
+                .filter_map(|zone| hitbox::polygon_bounds(&zone.name, &zone.points))
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(HitboxBounds { zones })
+}
 
-    // Get API key
-    let api_key = get_api_key()
-        .await?
-        .ok_or_else(|| "API key not configured".to_string())?;
+// ============ Chat Backends ============
 
-    // Get system prompt based on level
-    let system_prompt = match context_level {
-        1 => {
-            // Level 1: Use dialogue prompt (respond AS the character in direct conversation)
-            get_dialogue_prompt().await?
-        }
-        2 => {
-            // Level 2: Use deep research prompt (respond as analyst)
-            get_deep_research_prompt().await?
-        }
-        _ => {
-            // Level 0: Default system prompt
-            get_system_prompt().await?
-        }
-    };
+const OPENAI_CHAT_URL: &str = "https://api.openai.com/v1/chat/completions";
+const ANTHROPIC_MESSAGES_URL: &str = "https://api.anthropic.com/v1/messages";
+const ANTHROPIC_API_VERSION: &str = "2023-06-01";
 
-    // Take screenshot if enabled (only for level 0)
-    let screenshot_base64 = if include_screenshot && context_level == 0 {
-        let screenshot_path = take_screenshot(app).await?;
-        let screenshot_bytes = std::fs::read(&screenshot_path)
-            .map_err(|e| format!("Failed to read screenshot: {}", e))?;
-        Some(BASE64.encode(&screenshot_bytes))
-    } else {
-        None
-    };
+/// Picks the chat backend and max tokens for a request: `api_backend` when given, otherwise
+/// OpenAI with the active key profile's model
+async fn resolve_chat_backend(
+    api_backend: Option<ApiBackend>,
+) -> Result<(ApiBackend, u32), String> {
+    match api_backend {
+        Some(backend) => Ok((backend, 1000)),
+        None => {
+            let profile = get_active_profile().await?;
+            Ok((
+                ApiBackend::OpenAI {
+                    model: profile.model,
+                },
+                profile.max_tokens,
+            ))
+        }
+    }
+}
 
-    // Get recent chat history for context
-    let history = get_chat_history_internal(10)?;
+/// Loads the API key required by a chat backend
+async fn get_backend_api_key(backend: &ApiBackend) -> Result<String, String> {
+    match backend {
+        ApiBackend::OpenAI { .. } => get_active_profile().await.map(|p| p.api_key),
+        ApiBackend::Anthropic { .. } => get_anthropic_key()
+            .await?
+            .ok_or_else(|| "Anthropic API key not configured".to_string()),
+        // Local Ollama servers don't need a key
+        ApiBackend::Ollama { .. } => Ok(String::new()),
+    }
+}
 
-    // Build messages array with system prompt
-    let mut messages: Vec<Value> = vec![json!({
-        "role": "system",
-        "content": system_prompt
-    })];
+/// Converts OpenAI-style messages into Anthropic's (system, messages) shape
+fn to_anthropic_messages(messages: &[Value]) -> (String, Vec<Value>) {
+    let mut system = Vec::new();
+    let mut converted = Vec::new();
 
-    // Add past messages for context, filtered by level
-    for msg in &history {
-        let include_msg = match context_level {
-            1 => {
-                // Level 1: User + character + assistant (includes AI responses for context)
-                msg.role == "user" || msg.role == "character" || msg.role == "assistant"
-            }
-            2 => {
-                // Level 2: Only user + deep-thought messages
-                msg.role == "user" || msg.role == "deep-thought"
-            }
-            _ => {
-                // Level 0: All except deep-thought
-                msg.role != "deep-thought"
+    for msg in messages {
+        if msg["role"] == "system" {
+            if let Some(text) = msg["content"].as_str() {
+                system.push(text.to_string());
             }
-        };
-
-        if !include_msg {
             continue;
         }
 
-        // Convert custom roles to "assistant" for API compatibility
-        // Add distinct labels for level 1 context so character knows what's what
-        let (role, content) = if msg.role == "character" {
-            if context_level == 1 {
-                (
-                    "assistant",
-                    format!("[Character's Inner Thoughts]: {}", msg.content),
-                )
-            } else {
-                ("assistant", format!("[Character]: {}", msg.content))
-            }
-        } else if msg.role == "assistant" && context_level == 1 {
-            // For Level 1, format assistant messages distinctly
-            (
-                "assistant",
-                format!("[AI Assistant Response]: {}", msg.content),
-            )
-        } else if msg.role == "deep-thought" {
-            ("assistant", format!("[Analysis]: {}", msg.content))
-        } else {
-            (msg.role.as_str(), msg.content.clone())
+        // Image parts use a base64 source block instead of a data URL
+        let content = match msg["content"].as_array() {
+            Some(parts) => Value::Array(
+                parts
+                    .iter()
+                    .map(|part| {
+                        let url = part["image_url"]["url"].as_str().unwrap_or_default();
+                        match url
+                            .strip_prefix("data:")
+                            .and_then(|u| u.split_once(";base64,"))
+                        {
+                            Some((media_type, data)) if part["type"] == "image_url" => json!({
+                                "type": "image",
+                                "source": {
+                                    "type": "base64",
+                                    "media_type": media_type,
+                                    "data": data
+                                }
+                            }),
+                            _ => part.clone(),
+                        }
+                    })
+                    .collect(),
+            ),
+            None => msg["content"].clone(),
         };
 
-        messages.push(json!({
-            "role": role,
-            "content": content
-        }));
+        converted.push(json!({ "role": msg["role"], "content": content }));
     }
 
-    // Add current message (with or without screenshot)
-    if let Some(ref base64) = screenshot_base64 {
-        messages.push(json!({
-            "role": "user",
-            "content": [
-                {
-                    "type": "text",
-                    "text": message.clone()
-                },
-                {
-                    "type": "image_url",
-                    "image_url": {
-                        "url": format!("data:image/png;base64,{}", base64)
-                    }
-                }
-            ]
-        }));
-    } else {
-        messages.push(json!({
-            "role": "user",
-            "content": message.clone()
-        }));
-    }
+    (system.join("\n\n"), converted)
+}
 
-    // Call OpenAI API for main response
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&json!({
-            "model": "gpt-4.1-2025-04-14",
-            "messages": messages,
-            "max_tokens": 1000
-        }))
-        .send()
-        .await
-        .map_err(|e| format!("API request failed: {}", e))?;
+/// Converts OpenAI-style messages into Ollama's schema (images as a base64 list)
+fn to_ollama_messages(messages: &[Value]) -> Vec<Value> {
+    messages
+        .iter()
+        .map(|msg| match msg["content"].as_array() {
+            Some(parts) => {
+                let text = parts
+                    .iter()
+                    .filter_map(|part| part["text"].as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let images: Vec<&str> = parts
+                    .iter()
+                    .filter_map(|part| part["image_url"]["url"].as_str())
+                    .filter_map(|url| url.split_once(";base64,").map(|(_, data)| data))
+                    .collect();
+                json!({ "role": msg["role"], "content": text, "images": images })
+            }
+            None => json!({ "role": msg["role"], "content": msg["content"] }),
+        })
+        .collect()
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API error: {}", error_text));
+/// Builds a chat completion request for the selected backend
+fn build_chat_request(
+    client: &reqwest::Client,
+    backend: &ApiBackend,
+    api_key: &str,
+    messages: &[Value],
+    max_tokens: u32,
+    stream: bool,
+) -> reqwest::RequestBuilder {
+    match backend {
+        ApiBackend::OpenAI { model } => client
+            .post(OPENAI_CHAT_URL)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .header("Content-Type", "application/json")
+            .json(&json!({
+                "model": model,
+                "messages": messages,
+                "max_tokens": max_tokens,
+                "stream": stream
+            })),
+        ApiBackend::Anthropic { model } => {
+            let (system, messages) = to_anthropic_messages(messages);
+            let mut body = json!({
+                "model": model,
+                "max_tokens": max_tokens,
+                "messages": messages,
+                "stream": stream
+            });
+            if !system.is_empty() {
+                body["system"] = json!(system);
+            }
+            client
+                .post(ANTHROPIC_MESSAGES_URL)
+                .header("x-api-key", api_key)
+                .header("anthropic-version", ANTHROPIC_API_VERSION)
+                .header("Content-Type", "application/json")
+                .json(&body)
+        }
+        ApiBackend::Ollama { base_url, model } => client
+            .post(format!("{}/api/chat", base_url.trim_end_matches('/')))
+            .header("Content-Type", "application/json")
+            .json(&json!({
+                "model": model,
+                "messages": to_ollama_messages(messages),
+                "stream": stream,
+                "options": { "num_predict": max_tokens }
+            })),
     }
+}
 
-    let response_json: Value = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+/// Pulls the reply text out of a backend's (non-streaming) response JSON
+fn extract_reply_text(backend: &ApiBackend, response_json: &Value) -> Option<String> {
+    match backend {
+        ApiBackend::OpenAI { .. } => response_json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(|s| s.to_string()),
+        ApiBackend::Anthropic { .. } => {
+            let text = response_json["content"]
+                .as_array()?
+                .iter()
+                .filter_map(|block| block["text"].as_str())
+                .collect::<Vec<_>>()
+                .join("");
+            Some(text)
+        }
+        ApiBackend::Ollama { .. } => response_json["message"]["content"]
+            .as_str()
+            .map(|s| s.to_string()),
+    }
+}
 
-    let main_response = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or("No response")
-        .to_string();
+/// Pulls the total token usage out of a backend's (non-streaming) response JSON
+fn extract_token_count(backend: &ApiBackend, response_json: &Value) -> Option<u32> {
+    let usage = match backend {
+        ApiBackend::OpenAI { .. } => response_json["usage"]["total_tokens"].as_u64(),
+        ApiBackend::Anthropic { .. } => {
+            let usage = &response_json["usage"];
+            Some(usage["input_tokens"].as_u64()? + usage["output_tokens"].as_u64()?)
+        }
+        ApiBackend::Ollama { .. } => Some(
+            response_json["prompt_eval_count"].as_u64()? + response_json["eval_count"].as_u64()?,
+        ),
+    };
+    usage.map(|tokens| tokens as u32)
+}
 
-    // Store messages and generate character comments based on level
-    let timestamp = chrono::Utc::now().to_rfc3339();
-    store_chat_message(&timestamp, "user", &message, context_level)?;
+/// Default number of chat API requests allowed in flight at once
+const DEFAULT_API_CONCURRENCY: usize = 2;
+
+/// Load saved API concurrency limit (returns the default if not saved)
+fn load_api_concurrency() -> usize {
+    paths::get_api_concurrency_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse::<usize>().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or(DEFAULT_API_CONCURRENCY)
+}
 
-    let character_comments = match context_level {
-        1 => {
-            // Level 1: Save response as "character", no separate character comments
-            store_chat_message(&timestamp, "character", &main_response, 1)?;
-            None
-        }
-        2 => {
-            // Level 2: Save response as "deep-thought", no character comments
-            store_chat_message(&timestamp, "deep-thought", &main_response, 2)?;
-            None
-        }
-        _ => {
-            // Level 0: Save as "assistant", then generate character comment
-            store_chat_message(&timestamp, "assistant", &main_response, 0)?;
-
-            // Generate character commentary for level 0 only
-            let char_system_prompt = get_character_prompt().await?;
-
-            let char_messages: Vec<Value> = vec![
-                json!({
-                    "role": "system",
-                    "content": char_system_prompt
-                }),
-                json!({
-                    "role": "user",
-                    "content": format!("Here is the AI response to comment on:\n\n{}", main_response)
-                }),
-            ];
+#[command]
+async fn set_api_concurrency(
+    state: tauri::State<'_, AppState>,
+    limit: usize,
+) -> Result<(), String> {
+    if limit == 0 {
+        return Err("Concurrency limit must be at least 1".to_string());
+    }
 
-            let char_response = client
-                .post("https://api.openai.com/v1/chat/completions")
-                .header("Authorization", format!("Bearer {}", api_key))
-                .header("Content-Type", "application/json")
-                .json(&json!({
-                    "model": "gpt-4.1-2025-04-14",
-                    "messages": char_messages,
-                    "max_tokens": 500
-                }))
-                .send()
-                .await;
+    let path = paths::get_api_concurrency_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, limit.to_string())
+        .map_err(|e| format!("Failed to save API concurrency: {}", e))?;
 
-            match char_response {
-                Ok(resp) if resp.status().is_success() => {
-                    if let Ok(char_json) = resp.json::<Value>().await {
-                        let char_content = char_json["choices"][0]["message"]["content"]
-                            .as_str()
-                            .unwrap_or("");
-                        if !char_content.is_empty() {
-                            // Store character comment at level 0
-                            store_chat_message(&timestamp, "character", char_content, 0)?;
-                            // Return as single comment at end (not randomly inserted)
-                            Some(vec![char_content.trim().to_string()])
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                _ => None,
-            }
-        }
-    };
+    // Requests already holding a permit finish on the old semaphore
+    *state.request_semaphore.lock().unwrap() = Arc::new(tokio::sync::Semaphore::new(limit));
+    Ok(())
+}
 
-    Ok(ChatResponse {
-        main_response,
-        character_comments,
-    })
+/// Waits for a free API request slot; the slot is released when the permit is dropped
+async fn acquire_request_slot(
+    state: &AppState,
+) -> Result<tokio::sync::OwnedSemaphorePermit, String> {
+    let semaphore = state.request_semaphore.lock().unwrap().clone();
+    semaphore
+        .acquire_owned()
+        .await
+        .map_err(|e| format!("Failed to acquire request slot: {}", e))
 }
 
-// Database helper functions (store_chat_message, get_chat_history_internal) are in db.rs
+// ============ Session Commands ============
 
-#[command]
-async fn get_chat_history() -> Result<Vec<ChatMessage>, String> {
-    get_chat_history_internal(100)
+/// Returns the ID of the conversation session new messages go to
+fn active_session_id(state: &AppState) -> String {
+    state.active_session_id.lock().unwrap().clone()
+}
+
+/// Loads the last active session, falling back to the default session
+fn load_active_session_id(state: &AppState) -> String {
+    paths::get_active_session_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .filter(|id| session_exists_internal(&state.db_pool, id).unwrap_or(false))
+        .unwrap_or_else(|| DEFAULT_SESSION_ID.to_string())
+}
+
+fn set_active_session(state: &AppState, session_id: &str) -> Result<(), String> {
+    let path = paths::get_active_session_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, session_id)
+        .map_err(|e| format!("Failed to save active session: {}", e))?;
+    *state.active_session_id.lock().unwrap() = session_id.to_string();
+    Ok(())
 }
 
 #[command]
-async fn clear_chat_history() -> Result<(), String> {
-    clear_chat_history_internal()
+async fn create_session(state: tauri::State<'_, AppState>, name: String) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Session name cannot be empty".to_string());
+    }
+    let id = uuid::Uuid::new_v4().to_string();
+    create_session_internal(&state.db_pool, &id, name, &chrono::Utc::now().to_rfc3339())?;
+    Ok(id)
 }
 
 #[command]
-async fn trigger_deep_research() -> Result<DeepResearchResponse, String> {
-    use std::time::{SystemTime, UNIX_EPOCH};
+async fn list_sessions(state: tauri::State<'_, AppState>) -> Result<Vec<ChatSession>, String> {
+    list_sessions_internal(&state.db_pool)
+}
 
-    let cooldown_path = get_deep_research_cooldown_path()?;
-    let six_hours: u64 = 6 * 60 * 60;
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| e.to_string())?
-        .as_secs();
+#[command]
+async fn get_active_session(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    Ok(active_session_id(&state))
+}
 
-    // Check cooldown
-    if cooldown_path.exists() {
-        let last_time_str = std::fs::read_to_string(&cooldown_path).map_err(|e| e.to_string())?;
-        if let Ok(last_time) = last_time_str.parse::<u64>() {
-            if now - last_time < six_hours {
-                let remaining = six_hours - (now - last_time);
-                // Return cooldown status - frontend will show timer and existing deep thought
-                return Ok(DeepResearchResponse {
-                    on_cooldown: true,
-                    remaining_seconds: remaining,
-                    main_response: String::new(),
-                });
-            }
-        }
+#[command]
+async fn switch_session(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    if !session_exists_internal(&state.db_pool, &session_id)? {
+        return Err(format!("Session {} not found", session_id));
     }
+    set_active_session(&state, &session_id)
+}
 
-    // Not on cooldown - run deep research
-    let api_key = get_api_key().await?.ok_or("API key not configured")?;
-    let deep_prompt = get_deep_research_prompt().await?;
-    let history = get_chat_history_internal(50)?;
-
-    let context = history
-        .iter()
-        .map(|m| format!("[{}]: {}", m.role, m.content))
-        .collect::<Vec<_>>()
-        .join("\n\n");
+/// Copies the active session up to and including `message_id` into a new session and
+/// switches to it. The original session is left untouched.
+#[command]
+async fn fork_session_at(
+    state: tauri::State<'_, AppState>,
+    message_id: i64,
+) -> Result<String, String> {
+    let source_id = active_session_id(&state);
+    let id = uuid::Uuid::new_v4().to_string();
+    let copied = fork_session_internal(
+        &state.db_pool,
+        &source_id,
+        message_id,
+        &id,
+        &chrono::Utc::now().to_rfc3339(),
+    )?;
+    set_active_session(&state, &id)?;
+    info!(
+        "[sessions] Forked {} at message {} into {} ({} messages)",
+        source_id, message_id, id, copied
+    );
+    Ok(id)
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://api.openai.com/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .header("Content-Type", "application/json")
-        .json(&serde_json::json!({
-            "model": "gpt-4o",
-            "messages": [
-                { "role": "system", "content": deep_prompt },
-                { "role": "user", "content": format!("Analyze this conversation history:\n\n{}", context) }
-            ]
-        }))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+#[command]
+async fn rename_session(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+    name: String,
+) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Session name cannot be empty".to_string());
+    }
+    rename_session_internal(&state.db_pool, &session_id, name)
+}
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        error!("[DeepResearch] API error: {}", error_text);
-        return Err(format!("API request failed: {}", error_text));
+#[command]
+async fn delete_session(
+    state: tauri::State<'_, AppState>,
+    session_id: String,
+) -> Result<(), String> {
+    if session_id == DEFAULT_SESSION_ID {
+        return Err("The default session cannot be deleted".to_string());
     }
+    delete_session_internal(&state.db_pool, &session_id)?;
 
-    let response_json: serde_json::Value = response.json().await.map_err(|e| e.to_string())?;
-    let insights = response_json["choices"][0]["message"]["content"]
-        .as_str()
-        .unwrap_or("No insights generated")
-        .to_string();
+    // Fall back to the default session if the active one was removed
+    if active_session_id(&state) == session_id {
+        set_active_session(&state, DEFAULT_SESSION_ID)?;
+    }
+    Ok(())
+}
 
-    // Store with deep-thought marker at level 2
-    let timestamp = chrono::Utc::now().to_rfc3339();
-    store_chat_message(&timestamp, "deep-thought", &insights, 2)?;
+// ============ Database Maintenance ============
 
-    // Update cooldown timestamp
-    if let Some(parent) = cooldown_path.parent() {
-        std::fs::create_dir_all(parent).ok();
-    }
-    std::fs::write(&cooldown_path, now.to_string()).map_err(|e| e.to_string())?;
+/// Database size above which a vacuum runs automatically at startup
+const AUTO_VACUUM_THRESHOLD_BYTES: u64 = 50_000_000;
 
-    Ok(DeepResearchResponse {
-        on_cooldown: false,
-        remaining_seconds: 0,
-        main_response: insights,
-    })
+#[command]
+async fn vacuum_database(state: tauri::State<'_, AppState>) -> Result<VacuumStats, String> {
+    let stats = vacuum_database_internal(&state.db_pool)?;
+    info!(
+        "[db] Vacuum freed {} bytes ({} -> {})",
+        stats.bytes_freed, stats.size_before_bytes, stats.size_after_bytes
+    );
+    Ok(stats)
 }
 
 #[command]
-async fn clear_all_data() -> Result<(), String> {
-    clear_app_data()
+async fn get_database_stats(state: tauri::State<'_, AppState>) -> Result<DatabaseStats, String> {
+    get_database_stats_internal(&state.db_pool)
 }
 
 #[command]
-async fn generate_texture(prompt: String) -> Result<String, String> {
-    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
-    use image::GenericImageView;
+async fn get_db_pool_stats(state: tauri::State<'_, AppState>) -> Result<DbPoolStats, String> {
+    db::get_db_pool_stats_internal(&state.db_pool)
+}
 
-    // Load model config for dynamic paths
-    let config = load_model_config()?;
-    let texture_folder = config
-        .texture_folder
-        .ok_or_else(|| "No texture folder configured for this model".to_string())?;
-    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
-    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+#[command]
+async fn set_wal_autocheckpoint(
+    state: tauri::State<'_, AppState>,
+    pages: u32,
+) -> Result<(), String> {
+    db::set_wal_autocheckpoint_internal(&state.db_pool, pages)
+}
 
-    // Get OpenAI API key
-    let api_key = get_api_key()
-        .await?
-        .ok_or_else(|| "No API key configured".to_string())?;
+#[command]
+async fn get_db_pragma(state: tauri::State<'_, AppState>, name: String) -> Result<String, String> {
+    db::get_db_pragma_internal(&state.db_pool, &name)
+}
 
-    // Discover texture files dynamically
-    let texture_files: Vec<String> = std::fs::read_dir(&texture_dir)
-        .map_err(|e| format!("Failed to read texture directory: {}", e))?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().is_some_and(|ext| ext == "png"))
-        .filter(|e| !e.path().is_dir())
-        .map(|e| e.file_name().to_string_lossy().to_string())
-        .collect();
+/// Reports database stats to the UI and vacuums the database once it grows too large
+fn check_database_stats(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let stats = match get_database_stats_internal(&state.db_pool) {
+        Ok(stats) => stats,
+        Err(e) => {
+            warn!("[startup] Failed to read database stats: {}", e);
+            return;
+        }
+    };
+    emit_event(app, "db-stats", &stats);
 
-    if texture_files.is_empty() {
-        return Err("No texture files found in model".to_string());
+    if stats.size_bytes > AUTO_VACUUM_THRESHOLD_BYTES {
+        info!(
+            "[startup] Database is {} bytes, running automatic vacuum",
+            stats.size_bytes
+        );
+        match vacuum_database_internal(&state.db_pool) {
+            Ok(vacuum) => info!("[startup] Vacuum freed {} bytes", vacuum.bytes_freed),
+            Err(e) => warn!("[startup] Automatic vacuum failed: {}", e),
+        }
     }
+}
 
-    for texture_file in &texture_files {
-        let texture_path = texture_dir.join(texture_file);
-        let original_path = originals_dir.join(texture_file);
-
-        // Ensure we have originals backed up first
-        if !original_path.exists() {
-            if texture_path.exists() {
-                std::fs::create_dir_all(&originals_dir)
-                    .map_err(|e| format!("Failed to create originals dir: {}", e))?;
-                std::fs::copy(&texture_path, &original_path)
-                    .map_err(|e| format!("Failed to backup {}: {}", texture_file, e))?;
-            } else {
-                continue;
-            }
-        }
+// ============ History Summarization ============
 
-        // Load the original image
-        let img = image::open(&original_path)
-            .map_err(|e| format!("Failed to load {}: {}", texture_file, e))?;
+/// Message count above which old history is summarized automatically
+const DEFAULT_AUTO_SUMMARIZE_THRESHOLD: u32 = 200;
+/// Messages left untouched when summarizing automatically
+const AUTO_SUMMARIZE_KEEP_RECENT: u32 = 50;
 
-        let (orig_width, orig_height) = img.dimensions();
-        println!(
-            "[Texture] Processing {} - original dimensions: {}x{}",
-            texture_file, orig_width, orig_height
-        );
+fn load_auto_summarize_threshold() -> u32 {
+    paths::get_auto_summarize_threshold_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse::<u32>().ok())
+        .unwrap_or(DEFAULT_AUTO_SUMMARIZE_THRESHOLD)
+}
 
-        // Downscale to 1024x1024 for OpenAI
-        println!("[Texture] Downscaling to 1024x1024...");
-        let downscaled = img.resize_exact(1024, 1024, image::imageops::FilterType::Lanczos3);
+#[command]
+async fn save_auto_summarize_threshold(count: u32) -> Result<(), String> {
+    if count <= AUTO_SUMMARIZE_KEEP_RECENT {
+        return Err(format!(
+            "Threshold must be greater than {}",
+            AUTO_SUMMARIZE_KEEP_RECENT
+        ));
+    }
 
-        // Encode as PNG bytes
-        let mut png_bytes: Vec<u8> = Vec::new();
-        downscaled
-            .write_to(
-                &mut std::io::Cursor::new(&mut png_bytes),
-                image::ImageFormat::Png,
-            )
-            .map_err(|e| format!("Failed to encode image: {}", e))?;
+    let path = paths::get_auto_summarize_threshold_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, count.to_string())
+        .map_err(|e| format!("Failed to save auto-summarize threshold: {}", e))
+}
 
-        // Create multipart form for OpenAI API
-        let form = reqwest::multipart::Form::new()
-            .text("model", "gpt-image-1.5")
-            .text(
-                "prompt",
-                format!(
-                    "This is a texture atlas for a Live2D anime character. {}. \
-                CRITICAL: Keep every element in its EXACT position. \
-                Preserve all black outlines/lineart. \
-                Only modify what the prompt asks for. \
-                Maintain the same art style and quality.",
-                    prompt
-                ),
-            )
-            .text("size", "1024x1024")
-            .text("background", "transparent")
-            .text("output_format", "png")
-            .part(
-                "image[]",
-                reqwest::multipart::Part::bytes(png_bytes)
-                    .file_name("texture.png")
-                    .mime_str("image/png")
-                    .map_err(|e| format!("Failed to set mime type: {}", e))?,
-            );
+#[command]
+async fn get_auto_summarize_threshold() -> Result<u32, String> {
+    Ok(load_auto_summarize_threshold())
+}
 
-        // Call OpenAI API
-        println!("[Texture] Sending to OpenAI...");
-        let client = reqwest::Client::new();
-        let response = client
-            .post("https://api.openai.com/v1/images/edits")
-            .header("Authorization", format!("Bearer {}", api_key))
-            .multipart(form)
-            .send()
-            .await
-            .map_err(|e| format!("OpenAI API failed for {}: {}", texture_file, e))?;
+/// Replaces all but the newest `keep_recent` messages of a session with one summary message,
+/// leaving bookmarked messages in place
+async fn summarize_session(
+    app: &AppHandle,
+    session_id: &str,
+    keep_recent: u32,
+    api_backend: Option<ApiBackend>,
+) -> Result<String, String> {
+    let state = app.state::<AppState>();
+    let old_messages =
+        get_messages_before_recent_internal(&state.db_pool, session_id, keep_recent as i64)?;
+    let Some(first) = old_messages.first() else {
+        return Err("Not enough messages to summarize".to_string());
+    };
 
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(format!(
-                "OpenAI API error for {}: {}",
-                texture_file, error_text
-            ));
+    let (backend, _) = resolve_chat_backend(api_backend).await?;
+    let api_key = get_backend_api_key(&backend).await?;
+
+    let transcript = old_messages
+        .iter()
+        .map(|m| format!("[{}]: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let messages = vec![
+        json!({
+            "role": "system",
+            "content": "You condense chat transcripts. Keep names, facts, decisions and open questions; drop small talk."
+        }),
+        json!({
+            "role": "user",
+            "content": format!("Please summarize this conversation so far:\n\n{}", transcript)
+        }),
+    ];
+
+    let client = HttpChatClient {
+        app: app.clone(),
+        client: state.http_client.clone(),
+    };
+    let permit = acquire_request_slot(&state).await?;
+    let summary = client
+        .complete(&backend, &api_key, &messages, 1000)
+        .await?
+        .text;
+    drop(permit);
+    if summary.trim().is_empty() {
+        return Err("Summary came back empty".to_string());
+    }
+
+    let ids: Vec<i64> = old_messages.iter().map(|m| m.id).collect();
+    replace_with_summary_internal(
+        &state.db_pool,
+        session_id,
+        &ids,
+        &first.timestamp,
+        summary.trim(),
+    )?;
+    info!(
+        "[summarize] Replaced {} messages in session {} with a summary",
+        ids.len(),
+        session_id
+    );
+    Ok(summary.trim().to_string())
+}
+
+/// Summarizes the session in the background with `backend` once it grows past the
+/// configured threshold
+fn maybe_auto_summarize(app: &AppHandle, session_id: &str, backend: &ApiBackend) {
+    let state = app.state::<AppState>();
+    let count = count_chat_messages_internal(&state.db_pool, session_id).unwrap_or(0);
+    if count <= load_auto_summarize_threshold() {
+        return;
+    }
+    if state.summarizing.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let app = app.clone();
+    let session_id = session_id.to_string();
+    let backend = backend.clone();
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<AppState>();
+        if let Err(e) =
+            summarize_session(&app, &session_id, AUTO_SUMMARIZE_KEEP_RECENT, Some(backend)).await
+        {
+            warn!("[summarize] Auto-summarize failed: {}", e);
         }
+        state.summarizing.store(false, Ordering::SeqCst);
+    });
+}
 
-        let response_json: Value = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse response for {}: {}", texture_file, e))?;
+#[command]
+async fn auto_summarize_history(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    keep_recent: u32,
+    api_backend: Option<ApiBackend>,
+) -> Result<String, String> {
+    if state.summarizing.swap(true, Ordering::SeqCst) {
+        return Err("Summarization already in progress".to_string());
+    }
+    let result =
+        summarize_session(&app, &active_session_id(&state), keep_recent, api_backend).await;
+    state.summarizing.store(false, Ordering::SeqCst);
+    result
+}
 
-        println!("[Texture] Response received, extracting image...");
+// ============ Web Search ============
 
-        // Extract base64 image from response
-        let image_data = response_json["data"][0]["b64_json"]
-            .as_str()
-            .ok_or_else(|| format!("No image in response for {}", texture_file))?;
+/// Default search endpoint used by context level 3
+const BRAVE_SEARCH_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+/// Number of search results appended to the system prompt
+const WEB_SEARCH_RESULT_COUNT: usize = 3;
 
-        // Decode the edited image
-        let decoded = BASE64
-            .decode(image_data)
-            .map_err(|e| format!("Failed to decode {}: {}", texture_file, e))?;
+#[command]
+async fn save_search_api_key(key: String) -> Result<(), String> {
+    save_service_key(key_store::ServiceKey::Search, &get_search_key_path()?, &key)
+        .map_err(|e| format!("Failed to save search API key: {}", e))?;
 
-        let edited_img = image::load_from_memory(&decoded)
-            .map_err(|e| format!("Failed to load edited {}: {}", texture_file, e))?;
+    info!("[save_search_api_key] Search API key saved successfully");
+    Ok(())
+}
 
-        // Upscale back to original dimensions (2048x2048)
-        println!(
-            "[Texture] Upscaling back to {}x{}...",
-            orig_width, orig_height
-        );
-        let upscaled = edited_img.resize_exact(
-            orig_width,
-            orig_height,
-            image::imageops::FilterType::Lanczos3,
-        );
+#[command]
+async fn get_search_api_key() -> Result<Option<String>, String> {
+    load_service_key(key_store::ServiceKey::Search, &get_search_key_path()?)
+        .map_err(|e| format!("Failed to read search API key: {}", e))
+}
 
-        // Save the upscaled image
-        upscaled
-            .save(&texture_path)
-            .map_err(|e| format!("Failed to save {}: {}", texture_file, e))?;
+#[command]
+async fn has_search_api_key() -> Result<bool, String> {
+    Ok(get_search_api_key().await?.is_some())
+}
+
+fn load_search_endpoint() -> String {
+    get_search_endpoint_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .unwrap_or_else(|| BRAVE_SEARCH_URL.to_string())
+}
 
-        println!("[Texture] {} completed successfully", texture_file);
+/// Sets a Brave-compatible search endpoint; `None` restores the default
+#[command]
+async fn save_search_endpoint(endpoint: Option<String>) -> Result<(), String> {
+    let endpoint_path = get_search_endpoint_path()?;
+    match endpoint.map(|url| url.trim().to_string()) {
+        Some(url) if !url.is_empty() => {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err("Search endpoint must be an http(s) URL".to_string());
+            }
+            if let Some(parent) = endpoint_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory: {}", e))?;
+            }
+            std::fs::write(&endpoint_path, url)
+                .map_err(|e| format!("Failed to save search endpoint: {}", e))
+        }
+        _ if endpoint_path.exists() => std::fs::remove_file(&endpoint_path)
+            .map_err(|e| format!("Failed to reset search endpoint: {}", e)),
+        _ => Ok(()),
     }
+}
 
-    // Save this generation as a version
-    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-    let version_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?.join(&timestamp);
-    std::fs::create_dir_all(&version_dir)
-        .map_err(|e| format!("Failed to create version dir: {}", e))?;
+#[command]
+async fn get_search_endpoint() -> Result<String, String> {
+    Ok(load_search_endpoint())
+}
 
-    // Copy all processed textures to the version folder
-    for texture_file in &texture_files {
-        let src = texture_dir.join(texture_file);
-        let dst = version_dir.join(texture_file);
-        if src.exists() {
-            std::fs::copy(&src, &dst)
-                .map_err(|e| format!("Failed to copy {} to version: {}", texture_file, e))?;
+/// Queries the search API and formats the top results as a numbered list
+async fn fetch_web_results(client: &reqwest::Client, query: &str) -> Result<String, String> {
+    let api_key = get_search_api_key()
+        .await?
+        .ok_or_else(|| "Search API key not configured".to_string())?;
+
+    let response = client
+        .get(load_search_endpoint())
+        .query(&[
+            ("q", query),
+            ("count", &WEB_SEARCH_RESULT_COUNT.to_string()),
+        ])
+        .header("Accept", "application/json")
+        .header("X-Subscription-Token", api_key)
+        .send()
+        .await
+        .map_err(|e| format!("Web search failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("Search API error: {}", error_text));
+    }
+
+    let response_json: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse search results: {}", e))?;
+
+    let results: Vec<String> = response_json["web"]["results"]
+        .as_array()
+        .map(|results| {
+            results
+                .iter()
+                .take(WEB_SEARCH_RESULT_COUNT)
+                .enumerate()
+                .map(|(i, result)| {
+                    format!(
+                        "{}. {} ({}): {}",
+                        i + 1,
+                        result["title"].as_str().unwrap_or_default(),
+                        result["url"].as_str().unwrap_or_default(),
+                        result["description"].as_str().unwrap_or_default()
+                    )
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if results.is_empty() {
+        Ok("No results found".to_string())
+    } else {
+        Ok(results.join("\n"))
+    }
+}
+
+// ============ App Log ============
+
+/// Maximum number of entries kept in the in-memory app log
+const MAX_APP_EVENTS: usize = 1000;
+
+/// `log` sink that copies every record into the in-memory app log before
+/// handing it to the log plugin, so `info!` / `warn!` / `error!` all reach the viewer
+struct AppLogSink {
+    app: AppHandle,
+    inner: Box<dyn log::Log>,
+}
+
+impl log::Log for AppLogSink {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.inner.enabled(record.metadata()) {
+            if let Some(state) = self.app.try_state::<AppState>() {
+                record_app_event(&state, record);
+            }
         }
+        self.inner.log(record);
     }
 
-    // Save metadata
-    let metadata = json!({
-        "timestamp": timestamp,
-        "prompt": prompt,
-        "created_at": chrono::Utc::now().to_rfc3339()
-    });
-    std::fs::write(version_dir.join("metadata.json"), metadata.to_string())
-        .map_err(|e| format!("Failed to save metadata: {}", e))?;
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
 
-    Ok("Texture generated successfully!".to_string())
+/// Appends a log record to the in-memory app log. A leading `[source]` tag in
+/// the message becomes the entry's source, otherwise the module target is used.
+fn record_app_event(state: &AppState, record: &log::Record) {
+    let message = record.args().to_string();
+    let (source, message) = match message
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once("] "))
+    {
+        Some((source, rest)) => (source.to_string(), rest.to_string()),
+        None => (record.target().to_string(), message),
+    };
+
+    let mut events = state.app_events.lock().unwrap();
+    if events.len() >= MAX_APP_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(AppEvent {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        level: record.level().as_str().to_lowercase(),
+        source,
+        message,
+    });
 }
 
-#[derive(Serialize)]
-pub struct TexturePaths {
-    pub current_textures: Vec<String>,
-    pub original_textures: Vec<String>,
-    pub has_original: bool,
-    pub texture_enabled: bool,
+/// Returns the most recent `limit` app log entries, oldest first
+#[command]
+async fn get_app_log(
+    state: tauri::State<'_, AppState>,
+    limit: u32,
+) -> Result<Vec<AppEvent>, String> {
+    let events = state.app_events.lock().unwrap();
+    let skip = events.len().saturating_sub(limit as usize);
+    Ok(events.iter().skip(skip).cloned().collect())
 }
 
 #[command]
-async fn get_texture_paths() -> Result<TexturePaths, String> {
-    let config = load_model_config()?;
+async fn clear_app_log(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.app_events.lock().unwrap().clear();
+    Ok(())
+}
 
-    // Check if texture editing is enabled for this model
-    let texture_folder = match &config.texture_folder {
-        Some(folder) => folder.clone(),
+/// Writes the app log as plain text, defaulting to the exports folder. Returns the file path.
+#[command]
+async fn export_app_log(
+    state: tauri::State<'_, AppState>,
+    path: Option<String>,
+) -> Result<String, String> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
         None => {
-            return Ok(TexturePaths {
-                current_textures: vec![],
-                original_textures: vec![],
-                has_original: false,
-                texture_enabled: false,
-            });
+            let exports_dir = get_exports_dir()?;
+            std::fs::create_dir_all(&exports_dir)
+                .map_err(|e| format!("Failed to create exports directory: {}", e))?;
+            let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+            exports_dir.join(format!("app_log_{}.txt", timestamp))
         }
     };
 
-    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
-    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+    let content: String = state
+        .app_events
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|e| {
+            format!(
+                "{} [{}] {}: {}\n",
+                e.timestamp,
+                e.level.to_uppercase(),
+                e.source,
+                e.message
+            )
+        })
+        .collect();
+    std::fs::write(&path, content).map_err(|e| format!("Failed to export app log: {}", e))?;
 
-    // Discover current textures
-    let current_textures: Vec<String> = if texture_dir.exists() {
-        std::fs::read_dir(&texture_dir)
-            .map_err(|e| format!("Failed to read texture directory: {}", e))?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().is_some_and(|ext| ext == "png"))
-            .filter(|e| !e.path().is_dir())
-            .map(|e| e.path().to_string_lossy().to_string())
-            .collect()
-    } else {
-        vec![]
-    };
+    Ok(path.to_string_lossy().to_string())
+}
 
-    // Discover original textures
-    let original_textures: Vec<String> = if originals_dir.exists() {
-        std::fs::read_dir(&originals_dir)
-            .map_err(|e| format!("Failed to read originals directory: {}", e))?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().is_some_and(|ext| ext == "png"))
-            .filter(|e| !e.path().is_dir())
-            .map(|e| e.path().to_string_lossy().to_string())
-            .collect()
-    } else {
-        vec![]
+/// Maximum number of errors kept for `get_last_errors`
+const MAX_APP_ERRORS: usize = 100;
+
+/// Records a command failure in the error ring and emits `new-error`
+fn record_app_error(app: &AppHandle, command: &str, message: &str, severity: &str) {
+    let error = AppError {
+        timestamp: chrono::Local::now().to_rfc3339(),
+        command: command.to_string(),
+        message: message.to_string(),
+        severity: severity.to_string(),
     };
+    {
+        let state = app.state::<AppState>();
+        let mut errors = state.error_ring.lock().unwrap();
+        if errors.len() >= MAX_APP_ERRORS {
+            errors.pop_front();
+        }
+        errors.push_back(error.clone());
+    }
+    emit_event(app, "new-error", error);
+}
 
-    Ok(TexturePaths {
-        has_original: !original_textures.is_empty(),
-        current_textures,
-        original_textures,
-        texture_enabled: true,
-    })
+/// `record_error!(app, command, message, severity)`; `message` is anything `Display`
+macro_rules! record_error {
+    ($app:expr, $command:expr, $message:expr, $severity:expr) => {
+        record_app_error(&$app, $command, &$message.to_string(), $severity)
+    };
 }
 
+/// Returns the most recent `limit` recorded errors, oldest first
 #[command]
-async fn reload_character(
-    app: AppHandle,
+async fn get_last_errors(
     state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
-    println!("[Rust] reload_character called");
+    limit: u32,
+) -> Result<Vec<AppError>, String> {
+    let errors = state.error_ring.lock().unwrap();
+    let skip = errors.len().saturating_sub(limit as usize);
+    Ok(errors.iter().skip(skip).cloned().collect())
+}
 
-    // Close existing overlay window if it exists
-    if let Some(overlay) = app.get_webview_window("overlay") {
-        println!("[Rust] Closing existing overlay window");
-        overlay
-            .close()
-            .map_err(|e| format!("Failed to close overlay: {}", e))?;
+#[command]
+async fn clear_error_log(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.error_ring.lock().unwrap().clear();
+    Ok(())
+}
 
-        // Small delay to ensure window is closed
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    }
+// ============ Chat Commands ============
 
-    println!("[Rust] Creating new overlay window");
+/// Default lifetime of cached chat responses
+const DEFAULT_RESPONSE_CACHE_TTL_SECS: u64 = 10 * 60;
 
-    // Load saved scale (default 1.0)
-    let scale = load_overlay_scale();
-    let width = paths::DEFAULT_OVERLAY_WIDTH * scale;
-    let height = paths::DEFAULT_OVERLAY_HEIGHT * scale;
+/// Load saved response cache TTL (returns the default if not saved)
+fn load_response_cache_ttl() -> u64 {
+    paths::get_response_cache_ttl_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RESPONSE_CACHE_TTL_SECS)
+}
 
-    // Recreate the overlay window with fresh state
-    let overlay = tauri::WebviewWindowBuilder::new(
-        &app,
-        "overlay",
-        tauri::WebviewUrl::App("overlay.html".into()),
-    )
-    .title("Overlay")
-    .visible(false)
-    .transparent(true)
-    .decorations(false)
-    .shadow(false)
-    .always_on_top(true)
-    .skip_taskbar(true)
-    .inner_size(width, height)
-    .resizable(true)
-    .build()
-    .map_err(|e| format!("Failed to create overlay window: {}", e))?;
+/// Cache key for a chat request: SHA-256 of the session ID and the full messages array, so
+/// the system prompt, the context window and the new message all have to match
+fn response_cache_key(session_id: &str, messages: &[Value]) -> String {
+    let context = Value::Array(messages.to_vec()).to_string();
+    checksums::sha256_hex(format!("{}\n{}", session_id, context).as_bytes())
+}
 
-    println!("[Rust] New overlay window created, configuring...");
+/// Returns a cached response that is still within the TTL, evicting expired entries
+fn lookup_cached_response(state: &AppState, key: &str) -> Option<String> {
+    let ttl = std::time::Duration::from_secs(state.response_cache_ttl_secs.load(Ordering::Relaxed));
+    let mut cache = state.response_cache.lock().unwrap();
+    cache.retain(|_, (_, cached_at)| cached_at.elapsed() < ttl);
+    cache.get(key).map(|(response, _)| response.clone())
+}
 
-    // Configure the overlay (make it click-through, etc.)
-    configure_overlay(&overlay)?;
+#[command]
+async fn set_cache_ttl_secs(state: tauri::State<'_, AppState>, secs: u64) -> Result<(), String> {
+    let path = paths::get_response_cache_ttl_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, secs.to_string())
+        .map_err(|e| format!("Failed to save cache TTL: {}", e))?;
+
+    state.response_cache_ttl_secs.store(secs, Ordering::Relaxed);
+    Ok(())
+}
+
+#[command]
+async fn clear_response_cache(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.response_cache.lock().unwrap().clear();
+    Ok(())
+}
+
+/// Reports each phase of a chat request as `chat-processing` events
+#[command]
+async fn send_chat_message(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    message: String,
+    include_screenshot: bool,
+    context_level: u8,
+    streaming: Option<bool>,
+    api_backend: Option<ApiBackend>,
+    screenshot_region: Option<ScreenRegion>,
+    attachment_name: Option<String>,
+    include_image_path: Option<String>,
+) -> Result<ChatResponse, String> {
+    emit_event(
+        &app,
+        "chat-processing",
+        json!({
+            "status": "started",
+            "context_level": context_level,
+            "has_screenshot": include_screenshot && context_level == 0
+        }),
+    );
+
+    let client = HttpChatClient {
+        app: app.clone(),
+        client: state.http_client.clone(),
+    };
+    let host = TauriChatHost { app: app.clone() };
+    let mut pipeline = ChatPipeline::new(&state, context_level, client, host);
+    pipeline
+        .with_streaming(streaming.unwrap_or(false))
+        .with_attachment_name(attachment_name)
+        .with_image(include_image_path);
+    if include_screenshot {
+        pipeline.with_screenshot(screenshot_region);
+    }
+    if let Some(backend) = api_backend {
+        pipeline.with_api_backend(backend);
+    }
+    let result = pipeline.execute(message).await;
+
+    match &result {
+        Ok(_) => emit_event(&app, "chat-processing", json!({ "status": "done" })),
+        Err(e) => {
+            record_error!(app, "send_chat_message", e, "error");
+            emit_event(
+                &app,
+                "chat-processing",
+                json!({ "status": "error", "message": e }),
+            );
+        }
+    }
+    result
+}
+
+/// Base system prompt used for a chat context level, before variable substitution
+async fn system_prompt_for_level(context_level: u8) -> Result<String, String> {
+    match context_level {
+        1 => {
+            // Level 1: Use dialogue prompt (respond AS the character in direct conversation)
+            get_dialogue_prompt().await
+        }
+        2 | 3 => {
+            // Level 2: Use deep research prompt (respond as analyst)
+            // Level 3: Same, augmented with web search results below
+            get_deep_research_prompt().await
+        }
+        _ => {
+            // Level 0: Default system prompt
+            get_system_prompt().await
+        }
+    }
+}
+
+/// Number of recent messages sent as chat context
+const DEFAULT_CHAT_HISTORY_LIMIT: u32 = 10;
+
+/// Recent history sent as chat context, led by the latest summary when one exists
+fn context_history(
+    state: &AppState,
+    session_id: &str,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, String> {
+    let mut history = get_chat_history_internal(&state.db_pool, session_id, limit as i64)?;
+
+    // Older context that was summarized away always leads the history
+    if !history.iter().any(|m| m.role == "summary") {
+        if let Some(summary) = get_latest_summary_internal(&state.db_pool, session_id)? {
+            history.insert(0, summary);
+        }
+    }
+    Ok(history)
+}
+
+/// Whether a history message is sent as context at the given level
+fn history_included_at_level(msg: &ChatMessage, context_level: u8) -> bool {
+    // Summaries are included at every level
+    msg.role == "summary"
+        || match context_level {
+            1 => {
+                // Level 1: User + character + assistant (includes AI responses for context)
+                msg.role == "user" || msg.role == "character" || msg.role == "assistant"
+            }
+            2 | 3 => {
+                // Level 2/3: Only user + deep-thought messages
+                msg.role == "user" || msg.role == "deep-thought"
+            }
+            _ => {
+                // Level 0: All except deep-thought and stored search results
+                msg.role != "deep-thought" && msg.role != "search-context"
+            }
+        }
+}
+
+/// Role a chat response is stored under at the given context level
+fn response_role(context_level: u8) -> &'static str {
+    match context_level {
+        1 => "character",
+        2 | 3 => "deep-thought",
+        _ => "assistant",
+    }
+}
+
+/// Builds the messages array for a chat request: system prompt, the history included at
+/// `context_level`, then the user's message with any images
+fn build_context_messages(
+    system_prompt: &str,
+    history: &[ChatMessage],
+    context_level: u8,
+    message: &str,
+    image_urls: Vec<String>,
+) -> Vec<Value> {
+    let mut messages: Vec<Value> = vec![json!({
+        "role": "system",
+        "content": system_prompt
+    })];
+
+    // Add past messages for context, filtered by level
+    for msg in history {
+        if !history_included_at_level(msg, context_level) {
+            continue;
+        }
+
+        // Convert custom roles to "assistant" for API compatibility
+        // Add distinct labels for level 1 context so character knows what's what
+        let (role, content) = if msg.role == "character" {
+            if context_level == 1 {
+                (
+                    "assistant",
+                    format!("[Character's Inner Thoughts]: {}", msg.content),
+                )
+            } else {
+                ("assistant", format!("[Character]: {}", msg.content))
+            }
+        } else if msg.role == "assistant" && context_level == 1 {
+            // For Level 1, format assistant messages distinctly
+            (
+                "assistant",
+                format!("[AI Assistant Response]: {}", msg.content),
+            )
+        } else if msg.role == "deep-thought" {
+            ("assistant", format!("[Analysis]: {}", msg.content))
+        } else if msg.role == "summary" {
+            (
+                "assistant",
+                format!("[Summary of earlier conversation]: {}", msg.content),
+            )
+        } else {
+            (msg.role.as_str(), msg.content.clone())
+        };
+
+        messages.push(json!({
+            "role": role,
+            "content": content
+        }));
+    }
+
+    // Add current message (with or without images)
+    if image_urls.is_empty() {
+        messages.push(json!({
+            "role": "user",
+            "content": message
+        }));
+    } else {
+        let mut content = vec![json!({
+            "type": "text",
+            "text": message
+        })];
+        content.extend(image_urls.into_iter().map(|url| {
+            json!({
+                "type": "image_url",
+                "image_url": { "url": url }
+            })
+        }));
+        messages.push(json!({
+            "role": "user",
+            "content": content
+        }));
+    }
+    messages
+}
+
+/// Reply to a non-streaming chat request
+struct ChatCompletion {
+    text: String,
+    token_count: Option<u32>,
+}
+
+/// Backend calls made by `ChatPipeline`, so its stages can run against a stub in tests
+trait ChatClient {
+    fn complete(
+        &self,
+        backend: &ApiBackend,
+        api_key: &str,
+        messages: &[Value],
+        max_tokens: u32,
+    ) -> impl std::future::Future<Output = Result<ChatCompletion, String>> + Send;
+
+    /// Streams the reply as `chat-stream-chunk` events labelled `role` and returns the full text
+    fn complete_streaming(
+        &self,
+        backend: &ApiBackend,
+        api_key: &str,
+        messages: &[Value],
+        max_tokens: u32,
+        role: &str,
+        cancel: &AtomicBool,
+    ) -> impl std::future::Future<Output = Result<String, String>> + Send;
+}
+
+/// Sends chat requests to the real backends, retrying when rate limited
+struct HttpChatClient {
+    app: AppHandle,
+    client: reqwest::Client,
+}
+
+impl ChatClient for HttpChatClient {
+    async fn complete(
+        &self,
+        backend: &ApiBackend,
+        api_key: &str,
+        messages: &[Value],
+        max_tokens: u32,
+    ) -> Result<ChatCompletion, String> {
+        let request =
+            build_chat_request(&self.client, backend, api_key, messages, max_tokens, false);
+        let response = send_with_backoff(&self.app, request).await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("API error: {}", error_text));
+        }
+
+        let response_json: Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        Ok(ChatCompletion {
+            text: extract_reply_text(backend, &response_json).unwrap_or_default(),
+            token_count: extract_token_count(backend, &response_json),
+        })
+    }
+
+    async fn complete_streaming(
+        &self,
+        backend: &ApiBackend,
+        api_key: &str,
+        messages: &[Value],
+        max_tokens: u32,
+        role: &str,
+        cancel: &AtomicBool,
+    ) -> Result<String, String> {
+        let request =
+            build_chat_request(&self.client, backend, api_key, messages, max_tokens, true);
+        stream_chat_completion(&self.app, request, role, cancel).await
+    }
+}
+
+/// Asks the character to comment on a level 0 answer, returning None if it has nothing to say
+async fn generate_character_comment(
+    client: &impl ChatClient,
+    backend: &ApiBackend,
+    api_key: &str,
+    character_prompt: &str,
+    main_response: &str,
+) -> Option<String> {
+    let messages = vec![
+        json!({
+            "role": "system",
+            "content": character_prompt
+        }),
+        json!({
+            "role": "user",
+            "content": format!("Here is the AI response to comment on:\n\n{}", main_response)
+        }),
+    ];
+
+    let completion = client
+        .complete(backend, api_key, &messages, 500)
+        .await
+        .ok()?;
+    (!completion.text.is_empty()).then_some(completion.text)
+}
+
+/// App side effects of `ChatPipeline`, so `execute` can run without a Tauri app or
+/// database in tests
+trait ChatHost {
+    fn emit(&self, event: &str, payload: Value);
+
+    fn is_online(&self) -> impl std::future::Future<Output = bool> + Send;
+
+    /// Captures `region`, or the full screen without the overlay, returning the file path
+    fn capture_screenshot(
+        &self,
+        region: Option<ScreenRegion>,
+    ) -> impl std::future::Future<Output = Result<String, String>> + Send;
+
+    fn load_history(&self, session_id: &str, limit: u32) -> Result<Vec<ChatMessage>, String>;
+
+    fn store_message(
+        &self,
+        session_id: &str,
+        timestamp: &str,
+        role: &str,
+        content: &str,
+        context_level: u8,
+        token_count: Option<u32>,
+        metadata: Option<&str>,
+    ) -> Result<i64, String>;
+
+    /// Runs once a response has been stored
+    fn response_stored(&self, session_id: &str, backend: &ApiBackend);
+}
+
+/// Runs the pipeline's side effects against the running app
+struct TauriChatHost {
+    app: AppHandle,
+}
+
+impl ChatHost for TauriChatHost {
+    fn emit(&self, event: &str, payload: Value) {
+        emit_event(&self.app, event, payload);
+    }
+
+    async fn is_online(&self) -> bool {
+        probe_network_connectivity(&self.app, &self.app.state::<AppState>()).await
+    }
+
+    async fn capture_screenshot(&self, region: Option<ScreenRegion>) -> Result<String, String> {
+        // Keep the overlay out of full-screen captures so the AI never sees the character
+        match region {
+            Some(region) => take_screenshot(self.app.clone(), Some(region)).await,
+            None => take_screenshot_without_overlay(&self.app).await,
+        }
+    }
+
+    fn load_history(&self, session_id: &str, limit: u32) -> Result<Vec<ChatMessage>, String> {
+        context_history(&self.app.state::<AppState>(), session_id, limit)
+    }
+
+    fn store_message(
+        &self,
+        session_id: &str,
+        timestamp: &str,
+        role: &str,
+        content: &str,
+        context_level: u8,
+        token_count: Option<u32>,
+        metadata: Option<&str>,
+    ) -> Result<i64, String> {
+        store_chat_message(
+            &self.app.state::<AppState>().db_pool,
+            session_id,
+            timestamp,
+            role,
+            content,
+            context_level,
+            token_count,
+            metadata,
+        )
+    }
+
+    fn response_stored(&self, session_id: &str, backend: &ApiBackend) {
+        let state = self.app.state::<AppState>();
+        // Count responses that arrived while the character was hidden
+        if !*state.overlay_visible.lock().unwrap() {
+            let unread = state.unread_count.load(Ordering::SeqCst) + 1;
+            set_unread_count(&self.app, unread);
+        }
+        maybe_auto_summarize(&self.app, session_id, backend);
+    }
+}
+
+/// Builds and runs one chat request: resolves the prompt, history and images, calls the
+/// backend and stores the exchange
+struct ChatPipeline<'a, C: ChatClient, H: ChatHost> {
+    state: &'a AppState,
+    client: C,
+    host: H,
+    context_level: u8,
+    include_screenshot: bool,
+    screenshot_region: Option<ScreenRegion>,
+    api_backend: Option<ApiBackend>,
+    system_prompt: Option<String>,
+    history_limit: u32,
+    streaming: bool,
+    attachment_name: Option<String>,
+    image_path: Option<String>,
+}
+
+impl<'a, C: ChatClient, H: ChatHost> ChatPipeline<'a, C, H> {
+    fn new(state: &'a AppState, context_level: u8, client: C, host: H) -> Self {
+        Self {
+            state,
+            client,
+            host,
+            context_level,
+            include_screenshot: false,
+            screenshot_region: None,
+            api_backend: None,
+            system_prompt: None,
+            history_limit: DEFAULT_CHAT_HISTORY_LIMIT,
+            streaming: false,
+            attachment_name: None,
+            image_path: None,
+        }
+    }
+
+    /// Attaches a screenshot of `region`, or of the full screen; only used at level 0
+    fn with_screenshot(&mut self, region: Option<ScreenRegion>) -> &mut Self {
+        self.include_screenshot = true;
+        self.screenshot_region = region;
+        self
+    }
+
+    /// Uses `backend` instead of the active key profile
+    fn with_api_backend(&mut self, backend: ApiBackend) -> &mut Self {
+        self.api_backend = Some(backend);
+        self
+    }
+
+    /// Uses `prompt` instead of the saved prompt for the context level
+    fn with_system_prompt(&mut self, prompt: String) -> &mut Self {
+        self.system_prompt = Some(prompt);
+        self
+    }
+
+    /// Sends the last `limit` messages as context instead of `DEFAULT_CHAT_HISTORY_LIMIT`
+    fn with_history(&mut self, limit: u32) -> &mut Self {
+        self.history_limit = limit;
+        self
+    }
+
+    fn with_streaming(&mut self, streaming: bool) -> &mut Self {
+        self.streaming = streaming;
+        self
+    }
+
+    /// Name of a file whose contents were appended to the message
+    fn with_attachment_name(&mut self, name: Option<String>) -> &mut Self {
+        self.attachment_name = name;
+        self
+    }
+
+    /// Local image file sent along with the message
+    fn with_image(&mut self, path: Option<String>) -> &mut Self {
+        self.image_path = path;
+        self
+    }
+
+    async fn execute(&self, message: String) -> Result<ChatResponse, String> {
+        use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+        let (host, state, context_level) = (&self.host, self.state, self.context_level);
+
+        // Resolve the backend and its API key
+        let (backend, max_tokens) = resolve_chat_backend(self.api_backend.clone()).await?;
+
+        // Local Ollama servers work offline; hosted APIs would just hang until timeout
+        if !matches!(backend, ApiBackend::Ollama { .. }) && !host.is_online().await {
+            return Err("No network connectivity".to_string());
+        }
+        let api_key = get_backend_api_key(&backend).await?;
+        let session_id = active_session_id(state);
+
+        let system_prompt = match &self.system_prompt {
+            Some(prompt) => prompt.clone(),
+            None => system_prompt_for_level(context_level).await?,
+        };
+        let mut system_prompt = apply_prompt_variables(system_prompt);
+
+        // Level 3: ground the answer in fresh web results
+        let web_results = if context_level == 3 {
+            let results = fetch_web_results(&state.http_client, &message).await?;
+            system_prompt.push_str(&format!("\n\n[Web Results]:\n{}", results));
+            Some(results)
+        } else {
+            None
+        };
+
+        // Take screenshot if enabled (only for level 0)
+        let screenshot_path = if self.include_screenshot && context_level == 0 {
+            Some(host.capture_screenshot(self.screenshot_region).await?)
+        } else {
+            None
+        };
+        let screenshot_base64 = match screenshot_path {
+            Some(ref path) => {
+                let screenshot_bytes =
+                    std::fs::read(path).map_err(|e| format!("Failed to read screenshot: {}", e))?;
+                Some(BASE64.encode(&screenshot_bytes))
+            }
+            None => None,
+        };
+
+        // Image the user attached, e.g. by dropping a file on the chat
+        let (image_data_url, image_name) = match &self.image_path {
+            Some(path) => {
+                let path = Path::new(path);
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.to_string_lossy().to_string());
+                (Some(load_chat_image(path).await?), Some(name))
+            }
+            None => (None, None),
+        };
+
+        // Screenshots make every request unique and web-augmented answers should always
+        // be fresh
+        let cacheable =
+            screenshot_base64.is_none() && image_data_url.is_none() && web_results.is_none();
+
+        // Get recent chat history for context
+        let history = host.load_history(&session_id, self.history_limit)?;
+
+        let image_urls: Vec<String> = screenshot_base64
+            .as_ref()
+            .map(|base64| format!("data:image/png;base64,{}", base64))
+            .into_iter()
+            .chain(image_data_url)
+            .collect();
+        let messages = build_context_messages(
+            &system_prompt,
+            &history,
+            context_level,
+            &message,
+            image_urls,
+        );
+
+        // Reuse a recent answer to the same message in the same conversation context
+        let cache_key = cacheable.then(|| response_cache_key(&session_id, &messages));
+        if let Some(cached) = cache_key
+            .as_deref()
+            .and_then(|key| lookup_cached_response(state, key))
+        {
+            info!("[send_chat_message] Returning cached response");
+            host.emit("cache-hit", json!({ "context_level": context_level }));
+
+            // Keep the conversation history complete even when the API is skipped
+            let timestamp = chrono::Utc::now().to_rfc3339();
+            host.store_message(
+                &session_id,
+                &timestamp,
+                "user",
+                &message,
+                context_level,
+                None,
+                None,
+            )?;
+            host.store_message(
+                &session_id,
+                &timestamp,
+                response_role(context_level),
+                &cached,
+                context_level,
+                None,
+                None,
+            )?;
+
+            let chat_response = ChatResponse {
+                main_response: cached,
+                character_comments: None,
+                follow_up_questions: None,
+            };
+            if self.streaming {
+                host.emit("chat-stream-done", json!(chat_response));
+            }
+            return Ok(chat_response);
+        }
+
+        // Wait for a free request slot so quick successive messages don't trip rate limits.
+        // The slot covers the character comment and follow-up calls too.
+        let permit = acquire_request_slot(state).await?;
+
+        // Call the backend for the main response
+        let client = &self.client;
+        let streaming = self.streaming;
+        let (main_response, main_token_count) = if streaming {
+            state.chat_stream_cancel.store(false, Ordering::SeqCst);
+            let content = client
+                .complete_streaming(
+                    &backend,
+                    &api_key,
+                    &messages,
+                    max_tokens,
+                    response_role(context_level),
+                    &state.chat_stream_cancel,
+                )
+                .await?;
+            // Streamed responses don't report usage
+            (content, None)
+        } else {
+            let completion = client
+                .complete(&backend, &api_key, &messages, max_tokens)
+                .await?;
+            let text = if completion.text.is_empty() {
+                "No response".to_string()
+            } else {
+                completion.text
+            };
+            (text, completion.token_count)
+        };
+
+        if let Some(key) = cache_key {
+            state
+                .response_cache
+                .lock()
+                .unwrap()
+                .insert(key, (main_response.clone(), std::time::Instant::now()));
+        }
+
+        // Store messages and generate character comments based on level
+        let timestamp = chrono::Utc::now().to_rfc3339();
+        let mut user_metadata = serde_json::Map::new();
+        if let Some(path) = &screenshot_path {
+            let model = match &backend {
+                ApiBackend::OpenAI { model }
+                | ApiBackend::Anthropic { model }
+                | ApiBackend::Ollama { model, .. } => model,
+            };
+            user_metadata.insert("screenshot".to_string(), json!(path));
+            user_metadata.insert("model".to_string(), json!(model));
+        }
+        if let Some(name) = &self.attachment_name {
+            user_metadata.insert("attachment".to_string(), json!(name));
+        }
+        if let Some(name) = &image_name {
+            user_metadata.insert("image".to_string(), json!(name));
+        }
+        let user_metadata =
+            (!user_metadata.is_empty()).then(|| Value::Object(user_metadata).to_string());
+        host.store_message(
+            &session_id,
+            &timestamp,
+            "user",
+            &message,
+            context_level,
+            None,
+            user_metadata.as_deref(),
+        )?;
+
+        // Keep the search results the answer was based on for provenance
+        if let Some(ref results) = web_results {
+            host.store_message(
+                &session_id,
+                &timestamp,
+                "search-context",
+                results,
+                context_level,
+                None,
+                None,
+            )?;
+        }
+
+        let (main_message_id, character_comments) = match context_level {
+            1 => {
+                // Level 1: Save response as "character", no separate character comments
+                let id = host.store_message(
+                    &session_id,
+                    &timestamp,
+                    "character",
+                    &main_response,
+                    1,
+                    main_token_count,
+                    None,
+                )?;
+                (id, None)
+            }
+            2 | 3 => {
+                // Level 2/3: Save response as "deep-thought", no character comments
+                let id = host.store_message(
+                    &session_id,
+                    &timestamp,
+                    "deep-thought",
+                    &main_response,
+                    context_level,
+                    main_token_count,
+                    None,
+                )?;
+                (id, None)
+            }
+            _ => {
+                // Level 0: Save as "assistant", then generate character comment
+                let id = host.store_message(
+                    &session_id,
+                    &timestamp,
+                    "assistant",
+                    &main_response,
+                    0,
+                    main_token_count,
+                    None,
+                )?;
+
+                // Generate character commentary for level 0 only
+                host.emit(
+                    "chat-processing",
+                    json!({ "status": "generating_commentary" }),
+                );
+                let char_system_prompt = get_character_prompt().await?;
+                let char_response = generate_character_comment(
+                    client,
+                    &backend,
+                    &api_key,
+                    &char_system_prompt,
+                    &main_response,
+                )
+                .await;
+
+                let comments = match char_response {
+                    Some(char_content) => {
+                        // Store character comment at level 0
+                        host.store_message(
+                            &session_id,
+                            &timestamp,
+                            "character",
+                            &char_content,
+                            0,
+                            None,
+                            None,
+                        )?;
+                        // Return as single comment at end (not randomly inserted)
+                        Some(vec![char_content.trim().to_string()])
+                    }
+                    None => None,
+                };
+                (id, comments)
+            }
+        };
+
+        // Optionally suggest follow-up questions with a cheaper model
+        let chat_settings = load_chat_settings().unwrap_or_default();
+        let follow_up_questions = if chat_settings.generate_followups {
+            // The cheaper follow-up model only applies to OpenAI
+            let followup_backend = match &backend {
+                ApiBackend::OpenAI { .. } => ApiBackend::OpenAI {
+                    model: chat_settings.followup_model.clone(),
+                },
+                other => other.clone(),
+            };
+            let questions = generate_follow_up_questions(
+                client,
+                &followup_backend,
+                &api_key,
+                &message,
+                &main_response,
+            )
+            .await;
+            if let Some(questions) = &questions {
+                state
+                    .followup_cache
+                    .lock()
+                    .unwrap()
+                    .insert(main_message_id, questions.clone());
+            }
+            questions
+        } else {
+            None
+        };
+        drop(permit);
+
+        let chat_response = ChatResponse {
+            main_response,
+            character_comments,
+            follow_up_questions,
+        };
+
+        if streaming {
+            host.emit("chat-stream-done", json!(chat_response));
+        }
+
+        host.response_stored(&session_id, &backend);
+
+        Ok(chat_response)
+    }
+}
+
+/// Longest clipboard excerpt appended to a chat message
+const MAX_CLIPBOARD_CHARS: usize = 4000;
+
+/// Reads the clipboard text, trimmed and capped at `MAX_CLIPBOARD_CHARS`
+fn read_clipboard_text(app: &AppHandle) -> Option<String> {
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
+    let text = app.clipboard().read_text().ok()?;
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= MAX_CLIPBOARD_CHARS {
+        return Some(text.to_string());
+    }
+    let truncated: String = text.chars().take(MAX_CLIPBOARD_CHARS).collect();
+    Some(format!("{}...[truncated]", truncated))
+}
+
+#[command]
+async fn get_clipboard_text(app: AppHandle) -> Result<Option<String>, String> {
+    Ok(read_clipboard_text(&app))
+}
+
+/// Sends a chat message with the current clipboard text appended
+#[command]
+async fn send_chat_message_with_clipboard(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    message: String,
+    include_clipboard: bool,
+    context_level: u8,
+) -> Result<ChatResponse, String> {
+    let message = match include_clipboard
+        .then(|| read_clipboard_text(&app))
+        .flatten()
+    {
+        Some(clipboard_text) => format!("{}\n\n[Clipboard content]:\n{}", message, clipboard_text),
+        None => message,
+    };
+    send_chat_message(
+        app,
+        state,
+        message,
+        false,
+        context_level,
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .await
+}
+
+/// Longest attachment text included in a message, in characters
+const MAX_ATTACHMENT_CHARS: usize = 8000;
+/// File extensions `send_chat_message_with_attachment` can read
+const SUPPORTED_ATTACHMENT_EXTENSIONS: &[&str] = &["txt", "md", "pdf"];
+
+/// Extracts the text of a supported attachment, capped at `MAX_ATTACHMENT_CHARS`
+async fn read_attachment_text(path: &Path) -> Result<String, String> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let text = match extension.as_str() {
+        // Attachments are picked by the user from anywhere, unlike `read_file_as_text` paths
+        "txt" | "md" => tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| format!("Failed to read file {:?}: {}", path, e))?,
+        // Extraction is CPU-bound and panics on some malformed PDFs, so it runs on the
+        // blocking pool where a panic surfaces as a JoinError
+        "pdf" => {
+            let pdf_path = path.to_path_buf();
+            tokio::task::spawn_blocking(move || pdf_extract::extract_text(pdf_path))
+                .await
+                .map_err(|e| format!("Failed to extract text from PDF: {}", e))?
+                .map_err(|e| format!("Failed to extract text from PDF: {}", e))?
+        }
+        _ => {
+            return Err(format!(
+                "Unsupported attachment type .{} (supported: {})",
+                extension,
+                SUPPORTED_ATTACHMENT_EXTENSIONS
+                    .iter()
+                    .map(|ext| format!(".{}", ext))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        }
+    };
+
+    let text = text.trim();
+    if text.chars().count() <= MAX_ATTACHMENT_CHARS {
+        return Ok(text.to_string());
+    }
+    let truncated: String = text.chars().take(MAX_ATTACHMENT_CHARS).collect();
+    Ok(format!("{}...[truncated]", truncated))
+}
+
+/// Largest image file `send_chat_message` accepts via `include_image_path`
+const MAX_CHAT_IMAGE_BYTES: u64 = 20 * 1024 * 1024;
+/// Wider images are downscaled before sending to keep token usage down
+const MAX_CHAT_IMAGE_WIDTH: u32 = 1024;
+
+/// Reads a PNG/JPEG/WEBP image for a chat message, returning it as a base64 data URL
+async fn load_chat_image(path: &Path) -> Result<String, String> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|_| format!("Image not found: {}", path.display()))?;
+    if metadata.len() > MAX_CHAT_IMAGE_BYTES {
+        return Err(format!(
+            "Image is too large ({} bytes, limit is {} bytes)",
+            metadata.len(),
+            MAX_CHAT_IMAGE_BYTES
+        ));
+    }
+
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let format = image::guess_format(&bytes)
+        .ok()
+        .filter(|format| {
+            matches!(
+                format,
+                image::ImageFormat::Png | image::ImageFormat::Jpeg | image::ImageFormat::WebP
+            )
+        })
+        .ok_or_else(|| "Unsupported image format (supported: PNG, JPEG, WEBP)".to_string())?;
+
+    let img = image::load_from_memory_with_format(&bytes, format)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    if img.width() <= MAX_CHAT_IMAGE_WIDTH {
+        return Ok(format!(
+            "data:{};base64,{}",
+            format.to_mime_type(),
+            BASE64.encode(&bytes)
+        ));
+    }
+
+    let resized = img.resize(
+        MAX_CHAT_IMAGE_WIDTH,
+        u32::MAX,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let mut png = std::io::Cursor::new(Vec::new());
+    resized
+        .write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        BASE64.encode(png.into_inner())
+    ))
+}
+
+/// Sends a chat message with the text of a document appended
+#[command]
+async fn send_chat_message_with_attachment(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    message: String,
+    attachment_path: Option<String>,
+    context_level: u8,
+) -> Result<ChatResponse, String> {
+    let Some(attachment_path) = attachment_path else {
+        return send_chat_message(
+            app,
+            state,
+            message,
+            false,
+            context_level,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await;
+    };
+
+    let path = PathBuf::from(&attachment_path);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| attachment_path.clone());
+    let content = read_attachment_text(&path).await?;
+    let message = format!("{}\n\n[Attached file: {}]\n{}", message, file_name, content);
+    send_chat_message(
+        app,
+        state,
+        message,
+        false,
+        context_level,
+        None,
+        None,
+        None,
+        Some(file_name),
+        None,
+    )
+    .await
+}
+
+/// Number of times a rate-limited (429) request is retried
+const MAX_RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Sends a request, retrying with exponential backoff (2^attempt * 500ms) when rate limited
+async fn send_with_backoff(
+    app: &AppHandle,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, String> {
+    let mut attempt = 0;
+    loop {
+        let response = request
+            .try_clone()
+            .ok_or("Failed to clone API request")?
+            .send()
+            .await
+            .map_err(|e| format!("API request failed: {}", e))?;
+
+        if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS
+            || attempt >= MAX_RATE_LIMIT_RETRIES
+        {
+            return Ok(response);
+        }
+
+        let delay_ms = 2u64.pow(attempt) * 500;
+        warn!(
+            "[chat] Rate limited, retrying in {}ms (attempt {}/{})",
+            delay_ms,
+            attempt + 1,
+            MAX_RATE_LIMIT_RETRIES
+        );
+        emit_event(
+            app,
+            "api-rate-limited",
+            json!({ "attempt": attempt + 1, "retry_in_ms": delay_ms }),
+        );
+        tokio::time::sleep(tokio::time::Duration::from_millis(delay_ms)).await;
+        attempt += 1;
+    }
+}
+
+/// Streams a chat completion, emitting a `chat-stream-chunk` event per token.
+/// Returns the full response text once the stream ends.
+async fn stream_chat_completion(
+    app: &AppHandle,
+    request: reqwest::RequestBuilder,
+    role: &str,
+    cancel: &AtomicBool,
+) -> Result<String, String> {
+    use futures_util::StreamExt;
+
+    let response = send_with_backoff(app, request).await?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("API error: {}", error_text));
+    }
+
+    let emit_chunk = |delta: &str, done: bool| {
+        emit_event(
+            app,
+            "chat-stream-chunk",
+            StreamChunk {
+                role: role.to_string(),
+                delta: delta.to_string(),
+                done,
+            },
+        );
+    };
+
+    let mut stream = response.bytes_stream();
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut content = String::new();
+
+    'stream: while let Some(chunk) = stream.next().await {
+        if cancel.load(Ordering::SeqCst) {
+            emit_chunk("", true);
+            return Err("Stream cancelled".to_string());
+        }
+
+        let chunk = chunk.map_err(|e| format!("Stream read failed: {}", e))?;
+        buffer.extend_from_slice(&chunk);
+
+        // Server-sent events are newline-delimited "data: {...}" lines,
+        // Ollama streams bare NDJSON objects instead
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line_bytes: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line_bytes);
+            let line = line.trim();
+            let data = match line.strip_prefix("data:") {
+                Some(data) => data.trim(),
+                None if line.starts_with('{') => line,
+                None => continue,
+            };
+            if data == "[DONE]" {
+                break 'stream;
+            }
+            if let Ok(event) = serde_json::from_str::<Value>(data) {
+                // OpenAI puts tokens in choices[0].delta.content, Anthropic in
+                // delta.text and Ollama in message.content
+                let delta = event["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .or_else(|| event["delta"]["text"].as_str())
+                    .or_else(|| event["message"]["content"].as_str());
+                if let Some(delta) = delta {
+                    content.push_str(delta);
+                    emit_chunk(delta, false);
+                }
+                if event["done"] == true {
+                    break 'stream;
+                }
+            }
+        }
+    }
+
+    emit_chunk("", true);
+
+    if content.is_empty() {
+        Ok("No response".to_string())
+    } else {
+        Ok(content)
+    }
+}
+
+#[command]
+async fn cancel_chat_stream(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.chat_stream_cancel.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Asks a (cheaper) model for follow-up questions to a Q&A pair
+async fn generate_follow_up_questions(
+    client: &impl ChatClient,
+    backend: &ApiBackend,
+    api_key: &str,
+    question: &str,
+    answer: &str,
+) -> Option<Vec<String>> {
+    let messages = vec![
+        json!({
+            "role": "system",
+            "content": "Given this Q&A, suggest 3 concise follow-up questions the user might ask. Return as JSON array."
+        }),
+        json!({
+            "role": "user",
+            "content": format!("Q: {}\n\nA: {}", question, answer)
+        }),
+    ];
+
+    let completion = client
+        .complete(backend, api_key, &messages, 200)
+        .await
+        .ok()?;
+    let questions = parse_json_string_array(&completion.text);
+    if questions.is_empty() {
+        None
+    } else {
+        Some(questions)
+    }
+}
+
+#[command]
+async fn get_follow_up_questions(
+    state: tauri::State<'_, AppState>,
+    message_id: i64,
+) -> Result<Option<Vec<String>>, String> {
+    Ok(state
+        .followup_cache
+        .lock()
+        .unwrap()
+        .get(&message_id)
+        .cloned())
+}
+
+// Database helper functions (store_chat_message, get_chat_history_internal) are in db.rs
+
+#[command]
+async fn get_chat_history(state: tauri::State<'_, AppState>) -> Result<Vec<ChatMessage>, String> {
+    get_chat_history_internal(&state.db_pool, &active_session_id(&state), 100)
+}
+
+#[command]
+async fn get_chat_history_page(
+    state: tauri::State<'_, AppState>,
+    offset: u32,
+    limit: u32,
+    sort_direction: Option<SortDirection>,
+) -> Result<Vec<ChatMessage>, String> {
+    // Fall back to the user's saved preference when no direction is given
+    let direction = match sort_direction {
+        Some(direction) => direction,
+        None => load_chat_settings().unwrap_or_default().history_sort,
+    };
+    get_chat_history_page_internal(
+        &state.db_pool,
+        &active_session_id(&state),
+        offset as i64,
+        limit as i64,
+        direction,
+    )
+}
+
+#[command]
+async fn get_chat_history_range(
+    state: tauri::State<'_, AppState>,
+    from: String,
+    to: String,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, String> {
+    // Stored timestamps are UTC RFC 3339, so normalize the bounds to compare as strings
+    let normalize = |ts: &str| {
+        chrono::DateTime::parse_from_rfc3339(ts)
+            .map(|dt| dt.with_timezone(&chrono::Utc).to_rfc3339())
+            .map_err(|e| format!("Invalid timestamp '{}': {}", ts, e))
+    };
+    get_chat_history_range_internal(
+        &state.db_pool,
+        &active_session_id(&state),
+        &normalize(&from)?,
+        &normalize(&to)?,
+        limit as i64,
+    )
+}
+
+#[command]
+async fn search_chat_history(
+    state: tauri::State<'_, AppState>,
+    query: String,
+    limit: u32,
+) -> Result<Vec<SearchResult>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    search_chat_history_internal(
+        &state.db_pool,
+        &active_session_id(&state),
+        query.trim(),
+        limit as i64,
+    )
+}
+
+#[command]
+async fn get_chat_history_by_role(
+    state: tauri::State<'_, AppState>,
+    role: String,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, String> {
+    get_chat_history_by_role_internal(
+        &state.db_pool,
+        &active_session_id(&state),
+        &role,
+        limit as i64,
+    )
+}
+
+/// Retrieves messages sorted by "timestamp", "role" or "level", "asc" or "desc"
+#[command]
+async fn get_chat_history_sorted(
+    state: tauri::State<'_, AppState>,
+    sort_by: String,
+    direction: String,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, String> {
+    let direction = match direction.as_str() {
+        "asc" => SortDirection::OldestFirst,
+        "desc" => SortDirection::NewestFirst,
+        other => return Err(format!("Invalid sort direction: {}", other)),
+    };
+    get_chat_history_sorted_internal(
+        &state.db_pool,
+        &active_session_id(&state),
+        &sort_by,
+        direction,
+        limit as i64,
+    )
+}
+
+#[command]
+async fn get_chat_history_by_level(
+    state: tauri::State<'_, AppState>,
+    level: u8,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, String> {
+    get_chat_history_by_level_internal(
+        &state.db_pool,
+        &active_session_id(&state),
+        level,
+        limit as i64,
+    )
+}
+
+/// Number of messages in the active session, for pagination
+#[command]
+async fn get_chat_history_count(state: tauri::State<'_, AppState>) -> Result<u32, String> {
+    count_chat_messages_internal(&state.db_pool, &active_session_id(&state))
+}
+
+#[command]
+async fn save_history_sort_preference(direction: String) -> Result<(), String> {
+    let direction = match direction.as_str() {
+        "oldest_first" | "asc" => SortDirection::OldestFirst,
+        "newest_first" | "desc" => SortDirection::NewestFirst,
+        other => return Err(format!("Invalid sort direction: {}", other)),
+    };
+
+    let mut settings = load_chat_settings().unwrap_or_default();
+    settings.history_sort = direction;
+    save_chat_settings(&settings)
+}
+
+/// Turns follow-up question suggestions on or off, optionally changing the model used
+#[command]
+async fn save_followup_settings(
+    generate_followups: bool,
+    followup_model: Option<String>,
+) -> Result<(), String> {
+    let mut settings = load_chat_settings().unwrap_or_default();
+    settings.generate_followups = generate_followups;
+    if let Some(model) = followup_model {
+        let model = model.trim();
+        if model.is_empty() {
+            return Err("Follow-up model cannot be empty".to_string());
+        }
+        settings.followup_model = model.to_string();
+    }
+    save_chat_settings(&settings)
+}
+
+#[command]
+async fn get_chat_settings() -> Result<ChatSettings, String> {
+    load_chat_settings()
+}
+
+#[command]
+async fn clear_chat_history(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    clear_chat_history_internal(&state.db_pool, &active_session_id(&state))
+}
+
+#[command]
+async fn delete_chat_message(state: tauri::State<'_, AppState>, id: i64) -> Result<(), String> {
+    delete_chat_message_internal(&state.db_pool, id)
+}
+
+#[command]
+async fn toggle_bookmark(state: tauri::State<'_, AppState>, id: i64) -> Result<bool, String> {
+    toggle_bookmark_internal(&state.db_pool, id)
+}
+
+/// Attaches JSON metadata to a stored message, replacing any existing metadata
+#[command]
+async fn update_message_metadata(
+    state: tauri::State<'_, AppState>,
+    id: i64,
+    metadata: String,
+) -> Result<(), String> {
+    serde_json::from_str::<Value>(&metadata)
+        .map_err(|e| format!("Metadata must be valid JSON: {}", e))?;
+    update_message_metadata_internal(&state.db_pool, id, &metadata)
+}
+
+#[command]
+async fn get_bookmarked_messages(
+    state: tauri::State<'_, AppState>,
+    limit: u32,
+) -> Result<Vec<ChatMessage>, String> {
+    get_bookmarked_messages_internal(&state.db_pool, &active_session_id(&state), limit as i64)
+}
+
+#[command]
+async fn delete_chat_messages_before(
+    state: tauri::State<'_, AppState>,
+    timestamp: String,
+) -> Result<usize, String> {
+    delete_chat_messages_before_internal(&state.db_pool, &active_session_id(&state), &timestamp)
+}
+
+/// Approximate blended USD price per 1k tokens, matched by model name prefix (most specific first)
+const MODEL_PRICES_PER_1K_TOKENS: &[(&str, f64)] = &[
+    ("gpt-4.1-nano", 0.0002),
+    ("gpt-4.1-mini", 0.0008),
+    ("gpt-4.1", 0.004),
+    ("gpt-4o-mini", 0.0003),
+    ("gpt-4o", 0.005),
+    ("claude", 0.006),
+];
+/// Price used for models not in the table above
+const DEFAULT_PRICE_PER_1K_TOKENS: f64 = 0.004;
+
+fn price_per_1k_tokens(model: &str) -> f64 {
+    MODEL_PRICES_PER_1K_TOKENS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+        .map(|(_, price)| *price)
+        .unwrap_or(DEFAULT_PRICE_PER_1K_TOKENS)
+}
+
+#[command]
+async fn get_token_usage_stats(state: tauri::State<'_, AppState>) -> Result<TokenStats, String> {
+    let daily = get_daily_token_usage_internal(&state.db_pool)?;
+
+    let today = chrono::Utc::now().date_naive();
+    let week_start = today - chrono::Duration::days(6);
+    let (mut today_total, mut week_total, mut total) = (0u64, 0u64, 0u64);
+    for (day, tokens) in &daily {
+        total += tokens;
+        if let Ok(date) = chrono::NaiveDate::parse_from_str(day, "%Y-%m-%d") {
+            if date == today {
+                today_total += tokens;
+            }
+            if date >= week_start {
+                week_total += tokens;
+            }
+        }
+    }
+
+    // Usage isn't stored per model, so price everything at the active profile's model
+    let model = get_active_profile()
+        .await
+        .map(|p| p.model)
+        .unwrap_or_else(|_| default_key_profile().model);
+    let estimated_cost_usd = total as f64 / 1000.0 * price_per_1k_tokens(&model);
+
+    Ok(TokenStats {
+        today: today_total as u32,
+        this_week: week_total as u32,
+        total: total as u32,
+        estimated_cost_usd,
+    })
+}
+
+#[command]
+async fn get_hourly_message_distribution(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<HourlyCount>, String> {
+    get_hourly_message_distribution_internal(&state.db_pool)
+}
+
+/// How long generated topic suggestions stay valid before a new API call is made
+const TOPIC_SUGGESTIONS_TTL_SECS: u64 = 30 * 60;
+
+/// Parses a JSON array of strings from a model reply, tolerating markdown code fences
+fn parse_json_string_array(content: &str) -> Vec<String> {
+    let trimmed = content
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```")
+        .trim();
+
+    serde_json::from_str::<Vec<String>>(trimmed)
+        .map(|items| {
+            items
+                .into_iter()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[command]
+async fn suggest_conversation_topics(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    api_backend: Option<ApiBackend>,
+) -> Result<Vec<String>, String> {
+    let session_id = active_session_id(&state);
+
+    // Serve cached suggestions while they are still fresh for this session
+    if let Some((cached_session, created, topics)) =
+        state.topic_suggestions_cache.lock().unwrap().as_ref()
+    {
+        if *cached_session == session_id && created.elapsed().as_secs() < TOPIC_SUGGESTIONS_TTL_SECS
+        {
+            return Ok(topics.clone());
+        }
+    }
+
+    let (backend, _) = resolve_chat_backend(api_backend).await?;
+    let api_key = get_backend_api_key(&backend).await?;
+
+    let history = get_chat_history_internal(&state.db_pool, &session_id, 20)?;
+    if history.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let context = history
+        .iter()
+        .map(|m| format!("[{}]: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let messages = vec![
+        json!({
+            "role": "system",
+            "content": "Based on these conversation topics, suggest 3 specific follow-up questions or topics the user might want to explore. Return as JSON array."
+        }),
+        json!({ "role": "user", "content": context }),
+    ];
+
+    let client = HttpChatClient {
+        app: app.clone(),
+        client: state.http_client.clone(),
+    };
+    let permit = acquire_request_slot(&state).await?;
+    let reply = client.complete(&backend, &api_key, &messages, 200).await?;
+    drop(permit);
+
+    let topics = parse_json_string_array(&reply.text);
+
+    *state.topic_suggestions_cache.lock().unwrap() =
+        Some((session_id, std::time::Instant::now(), topics.clone()));
+
+    emit_event(&app, "topic-suggestions-ready", json!({ "topics": topics }));
+
+    Ok(topics)
+}
+
+/// Default time between deep research runs (6 hours)
+const DEFAULT_DEEP_RESEARCH_COOLDOWN_SECS: u64 = 6 * 60 * 60;
+/// Shortest cooldown allowed, to keep API costs in check
+const MIN_DEEP_RESEARCH_COOLDOWN_SECS: u64 = 60;
+
+#[command]
+async fn save_deep_research_cooldown_secs(secs: u64) -> Result<(), String> {
+    if secs < MIN_DEEP_RESEARCH_COOLDOWN_SECS {
+        return Err(format!(
+            "Cooldown must be at least {} seconds",
+            MIN_DEEP_RESEARCH_COOLDOWN_SECS
+        ));
+    }
+
+    let config_path = get_deep_research_cooldown_config_path()?;
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&config_path, secs.to_string())
+        .map_err(|e| format!("Failed to save deep research cooldown: {}", e))
+}
+
+#[command]
+async fn get_deep_research_cooldown_secs() -> Result<u64, String> {
+    let config_path = get_deep_research_cooldown_config_path()?;
+    let secs = std::fs::read_to_string(&config_path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u64>().ok())
+        .unwrap_or(DEFAULT_DEEP_RESEARCH_COOLDOWN_SECS);
+    Ok(secs.max(MIN_DEEP_RESEARCH_COOLDOWN_SECS))
+}
+
+#[command]
+async fn save_deep_research_topics(topics: Vec<String>) -> Result<(), String> {
+    let topics: Vec<String> = topics
+        .into_iter()
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    let topics_path = get_deep_research_topics_path()?;
+    if let Some(parent) = topics_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&topics)
+        .map_err(|e| format!("Failed to serialize deep research topics: {}", e))?;
+    std::fs::write(&topics_path, content)
+        .map_err(|e| format!("Failed to save deep research topics: {}", e))
+}
+
+#[command]
+async fn get_deep_research_topics() -> Result<Vec<String>, String> {
+    let topics_path = get_deep_research_topics_path()?;
+    if !topics_path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&topics_path)
+        .map_err(|e| format!("Failed to read deep research topics: {}", e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse deep research topics: {}", e))
+}
+
+fn load_user_prompt_variables() -> HashMap<String, String> {
+    get_prompt_variables_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Variables filled in at send time; these override user-defined ones
+fn builtin_prompt_variables() -> HashMap<String, String> {
+    let now = chrono::Local::now();
+    let timezone = std::env::var("TZ")
+        .ok()
+        .filter(|tz| !tz.is_empty())
+        .unwrap_or_else(|| format!("UTC{}", now.format("%:z")));
+    HashMap::from([
+        ("current_time".to_string(), now.to_rfc2822()),
+        ("day_of_week".to_string(), now.format("%A").to_string()),
+        ("user_timezone".to_string(), timezone),
+    ])
+}
+
+/// Fills `{{variable}}` placeholders in a prompt
+fn apply_prompt_variables(prompt: String) -> String {
+    let mut vars = load_user_prompt_variables();
+    vars.extend(builtin_prompt_variables());
+    substitute_prompt_variables(prompt, vars)
+}
+
+#[command]
+async fn save_user_prompt_variables(vars: HashMap<String, String>) -> Result<(), String> {
+    let vars_path = get_prompt_variables_path()?;
+    if let Some(parent) = vars_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&vars)
+        .map_err(|e| format!("Failed to serialize prompt variables: {}", e))?;
+    std::fs::write(&vars_path, content)
+        .map_err(|e| format!("Failed to save prompt variables: {}", e))
+}
+
+#[command]
+async fn get_user_prompt_variables() -> Result<HashMap<String, String>, String> {
+    Ok(load_user_prompt_variables())
+}
+
+#[command]
+async fn trigger_deep_research(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    topic: Option<String>,
+    api_backend: Option<ApiBackend>,
+) -> Result<DeepResearchResponse, String> {
+    run_deep_research(&app, state, topic, api_backend)
+        .await
+        .inspect_err(|e| record_error!(app, "trigger_deep_research", e, "error"))
+}
+
+async fn run_deep_research(
+    app: &AppHandle,
+    state: tauri::State<'_, AppState>,
+    topic: Option<String>,
+    api_backend: Option<ApiBackend>,
+) -> Result<DeepResearchResponse, String> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let cooldown_path = get_deep_research_cooldown_path()?;
+    let cooldown_secs = get_deep_research_cooldown_secs().await?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+
+    // Check cooldown
+    if cooldown_path.exists() {
+        let last_time_str = std::fs::read_to_string(&cooldown_path).map_err(|e| e.to_string())?;
+        if let Ok(last_time) = last_time_str.parse::<u64>() {
+            if now.saturating_sub(last_time) < cooldown_secs {
+                let remaining = cooldown_secs - now.saturating_sub(last_time);
+                // Return cooldown status - frontend will show timer and existing deep thought
+                return Ok(DeepResearchResponse {
+                    on_cooldown: true,
+                    remaining_seconds: remaining,
+                    cooldown_secs,
+                    main_response: String::new(),
+                });
+            }
+        }
+    }
+
+    // Not on cooldown - run deep research
+    let session_id = active_session_id(&state);
+    let history = get_chat_history_internal(&state.db_pool, &session_id, 50)?;
+    let insights =
+        run_deep_research_impl(app, &session_id, history, topic.as_deref(), api_backend).await?;
+    write_deep_research_cooldown(now)?;
+
+    Ok(DeepResearchResponse {
+        on_cooldown: false,
+        remaining_seconds: 0,
+        cooldown_secs,
+        main_response: insights,
+    })
+}
+
+/// Runs deep research immediately, ignoring the cooldown but still restarting it
+#[command]
+async fn force_deep_research(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    api_backend: Option<ApiBackend>,
+) -> Result<DeepResearchResponse, String> {
+    run_forced_deep_research(&app, state, api_backend)
+        .await
+        .inspect_err(|e| record_error!(app, "force_deep_research", e, "error"))
+}
+
+async fn run_forced_deep_research(
+    app: &AppHandle,
+    state: tauri::State<'_, AppState>,
+    api_backend: Option<ApiBackend>,
+) -> Result<DeepResearchResponse, String> {
+    let session_id = active_session_id(&state);
+    let history = get_chat_history_internal(&state.db_pool, &session_id, 50)?;
+    let insights = run_deep_research_impl(app, &session_id, history, None, api_backend).await?;
+
+    let now = chrono::Utc::now().timestamp().max(0) as u64;
+    write_deep_research_cooldown(now)?;
+
+    Ok(DeepResearchResponse {
+        on_cooldown: false,
+        remaining_seconds: 0,
+        cooldown_secs: get_deep_research_cooldown_secs().await?,
+        main_response: insights,
+    })
+}
+
+/// Clears the cooldown so the next `trigger_deep_research` call runs right away
+#[command]
+async fn reset_deep_research_cooldown(app: AppHandle) -> Result<(), String> {
+    get_api_key().await?.ok_or("API key not configured")?;
+
+    let cooldown_path = get_deep_research_cooldown_path()?;
+    if cooldown_path.exists() {
+        std::fs::remove_file(&cooldown_path)
+            .map_err(|e| format!("Failed to reset deep research cooldown: {}", e))?;
+    }
+
+    emit_event(&app, "cooldown-reset", json!({}));
+    Ok(())
+}
+
+/// Records `now` as the time of the last deep research run
+fn write_deep_research_cooldown(now: u64) -> Result<(), String> {
+    let cooldown_path = get_deep_research_cooldown_path()?;
+    if let Some(parent) = cooldown_path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    std::fs::write(&cooldown_path, now.to_string()).map_err(|e| e.to_string())
+}
+
+/// Sends the conversation to `api_backend`, or the active key profile's model, and stores
+/// the insights
+async fn run_deep_research_impl(
+    app: &AppHandle,
+    session_id: &str,
+    history: Vec<ChatMessage>,
+    topic: Option<&str>,
+    api_backend: Option<ApiBackend>,
+) -> Result<String, String> {
+    let state = app.state::<AppState>();
+    let (backend, max_tokens) = resolve_chat_backend(api_backend).await?;
+    let api_key = get_backend_api_key(&backend).await?;
+    let deep_prompt = get_deep_research_prompt().await?;
+
+    let context = history
+        .iter()
+        .map(|m| format!("[{}]: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let user_content = match topic.map(str::trim).filter(|t| !t.is_empty()) {
+        Some(topic) => format!(
+            "Focus your analysis on the following topic: {}\n\nConversation history:\n\n{}",
+            topic, context
+        ),
+        None => format!("Analyze this conversation history:\n\n{}", context),
+    };
+
+    let messages = vec![
+        json!({ "role": "system", "content": deep_prompt }),
+        json!({ "role": "user", "content": user_content }),
+    ];
+
+    let client = HttpChatClient {
+        app: app.clone(),
+        client: state.http_client.clone(),
+    };
+    let permit = acquire_request_slot(&state).await?;
+    let completion = client
+        .complete(&backend, &api_key, &messages, max_tokens)
+        .await
+        .inspect_err(|e| error!("[DeepResearch] {}", e))?;
+    drop(permit);
+
+    let insights = if completion.text.is_empty() {
+        "No insights generated".to_string()
+    } else {
+        completion.text
+    };
+
+    // Store with deep-thought marker at level 2
+    let timestamp = chrono::Utc::now().to_rfc3339();
+    let token_count = completion.token_count;
+    store_deep_research_result(
+        &state.db_pool,
+        session_id,
+        &timestamp,
+        &deep_prompt,
+        &insights,
+        token_count,
+    )?;
+    // Also kept in the chat history so it is included as context
+    store_chat_message(
+        &state.db_pool,
+        session_id,
+        &timestamp,
+        "deep-thought",
+        &insights,
+        2,
+        token_count,
+        None,
+    )?;
+
+    Ok(insights)
+}
+
+#[command]
+async fn get_deep_research_history(
+    state: tauri::State<'_, AppState>,
+    limit: u32,
+) -> Result<Vec<DeepResearchRecord>, String> {
+    get_deep_research_history_internal(&state.db_pool, limit as i64)
+}
+
+#[command]
+async fn delete_deep_research_record(
+    state: tauri::State<'_, AppState>,
+    id: i64,
+) -> Result<(), String> {
+    delete_deep_research_record_internal(&state.db_pool, id)
+}
+
+/// Adds up the file count and size of a file or directory tree
+fn measure_path(path: &Path) -> (u32, u64) {
+    if path.is_dir() {
+        std::fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| measure_path(&e.path()))
+                    .fold((0, 0), |(files, bytes), (f, b)| (files + f, bytes + b))
+            })
+            .unwrap_or((0, 0))
+    } else {
+        std::fs::metadata(path)
+            .map(|m| (1, m.len()))
+            .unwrap_or((0, 0))
+    }
+}
+
+/// Removes a file or directory, recording the outcome in `result`
+fn remove_data_path(path: &Path, result: &mut ClearResult) {
+    if !path.exists() {
+        return;
+    }
+    let (files, bytes) = measure_path(path);
+    let removed = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    match removed {
+        Ok(()) => {
+            result.deleted_files += files;
+            result.freed_bytes += bytes;
+        }
+        Err(e) => result
+            .errors
+            .push(format!("Failed to remove {:?}: {}", path, e)),
+    }
+}
+
+/// Resolves a path helper, recording the error if it fails
+fn collect_data_path(path: Result<PathBuf, String>, result: &mut ClearResult) -> Option<PathBuf> {
+    path.map_err(|e| result.errors.push(e)).ok()
+}
+
+/// Files that hold API keys and the selected profile
+fn api_key_file_paths() -> Vec<PathBuf> {
+    [
+        get_key_profiles_path(),
+        get_active_profile_path(),
+        get_api_key_path(),
+        get_anthropic_key_path(),
+        get_search_key_path(),
+    ]
+    .into_iter()
+    .filter_map(Result::ok)
+    .collect()
+}
+
+/// Removes every API key, including keys held in the OS keyring
+fn clear_api_key_data(result: &mut ClearResult) {
+    key_store::delete_service(key_store::ServiceKey::Anthropic);
+    key_store::delete_service(key_store::ServiceKey::Search);
+    match load_key_profiles() {
+        Ok(profiles) => {
+            for profile in profiles {
+                key_store::delete(&profile.name);
+            }
+        }
+        Err(e) => result.errors.push(e),
+    }
+    for path in api_key_file_paths() {
+        remove_data_path(&path, result);
+    }
+}
+
+/// Deletes the selected categories of app data, reporting partial failures.
+/// Settings never include API keys; those are only removed when `api_keys` is set.
+#[command]
+async fn clear_data_selective(
+    app: AppHandle,
+    chat: bool,
+    screenshots: bool,
+    textures: bool,
+    settings: bool,
+    models: bool,
+    api_keys: bool,
+) -> Result<ClearResult, String> {
+    let mut result = ClearResult::default();
+
+    if chat {
+        let state = app.state::<AppState>();
+        // Release pooled connections so they don't keep writing to the deleted file
+        db::close_db_pool(&state.db_pool);
+        if let Some(db_path) = collect_data_path(get_db_path(), &mut result) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = db_path.clone().into_os_string();
+                path.push(suffix);
+                remove_data_path(Path::new(&path), &mut result);
+            }
+        }
+        if let Some(dir) = collect_data_path(get_exports_dir(), &mut result) {
+            remove_data_path(&dir, &mut result);
+        }
+    }
+
+    if screenshots {
+        if let Some(dir) = collect_data_path(get_screenshots_dir(), &mut result) {
+            remove_data_path(&dir, &mut result);
+        }
+    }
+
+    if textures {
+        // Generated versions only; originals are kept so textures can still be restored
+        if let Ok(config) = load_model_config() {
+            if let Some(texture_folder) = &config.texture_folder {
+                if let Some(dir) = collect_data_path(
+                    get_versions_dir_for_model(&config.folder, texture_folder),
+                    &mut result,
+                ) {
+                    remove_data_path(&dir, &mut result);
+                }
+            }
+        }
+        if let Some(path) = collect_data_path(get_texture_lock_path(), &mut result) {
+            remove_data_path(&path, &mut result);
+        }
+    }
+
+    if settings {
+        let key_files = api_key_file_paths();
+        if let Some(app_dir) = collect_data_path(get_config_dir(), &mut result) {
+            if let Ok(entries) = std::fs::read_dir(&app_dir) {
+                for entry in entries.filter_map(|e| e.ok()) {
+                    let path = entry.path();
+                    if paths::is_settings_file(&path) && !key_files.contains(&path) {
+                        remove_data_path(&path, &mut result);
+                    }
+                }
+            }
+        }
+    }
+
+    if api_keys {
+        clear_api_key_data(&mut result);
+    }
+
+    if models {
+        if let Some(dir) = collect_data_path(get_models_dir(), &mut result) {
+            remove_data_path(&dir, &mut result);
+        }
+    }
+
+    info!(
+        "[data] Cleared {} files ({} bytes), {} errors",
+        result.deleted_files,
+        result.freed_bytes,
+        result.errors.len()
+    );
+    emit_event(&app, "data-cleared", &result);
+    Ok(result)
+}
+
+/// Deletes the app's data, config and cache directories, including API keys,
+/// downloaded models and original textures
+#[command]
+async fn clear_all_data(app: AppHandle) -> Result<ClearResult, String> {
+    let mut result = ClearResult::default();
+
+    // Keyring entries live outside the app directories
+    clear_api_key_data(&mut result);
+
+    // Release pooled connections so they don't keep writing to the deleted file
+    db::close_db_pool(&app.state::<AppState>().db_pool);
+
+    let mut dirs: Vec<PathBuf> = [get_app_data_dir(), get_config_dir(), get_cache_dir()]
+        .into_iter()
+        .filter_map(|dir| collect_data_path(dir, &mut result))
+        .collect();
+    dirs.dedup();
+    for (files, bytes) in dirs.iter().map(|dir| measure_path(dir)) {
+        result.deleted_files += files;
+        result.freed_bytes += bytes;
+    }
+    clear_app_data()?;
+
+    info!(
+        "[data] Cleared all app data: {} files ({} bytes)",
+        result.deleted_files, result.freed_bytes
+    );
+    emit_event(&app, "data-cleared", &result);
+    Ok(result)
+}
+
+/// Loads the original (pre-edit) version of a texture, backing up the current file first.
+/// Returns None if neither the original nor the current texture exists.
+fn load_original_texture(
+    texture_dir: &Path,
+    originals_dir: &Path,
+    texture_file: &str,
+) -> Result<Option<image::DynamicImage>, String> {
+    let texture_path = texture_dir.join(texture_file);
+    let original_path = originals_dir.join(texture_file);
+
+    // Ensure we have originals backed up first
+    if !original_path.exists() {
+        if texture_path.exists() {
+            std::fs::create_dir_all(originals_dir)
+                .map_err(|e| format!("Failed to create originals dir: {}", e))?;
+            std::fs::copy(&texture_path, &original_path)
+                .map_err(|e| format!("Failed to backup {}: {}", texture_file, e))?;
+        } else {
+            return Ok(None);
+        }
+    }
+
+    image::open(&original_path)
+        .map(Some)
+        .map_err(|e| format!("Failed to load {}: {}", texture_file, e))
+}
+
+/// Sends one texture through the OpenAI image edit API and returns the 1024x1024 result.
+/// `on_step` is called before each stage and can abort the edit by returning an error.
+async fn request_texture_edit<F>(
+    client: &reqwest::Client,
+    api_key: &str,
+    img: &image::DynamicImage,
+    texture_file: &str,
+    prompt: &str,
+    quality: Option<&str>,
+    on_step: F,
+) -> Result<image::DynamicImage, String>
+where
+    F: Fn(&str) -> Result<(), String>,
+{
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    on_step("downscale")?;
+    // Downscale to 1024x1024 for OpenAI
+    info!("[Texture] Downscaling to 1024x1024...");
+    let downscaled = img.resize_exact(1024, 1024, image::imageops::FilterType::Lanczos3);
+
+    // Encode as PNG bytes
+    let mut png_bytes: Vec<u8> = Vec::new();
+    downscaled
+        .write_to(
+            &mut std::io::Cursor::new(&mut png_bytes),
+            image::ImageFormat::Png,
+        )
+        .map_err(|e| format!("Failed to encode image: {}", e))?;
+
+    // Create multipart form for OpenAI API
+    let mut form = reqwest::multipart::Form::new()
+        .text("model", "gpt-image-1.5")
+        .text(
+            "prompt",
+            format!(
+                "This is a texture atlas for a Live2D anime character. {}. \
+            CRITICAL: Keep every element in its EXACT position. \
+            Preserve all black outlines/lineart. \
+            Only modify what the prompt asks for. \
+            Maintain the same art style and quality.",
+                prompt
+            ),
+        )
+        .text("size", "1024x1024")
+        .text("background", "transparent")
+        .text("output_format", "png")
+        .part(
+            "image[]",
+            reqwest::multipart::Part::bytes(png_bytes)
+                .file_name("texture.png")
+                .mime_str("image/png")
+                .map_err(|e| format!("Failed to set mime type: {}", e))?,
+        );
+    if let Some(quality) = quality {
+        form = form.text("quality", quality.to_string());
+    }
+
+    // Call OpenAI API
+    on_step("uploading")?;
+    info!("[Texture] Sending to OpenAI...");
+    let response = client
+        .post("https://api.openai.com/v1/images/edits")
+        .header("Authorization", format!("Bearer {}", api_key))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("OpenAI API failed for {}: {}", texture_file, e))?;
+    on_step("processing")?;
+
+    if !response.status().is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!(
+            "OpenAI API error for {}: {}",
+            texture_file, error_text
+        ));
+    }
+
+    on_step("downloading")?;
+    let response_json: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response for {}: {}", texture_file, e))?;
+
+    info!("[Texture] Response received, extracting image...");
+
+    // Extract base64 image from response
+    let image_data = response_json["data"][0]["b64_json"]
+        .as_str()
+        .ok_or_else(|| format!("No image in response for {}", texture_file))?;
+
+    // Decode the edited image
+    let decoded = BASE64
+        .decode(image_data)
+        .map_err(|e| format!("Failed to decode {}: {}", texture_file, e))?;
+
+    image::load_from_memory(&decoded)
+        .map_err(|e| format!("Failed to load edited {}: {}", texture_file, e))
+}
+
+/// Lists the PNG texture files in a texture directory
+fn list_texture_files(texture_dir: &Path) -> Result<Vec<String>, String> {
+    let mut texture_files: Vec<String> = std::fs::read_dir(texture_dir)
+        .map_err(|e| format!("Failed to read texture directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "png"))
+        .filter(|e| !e.path().is_dir())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    texture_files.sort();
+
+    if texture_files.is_empty() {
+        return Err("No texture files found in model".to_string());
+    }
+    Ok(texture_files)
+}
+
+fn write_texture_generation_lock(lock: &GenerationLock) -> Result<(), String> {
+    let lock_path = get_texture_lock_path()?;
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(lock)
+        .map_err(|e| format!("Failed to serialize generation lock: {}", e))?;
+    std::fs::write(&lock_path, content)
+        .map_err(|e| format!("Failed to write generation lock: {}", e))
+}
+
+fn remove_texture_generation_lock() -> Result<(), String> {
+    let lock_path = get_texture_lock_path()?;
+    if lock_path.exists() {
+        std::fs::remove_file(&lock_path)
+            .map_err(|e| format!("Failed to remove generation lock: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Returns the lock left behind by an interrupted texture generation, if any
+#[command]
+async fn check_texture_generation_lock() -> Result<Option<GenerationLock>, String> {
+    let lock_path = get_texture_lock_path()?;
+    if !lock_path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&lock_path)
+        .map_err(|e| format!("Failed to read generation lock: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse generation lock: {}", e))
+}
+
+/// Undoes an interrupted texture generation by restoring the original textures
+#[command]
+async fn recover_texture_generation() -> Result<String, String> {
+    let lock = check_texture_generation_lock()
+        .await?
+        .ok_or_else(|| "No interrupted texture generation found".to_string())?;
+
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+
+    // Textures are only modified after their original was backed up
+    let mut restored = 0;
+    if originals_dir.exists() {
+        for entry in std::fs::read_dir(&originals_dir)
+            .map_err(|e| format!("Failed to read originals: {}", e))?
+        {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if entry.path().extension().is_some_and(|ext| ext == "png") {
+                let file_name = entry.file_name();
+                std::fs::copy(entry.path(), texture_dir.join(&file_name))
+                    .map_err(|e| format!("Failed to restore {:?}: {}", file_name, e))?;
+                restored += 1;
+            }
+        }
+    }
+
+    let partial_version_dir =
+        get_versions_dir_for_model(&config.folder, &texture_folder)?.join(&lock.started_at);
+    if partial_version_dir.exists() {
+        std::fs::remove_dir_all(&partial_version_dir)
+            .map_err(|e| format!("Failed to remove partial version: {}", e))?;
+    }
+
+    remove_texture_generation_lock()?;
+    info!(
+        "[Texture] Recovered interrupted generation from {} ({} textures restored)",
+        lock.started_at, restored
+    );
+    Ok(format!("Restored {} original textures", restored))
+}
+
+/// Overall progress (0-100) reached at the start of each texture generation step
+fn texture_step_percent(step: &str) -> u32 {
+    match step {
+        "backup" => 0,
+        "downscale" => 10,
+        "uploading" => 20,
+        "processing" => 40,
+        "downloading" => 70,
+        "upscaling" => 85,
+        "saving" => 95,
+        _ => 100,
+    }
+}
+
+/// Stops the running texture generation; a recovery lock is left behind if it was mid-write
+#[command]
+async fn cancel_texture_generation(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let handle = state
+        .texture_task
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| "No texture generation in progress".to_string())?;
+    if let Some((_, token)) = state.texture_cancel_token.lock().unwrap().take() {
+        token.cancel();
+    }
+    handle.abort();
+
+    info!("[Texture] Generation cancelled");
+    emit_event(&app, "texture-generation-cancelled", json!({}));
+    Ok(())
+}
+
+#[command]
+async fn is_texture_generating(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    Ok(state
+        .texture_task
+        .lock()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|handle| !handle.is_finished()))
+}
+
+/// Range of output scale factors accepted by `generate_texture`
+const MIN_TEXTURE_SCALE: f32 = 0.25;
+const MAX_TEXTURE_SCALE: f32 = 2.0;
+
+/// Identifies each `generate_texture` call
+static TEXTURE_GENERATION_ID: AtomicU64 = AtomicU64::new(0);
+
+#[command]
+async fn generate_texture(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    prompt: String,
+    scale_factor: Option<f32>,
+) -> Result<String, String> {
+    let scale_factor = scale_factor
+        .unwrap_or(1.0)
+        .clamp(MIN_TEXTURE_SCALE, MAX_TEXTURE_SCALE);
+    let previous_version = current_texture_version_id().await;
+    let generation_id = TEXTURE_GENERATION_ID.fetch_add(1, Ordering::SeqCst);
+    let token = tokio_util::sync::CancellationToken::new();
+    let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+    {
+        let mut task = state.texture_task.lock().unwrap();
+        if task.as_ref().is_some_and(|handle| !handle.is_finished()) {
+            let message = "Texture generation already in progress".to_string();
+            record_error!(app, "generate_texture", message, "warning");
+            return Err(message);
+        }
+
+        let (task_app, task_token) = (app.clone(), token.clone());
+        *task = Some(tokio::spawn(async move {
+            let result = run_texture_generation(task_app, prompt, scale_factor, task_token).await;
+            let _ = result_tx.send(result.clone());
+            result
+        }));
+        *state.texture_cancel_token.lock().unwrap() = Some((generation_id, token));
+    }
+
+    // The sender is dropped without a value when the task is aborted
+    let result = result_rx
+        .await
+        .unwrap_or_else(|_| Err("Cancelled".to_string()));
+    {
+        // A generation started after this one was cancelled owns the slot now
+        let mut cancel_token = state.texture_cancel_token.lock().unwrap();
+        if cancel_token
+            .as_ref()
+            .is_some_and(|(id, _)| *id == generation_id)
+        {
+            cancel_token.take();
+        }
+    }
+    {
+        let mut task = state.texture_task.lock().unwrap();
+        if task.as_ref().is_some_and(|handle| handle.is_finished()) {
+            task.take();
+        }
+    }
+    match &result {
+        Ok(_) => {
+            if let Some(previous) = previous_version {
+                push_texture_history(&state.texture_undo_stack, previous);
+            }
+            state.texture_redo_stack.lock().unwrap().clear();
+        }
+        // Cancelling is a user action, not a failure
+        Err(e) if e != "Cancelled" => record_error!(app, "generate_texture", e, "error"),
+        _ => {}
+    }
+    result
+}
+
+/// Deletes a version directory left behind by a failed or cancelled generation
+struct PartialVersionDir(Option<PathBuf>);
+
+impl Drop for PartialVersionDir {
+    fn drop(&mut self) {
+        if let Some(dir) = self.0.take() {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+async fn run_texture_generation(
+    app: AppHandle,
+    prompt: String,
+    scale_factor: f32,
+    cancel: tokio_util::sync::CancellationToken,
+) -> Result<String, String> {
+    use image::GenericImageView;
+
+    // Load model config for dynamic paths
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured for this model".to_string())?;
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+
+    // Get OpenAI API key
+    let api_key = get_api_key()
+        .await?
+        .ok_or_else(|| "No API key configured".to_string())?;
+
+    // Discover texture files dynamically
+    let texture_files = list_texture_files(&texture_dir)?;
+
+    // Mark the run as in progress so a crash mid-way can be recovered from
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    write_texture_generation_lock(&GenerationLock {
+        in_progress: true,
+        started_at: timestamp.clone(),
+        prompt: prompt.clone(),
+    })?;
+
+    // Scaled textures only go into the version; the live textures stay at model size
+    let version_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?.join(&timestamp);
+    std::fs::create_dir_all(&version_dir)
+        .map_err(|e| format!("Failed to create version dir: {}", e))?;
+    let mut partial_version = PartialVersionDir(Some(version_dir.clone()));
+
+    let client = reqwest::Client::new();
+    let file_count = texture_files.len() as u32;
+    for (index, texture_file) in texture_files.iter().enumerate() {
+        let texture_path = texture_dir.join(texture_file);
+
+        // Reports the step and stops between steps once cancellation is requested
+        let progress = |step: &str| -> Result<(), String> {
+            if cancel.is_cancelled() {
+                info!(
+                    "[Texture] Generation cancelled before {} of {}",
+                    step, texture_file
+                );
+                return Err("Cancelled".to_string());
+            }
+            let percent = (index as u32 * 100 + texture_step_percent(step)) / file_count;
+            emit_event(
+                &app,
+                "texture-progress",
+                json!({ "file": texture_file, "step": step, "percent": percent as u8 }),
+            );
+            Ok(())
+        };
+
+        // Load the original image (backing it up first if needed)
+        progress("backup")?;
+        let Some(img) = load_original_texture(&texture_dir, &originals_dir, texture_file)? else {
+            continue;
+        };
+
+        let (orig_width, orig_height) = img.dimensions();
+        info!(
+            "[Texture] Processing {} - original dimensions: {}x{}",
+            texture_file, orig_width, orig_height
+        );
+
+        // Abort mid-request rather than waiting out the API round-trip
+        let edited_img = tokio::select! {
+            _ = cancel.cancelled() => return Err("Cancelled".to_string()),
+            edited = request_texture_edit(
+                &client,
+                &api_key,
+                &img,
+                texture_file,
+                &prompt,
+                None,
+                &progress,
+            ) => edited?,
+        };
+
+        // Upscale back to original dimensions (2048x2048)
+        progress("upscaling")?;
+        info!("[Texture] Upscaling to {}x{}...", orig_width, orig_height);
+        let upscaled = edited_img.resize_exact(
+            orig_width,
+            orig_height,
+            image::imageops::FilterType::Lanczos3,
+        );
+
+        // Save the upscaled image
+        progress("saving")?;
+        upscaled
+            .save(&texture_path)
+            .map_err(|e| format!("Failed to save {}: {}", texture_file, e))?;
+
+        // The version keeps a copy at the requested multiple of the original size
+        if scale_factor != 1.0 {
+            let scaled_width = ((orig_width as f32 * scale_factor) as u32).max(1);
+            let scaled_height = ((orig_height as f32 * scale_factor) as u32).max(1);
+            edited_img
+                .resize_exact(
+                    scaled_width,
+                    scaled_height,
+                    image::imageops::FilterType::Lanczos3,
+                )
+                .save(version_dir.join(texture_file))
+                .map_err(|e| format!("Failed to save scaled {}: {}", texture_file, e))?;
+        }
+
+        info!("[Texture] {} completed successfully", texture_file);
+    }
+
+    // Copy the remaining textures to the version folder
+    for texture_file in &texture_files {
+        let src = texture_dir.join(texture_file);
+        let dst = version_dir.join(texture_file);
+        if src.exists() && !dst.exists() {
+            std::fs::copy(&src, &dst)
+                .map_err(|e| format!("Failed to copy {} to version: {}", texture_file, e))?;
+        }
+    }
+
+    // Save metadata
+    let metadata = json!({
+        "timestamp": timestamp,
+        "prompt": prompt,
+        "scale_factor": scale_factor,
+        "created_at": chrono::Utc::now().to_rfc3339()
+    });
+    std::fs::write(version_dir.join("metadata.json"), metadata.to_string())
+        .map_err(|e| format!("Failed to save metadata: {}", e))?;
+    create_texture_thumbnail(&version_dir);
+    partial_version.0 = None;
+
+    remove_texture_generation_lock()?;
+
+    Ok("Texture generated successfully!".to_string())
+}
+
+/// Most variations generated per request
+const MAX_TEXTURE_VARIATIONS: u8 = 4;
+
+/// Generates several edits of the textures from the same prompt, saving each as its own
+/// version without applying it. Returns the new version IDs.
+#[command]
+async fn generate_texture_variations(prompt: String, count: u8) -> Result<Vec<String>, String> {
+    use image::GenericImageView;
+
+    let count = count.clamp(1, MAX_TEXTURE_VARIATIONS);
+
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured for this model".to_string())?;
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
+
+    let api_key = get_api_key()
+        .await?
+        .ok_or_else(|| "No API key configured".to_string())?;
+    let texture_files = list_texture_files(&texture_dir)?;
+
+    let client = reqwest::Client::new();
+    let timestamp = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let mut version_ids = Vec::new();
+
+    for variation in 1..=count {
+        let version_id = format!("{}_v{}", timestamp, variation);
+        let version_dir = versions_dir.join(&version_id);
+        std::fs::create_dir_all(&version_dir)
+            .map_err(|e| format!("Failed to create version dir: {}", e))?;
+
+        for texture_file in &texture_files {
+            let Some(img) = load_original_texture(&texture_dir, &originals_dir, texture_file)?
+            else {
+                continue;
+            };
+            let (orig_width, orig_height) = img.dimensions();
+
+            info!(
+                "[Texture] Variation {}/{}: processing {}",
+                variation, count, texture_file
+            );
+            let edited_img =
+                request_texture_edit(&client, &api_key, &img, texture_file, &prompt, None, |_| {
+                    Ok(())
+                })
+                .await?;
+            edited_img
+                .resize_exact(
+                    orig_width,
+                    orig_height,
+                    image::imageops::FilterType::Lanczos3,
+                )
+                .save(version_dir.join(texture_file))
+                .map_err(|e| format!("Failed to save {}: {}", texture_file, e))?;
+        }
+
+        let metadata = json!({
+            "timestamp": timestamp,
+            "prompt": prompt,
+            "variation": variation,
+            "created_at": chrono::Utc::now().to_rfc3339()
+        });
+        std::fs::write(version_dir.join("metadata.json"), metadata.to_string())
+            .map_err(|e| format!("Failed to save metadata: {}", e))?;
+        create_texture_thumbnail(&version_dir);
+
+        version_ids.push(version_id);
+    }
+
+    Ok(version_ids)
+}
+
+/// Size of the preview image returned by `preview_texture_generation`
+const TEXTURE_PREVIEW_SIZE: u32 = 256;
+
+/// Generates a quick low-quality edit of the main texture and returns it as a base64 PNG
+/// (256x256) without touching disk. gpt-image models have no 256x256 output, so the
+/// result is downscaled.
+#[command]
+async fn preview_texture_generation(prompt: String) -> Result<String, String> {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured for this model".to_string())?;
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+
+    let api_key = get_api_key()
+        .await?
+        .ok_or_else(|| "No API key configured".to_string())?;
+    let texture_files = list_texture_files(&texture_dir)?;
+    let texture_file = &texture_files[0];
+
+    // Read the original without creating a backup, so previews never write to disk
+    let source_path = if originals_dir.join(texture_file).exists() {
+        originals_dir.join(texture_file)
+    } else {
+        texture_dir.join(texture_file)
+    };
+    let img =
+        image::open(&source_path).map_err(|e| format!("Failed to load {}: {}", texture_file, e))?;
+
+    let client = reqwest::Client::new();
+    let edited_img = request_texture_edit(
+        &client,
+        &api_key,
+        &img,
+        texture_file,
+        &prompt,
+        Some("low"),
+        |_| Ok(()),
+    )
+    .await?;
+    let preview = edited_img.resize_exact(
+        TEXTURE_PREVIEW_SIZE,
+        TEXTURE_PREVIEW_SIZE,
+        image::imageops::FilterType::Lanczos3,
+    );
+
+    let mut png_bytes: Vec<u8> = Vec::new();
+    preview
+        .write_to(
+            &mut std::io::Cursor::new(&mut png_bytes),
+            image::ImageFormat::Png,
+        )
+        .map_err(|e| format!("Failed to encode preview: {}", e))?;
+
+    Ok(BASE64.encode(&png_bytes))
+}
+
+#[derive(Serialize)]
+pub struct TexturePaths {
+    pub current_textures: Vec<String>,
+    pub original_textures: Vec<String>,
+    pub has_original: bool,
+    pub texture_enabled: bool,
+}
+
+#[command]
+async fn get_texture_paths() -> Result<TexturePaths, String> {
+    let config = load_model_config()?;
+
+    // Check if texture editing is enabled for this model
+    let texture_folder = match &config.texture_folder {
+        Some(folder) => folder.clone(),
+        None => {
+            return Ok(TexturePaths {
+                current_textures: vec![],
+                original_textures: vec![],
+                has_original: false,
+                texture_enabled: false,
+            });
+        }
+    };
+
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+
+    // Discover current textures
+    let current_textures: Vec<String> = if texture_dir.exists() {
+        std::fs::read_dir(&texture_dir)
+            .map_err(|e| format!("Failed to read texture directory: {}", e))?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "png"))
+            .filter(|e| !e.path().is_dir())
+            .map(|e| e.path().to_string_lossy().to_string())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    // Discover original textures
+    let original_textures: Vec<String> = if originals_dir.exists() {
+        std::fs::read_dir(&originals_dir)
+            .map_err(|e| format!("Failed to read originals directory: {}", e))?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension().is_some_and(|ext| ext == "png"))
+            .filter(|e| !e.path().is_dir())
+            .map(|e| e.path().to_string_lossy().to_string())
+            .collect()
+    } else {
+        vec![]
+    };
+
+    Ok(TexturePaths {
+        has_original: !original_textures.is_empty(),
+        current_textures,
+        original_textures,
+        texture_enabled: true,
+    })
+}
+
+/// How long `reload_character` waits for the overlay to report the model as loaded
+const MODEL_LOAD_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Recreates the overlay; only one reload runs at a time
+#[command]
+async fn reload_character(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let Ok(_reload_guard) = state.character_reload_lock.try_lock() else {
+        return Err("Reload already in progress".to_string());
+    };
+    emit_event(&app, "character-reload-started", json!({}));
+
+    let result = run_character_reload(&app, &state).await;
+    match &result {
+        Ok(_) => emit_event(&app, "character-reload-done", json!({})),
+        Err(e) => emit_event(&app, "character-reload-error", json!({ "error": e })),
+    }
+    result
+}
+
+/// Whether a `reload_character` call is currently running
+#[command]
+async fn is_character_loading(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.character_reload_lock.try_lock().is_err())
+}
+
+async fn run_character_reload(app: &AppHandle, state: &AppState) -> Result<String, String> {
+    info!("[reload_character] reload_character called");
+
+    // Close existing overlay window if it exists
+    if let Some(overlay) = app.get_webview_window("overlay") {
+        info!("[reload_character] Closing existing overlay window");
+        overlay
+            .close()
+            .map_err(|e| format!("Failed to close overlay: {}", e))?;
+
+        // Small delay to ensure window is closed
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    info!("[reload_character] Creating new overlay window");
+
+    // Load saved scale (default 1.0)
+    let scale = load_overlay_scale();
+    let width = paths::DEFAULT_OVERLAY_WIDTH * scale;
+    let height = paths::DEFAULT_OVERLAY_HEIGHT * scale;
+
+    // Register for the frontend's model-loaded notification before the page can load
+    let (loaded_tx, loaded_rx) = tokio::sync::oneshot::channel();
+    *state.model_loaded_tx.lock().unwrap() = Some(loaded_tx);
+
+    // Recreate the overlay window with fresh state
+    let mut builder = tauri::WebviewWindowBuilder::new(
+        app,
+        "overlay",
+        tauri::WebviewUrl::App("overlay.html".into()),
+    )
+    .title("Overlay")
+    .visible(false)
+    .transparent(true)
+    .decorations(false)
+    .shadow(false)
+    .always_on_top(true)
+    .skip_taskbar(true)
+    .inner_size(width, height)
+    .resizable(true);
+
+    // Restore the last saved position and size
+    let saved_geometry = load_overlay_geometry_from_file();
+    if let Some(geometry) = saved_geometry {
+        builder = builder
+            .position(geometry.x as f64, geometry.y as f64)
+            .inner_size(geometry.width as f64, geometry.height as f64);
+    }
+
+    let overlay = builder
+        .build()
+        .map_err(|e| format!("Failed to create overlay window: {}", e))?;
+
+    info!("[reload_character] New overlay window created, configuring...");
+
+    // Configure the overlay (make it click-through, etc.)
+    configure_overlay(&overlay)?;
+    apply_overlay_opacity(&overlay, load_overlay_opacity())?;
+
+    // Default to the bottom right of the screen unless a position was saved
+    if saved_geometry.is_none() {
+        position_overlay(&overlay);
+    }
+
+    // Show the overlay
+    overlay
+        .show()
+        .map_err(|e| format!("Failed to show overlay: {}", e))?;
+
+    // Update state
+    *state.overlay_visible.lock().unwrap() = true;
+
+    // Wait for page to fully load before emitting init-complete
+    info!("[reload_character] Waiting for overlay page to load...");
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // Emit init-complete to trigger model loading
+    info!("[reload_character] Emitting init-complete to load model");
+    let payload = json!({ "active_model": load_active_character_model() });
+    log_overlay_event(app, "init-complete", &payload);
+    overlay
+        .emit("init-complete", payload)
+        .map_err(|e| format!("Failed to emit init-complete: {}", e))?;
+
+    info!("[reload_character] Waiting for overlay to report model-loaded...");
+    let loaded = tokio::time::timeout(MODEL_LOAD_TIMEOUT, loaded_rx).await;
+    if !matches!(loaded, Ok(Ok(()))) {
+        state.model_loaded_tx.lock().unwrap().take();
+        let message = format!("Model load timeout after {}s", MODEL_LOAD_TIMEOUT.as_secs());
+        error!("[reload_character] {}", message);
+        emit_event(app, "character-reload-failed", json!({ "error": message }));
+        return Err(message);
+    }
+
+    info!("[reload_character] Overlay recreated successfully");
+    Ok("Character reloaded!".to_string())
+}
+
+/// Called by the overlay once the Live2D model has finished initializing
+#[command]
+async fn notify_model_loaded(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    if let Some(tx) = state.model_loaded_tx.lock().unwrap().take() {
+        let _ = tx.send(());
+    }
+    Ok(())
+}
+
+// TextureVersion struct is in models.rs
+
+/// Width and height of texture version thumbnails
+const TEXTURE_THUMBNAIL_SIZE: u32 = 128;
+
+/// Writes a thumbnail of the first texture in `version_dir`, returning false if it has none
+fn write_texture_thumbnail(version_dir: &Path) -> Result<bool, String> {
+    let mut textures: Vec<PathBuf> = std::fs::read_dir(version_dir)
+        .map_err(|e| format!("Failed to read version dir: {}", e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+        .collect();
+    textures.sort();
+    let Some(source) = textures.first() else {
+        return Ok(false);
+    };
+
+    let img = image::open(source).map_err(|e| format!("Failed to open texture: {}", e))?;
+    let thumbnail = image::imageops::thumbnail(
+        &img.to_rgb8(),
+        TEXTURE_THUMBNAIL_SIZE,
+        TEXTURE_THUMBNAIL_SIZE,
+    );
+    let thumbnail_path = get_texture_thumbnail_path(version_dir)?;
+    if let Some(parent) = thumbnail_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create thumbnail directory: {}", e))?;
+    }
+    thumbnail
+        .save(&thumbnail_path)
+        .map_err(|e| format!("Failed to save thumbnail: {}", e))?;
+    Ok(true)
+}
+
+/// Thumbnails are saved alongside each version; a failure only costs the preview
+fn create_texture_thumbnail(version_dir: &Path) {
+    if let Err(e) = write_texture_thumbnail(version_dir) {
+        warn!("[Texture] {}", e);
+    }
+}
+
+/// Backfills thumbnails for texture versions created before they were generated
+#[command]
+async fn generate_missing_thumbnails() -> Result<u32, String> {
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
+    if !versions_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut generated = 0;
+    for entry in std::fs::read_dir(&versions_dir).map_err(|e| e.to_string())? {
+        let version_dir = entry.map_err(|e| e.to_string())?.path();
+        if !version_dir.is_dir() || get_texture_thumbnail_path(&version_dir)?.exists() {
+            continue;
+        }
+        match write_texture_thumbnail(&version_dir) {
+            Ok(true) => generated += 1,
+            Ok(false) => {}
+            Err(e) => warn!("[Texture] {}", e),
+        }
+    }
+    Ok(generated)
+}
+
+#[command]
+async fn get_texture_versions() -> Result<Vec<TextureVersion>, String> {
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+
+    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+
+    let mut versions = Vec::new();
+
+    // Add generated versions
+    if versions_dir.exists() {
+        for entry in std::fs::read_dir(&versions_dir).map_err(|e| e.to_string())? {
+            let entry = entry.map_err(|e| e.to_string())?;
+            if entry.path().is_dir() {
+                let id = entry.file_name().to_string_lossy().to_string();
+                let metadata_path = entry.path().join("metadata.json");
+                let (created_at, prompt) = if metadata_path.exists() {
+                    let content = std::fs::read_to_string(&metadata_path).unwrap_or_default();
+                    let json: Value = serde_json::from_str(&content).unwrap_or(json!({}));
+                    (
+                        json["created_at"].as_str().unwrap_or(&id).to_string(),
+                        json["prompt"].as_str().map(|s| s.to_string()),
+                    )
+                } else {
+                    (id.clone(), None)
+                };
+                let thumbnail_path = get_texture_thumbnail_path(&entry.path())?;
+                versions.push(TextureVersion {
+                    id,
+                    created_at,
+                    prompt,
+                    size_bytes: measure_path(&entry.path()).1,
+                    // file:// URLs load directly in the frontend's <img> tags
+                    thumbnail_path: thumbnail_path
+                        .exists()
+                        .then(|| tauri::Url::from_file_path(&thumbnail_path).ok())
+                        .flatten()
+                        .map(|url| url.to_string()),
+                });
+            }
+        }
+    }
+
+    versions.sort_by(|a, b| b.id.cmp(&a.id)); // Newest first
+
+    // Add "original" as the last option if originals exist
+    if originals_dir.exists() {
+        // Check if any png files exist in originals
+        let has_originals = std::fs::read_dir(&originals_dir)
+            .ok()
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .any(|e| e.path().extension().is_some_and(|ext| ext == "png"))
+            })
+            .unwrap_or(false);
+
+        if has_originals {
+            versions.push(TextureVersion {
+                id: "original".to_string(),
+                created_at: "Original".to_string(),
+                prompt: Some("Original textures".to_string()),
+                size_bytes: measure_path(&originals_dir).1,
+                thumbnail_path: None,
+            });
+        }
+    }
+
+    Ok(versions)
+}
+
+/// Total disk space used by all generated texture versions of the current model
+#[command]
+async fn get_total_versions_size() -> Result<u64, String> {
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
+    Ok(measure_path(&versions_dir).1)
+}
+
+/// Directory holding a saved texture version, with "original" mapping to the backup folder
+fn texture_version_dir(
+    model_folder: &str,
+    texture_folder: &str,
+    version_id: &str,
+) -> Result<PathBuf, String> {
+    if version_id == "original" {
+        get_originals_dir_for_model(model_folder, texture_folder)
+    } else {
+        Ok(get_versions_dir_for_model(model_folder, texture_folder)?.join(version_id))
+    }
+}
+
+/// Output scale a version was generated at, from its metadata; 1.0 when unrecorded
+fn texture_version_scale_factor(version_dir: &Path) -> f32 {
+    std::fs::read_to_string(version_dir.join("metadata.json"))
+        .ok()
+        .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+        .and_then(|json| json["scale_factor"].as_f64())
+        .map(|scale| (scale as f32).clamp(MIN_TEXTURE_SCALE, MAX_TEXTURE_SCALE))
+        .unwrap_or(1.0)
+}
+
+/// Size of the first texture listed in the current model's .model3.json
+#[command]
+async fn get_model_expected_texture_size() -> Result<(u32, u32), String> {
+    model_expected_texture_size()
+}
+
+fn model_expected_texture_size() -> Result<(u32, u32), String> {
+    let config = load_model_config()?;
+    let model_dir = get_models_dir()?.join(&config.folder);
+    let content = std::fs::read_to_string(model_dir.join(&config.model_file))
+        .map_err(|e| format!("Failed to read model file: {}", e))?;
+    let model: Value =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse model file: {}", e))?;
+    let texture = model["FileReferences"]["Textures"][0]
+        .as_str()
+        .ok_or_else(|| "Model file lists no textures".to_string())?;
+
+    // Prefer the untouched backup in case the live texture was replaced
+    let texture_path = model_dir.join(texture);
+    let original = texture_path
+        .parent()
+        .zip(texture_path.file_name())
+        .map(|(dir, name)| dir.join("originals").join(name))
+        .filter(|path| path.exists());
+    image::image_dimensions(original.as_ref().unwrap_or(&texture_path))
+        .map_err(|e| format!("Failed to read texture size: {}", e))
+}
+
+#[command]
+async fn apply_texture_version(version_id: String) -> Result<String, String> {
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+    let source_dir = texture_version_dir(&config.folder, &texture_folder, &version_id)?;
+
+    if !source_dir.exists() {
+        return Err("Version not found".to_string());
+    }
+    let scale_factor = texture_version_scale_factor(&source_dir);
+
+    // Discover and copy all texture files from the source
+    for entry in
+        std::fs::read_dir(&source_dir).map_err(|e| format!("Failed to read source: {}", e))?
+    {
+        let entry = entry.map_err(|e| e.to_string())?;
+        if entry.path().extension().is_some_and(|ext| ext == "png") {
+            let file_name = entry.file_name();
+            let dst = texture_dir.join(&file_name);
+            if scale_factor == 1.0 {
+                std::fs::copy(entry.path(), &dst)
+                    .map_err(|e| format!("Failed to apply {:?}: {}", file_name, e))?;
+                continue;
+            }
+
+            // Scaled versions are resized back to the dimensions the model expects: those of
+            // the backed-up original, or of the model's first texture
+            let original = originals_dir.join(&file_name);
+            let (width, height) = if original.exists() {
+                image::image_dimensions(&original)
+                    .map_err(|e| format!("Failed to read size of {:?}: {}", file_name, e))?
+            } else {
+                model_expected_texture_size()?
+            };
+            let img = image::open(entry.path())
+                .map_err(|e| format!("Failed to open {:?}: {}", file_name, e))?;
+            img.resize_exact(width, height, image::imageops::FilterType::Lanczos3)
+                .save(&dst)
+                .map_err(|e| format!("Failed to apply {:?}: {}", file_name, e))?;
+        }
+    }
+
+    Ok(format!(
+        "Applied {}",
+        if version_id == "original" {
+            "original textures"
+        } else {
+            &version_id
+        }
+    ))
+}
+
+#[command]
+async fn compare_texture_versions(
+    version_a: String,
+    version_b: String,
+) -> Result<TextureDiff, String> {
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+
+    let load_version = |version_id: &str| -> Result<image::RgbaImage, String> {
+        let dir = texture_version_dir(&config.folder, &texture_folder, version_id)?;
+        let texture_path = find_texture_file(&dir, "texture_00")
+            .ok_or_else(|| format!("Version {} has no texture_00 file", version_id))?;
+        image::open(&texture_path)
+            .map(|img| img.to_rgba8())
+            .map_err(|e| format!("Failed to load texture for {}: {}", version_id, e))
+    };
+    let img_a = load_version(&version_a)?;
+    let img_b = load_version(&version_b)?;
+
+    if img_a.dimensions() != img_b.dimensions() {
+        return Err(format!(
+            "Texture sizes differ: {:?} vs {:?}",
+            img_a.dimensions(),
+            img_b.dimensions()
+        ));
+    }
+
+    let (width, height) = img_a.dimensions();
+    let mut changed_pixels: u32 = 0;
+    let mut max_delta: u8 = 0;
+    // (min_x, min_y, max_x, max_y) of changed pixels
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+
+    for (x, y, pixel_a) in img_a.enumerate_pixels() {
+        let pixel_b = img_b.get_pixel(x, y);
+        let delta = pixel_a
+            .0
+            .iter()
+            .zip(pixel_b.0.iter())
+            .map(|(a, b)| a.abs_diff(*b))
+            .max()
+            .unwrap_or(0);
+        if delta == 0 {
+            continue;
+        }
+
+        changed_pixels += 1;
+        max_delta = max_delta.max(delta);
+        bounds = Some(match bounds {
+            Some((min_x, min_y, max_x, max_y)) => {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            }
+            None => (x, y, x, y),
+        });
+    }
+
+    let total_pixels = width * height;
+    Ok(TextureDiff {
+        changed_pixels,
+        total_pixels,
+        change_percent: if total_pixels > 0 {
+            changed_pixels as f32 / total_pixels as f32 * 100.0
+        } else {
+            0.0
+        },
+        max_delta,
+        affected_region: bounds.map(|(min_x, min_y, max_x, max_y)| BoundingBox {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+        }),
+    })
+}
+
+/// Returns true if every texture in `source_dir` matches the currently applied texture
+fn textures_match_dir(texture_dir: &Path, source_dir: &Path) -> bool {
+    let Ok(entries) = std::fs::read_dir(source_dir) else {
+        return false;
+    };
+
+    let mut compared = 0;
+    for entry in entries.filter_map(|e| e.ok()) {
+        if !entry.path().extension().is_some_and(|ext| ext == "png") {
+            continue;
+        }
+        let applied = std::fs::read(texture_dir.join(entry.file_name()));
+        let saved = std::fs::read(entry.path());
+        match (applied, saved) {
+            (Ok(a), Ok(b)) if a == b => compared += 1,
+            _ => return false,
+        }
+    }
+
+    compared > 0
+}
+
+/// Detects which texture version is currently applied by comparing file contents
+fn detect_current_texture_version(versions: &[TextureVersion]) -> Result<Option<String>, String> {
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+
+    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
+    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+
+    for version in versions {
+        let source_dir = if version.id == "original" {
+            originals_dir.clone()
+        } else {
+            versions_dir.join(&version.id)
+        };
+        if textures_match_dir(&texture_dir, &source_dir) {
+            return Ok(Some(version.id.clone()));
+        }
+    }
+
+    Ok(None)
+}
+
+#[command]
+async fn rollback_texture(app: AppHandle, steps: Option<usize>) -> Result<String, String> {
+    let steps = steps.unwrap_or(1);
+
+    // Newest first, with "original" as the oldest entry
+    let versions = get_texture_versions().await?;
+    if versions.is_empty() {
+        return Err("No texture versions available".to_string());
+    }
+
+    let current = detect_current_texture_version(&versions)?;
+    let target_index = match &current {
+        Some(id) => {
+            let index = versions.iter().position(|v| &v.id == id).unwrap_or(0);
+            (index + steps).min(versions.len() - 1)
+        }
+        // Unknown state: fall back to the most recent version
+        None => 0,
+    };
+    let target = versions[target_index].id.clone();
+
+    apply_texture_version(target.clone()).await?;
+
+    emit_event(
+        &app,
+        "texture-rolled-back",
+        json!({ "from_version": current, "to_version": target }),
+    );
+
+    Ok(target)
+}
+
+/// Maximum number of texture versions kept for undo/redo
+const MAX_TEXTURE_UNDO: usize = 10;
+
+/// Pushes a version ID onto an undo/redo stack, dropping the oldest entry when full
+fn push_texture_history(stack: &Mutex<VecDeque<String>>, version_id: String) {
+    let mut stack = stack.lock().unwrap();
+    stack.push_back(version_id);
+    while stack.len() > MAX_TEXTURE_UNDO {
+        stack.pop_front();
+    }
+}
+
+/// Returns the ID of the texture version currently applied, if it matches one
+async fn current_texture_version_id() -> Option<String> {
+    let versions = get_texture_versions().await.ok()?;
+    detect_current_texture_version(&versions).ok().flatten()
+}
+
+/// Pops a version from `from`, applies it and records the replaced version on `to`
+async fn step_texture_history(
+    from: &Mutex<VecDeque<String>>,
+    to: &Mutex<VecDeque<String>>,
+    empty_message: &str,
+) -> Result<String, String> {
+    let target = from
+        .lock()
+        .unwrap()
+        .pop_back()
+        .ok_or_else(|| empty_message.to_string())?;
+    let current = current_texture_version_id().await;
+
+    if let Err(e) = apply_texture_version(target.clone()).await {
+        from.lock().unwrap().push_back(target);
+        return Err(e);
+    }
+    if let Some(current) = current {
+        push_texture_history(to, current);
+    }
+    Ok(target)
+}
+
+#[command]
+async fn undo_texture_generation(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    step_texture_history(
+        &state.texture_undo_stack,
+        &state.texture_redo_stack,
+        "Nothing to undo",
+    )
+    .await
+}
+
+#[command]
+async fn redo_texture_generation(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    step_texture_history(
+        &state.texture_redo_stack,
+        &state.texture_undo_stack,
+        "Nothing to redo",
+    )
+    .await
+}
+
+#[command]
+async fn get_texture_undo_stack(state: tauri::State<'_, AppState>) -> Result<Vec<String>, String> {
+    Ok(state
+        .texture_undo_stack
+        .lock()
+        .unwrap()
+        .iter()
+        .cloned()
+        .collect())
+}
+
+#[command]
+async fn delete_texture_version(version_id: String) -> Result<String, String> {
+    // Prevent deleting the original
+    if version_id == "original" {
+        return Err("Cannot delete original textures".to_string());
+    }
+
+    let config = load_model_config()?;
+    let texture_folder = config
+        .texture_folder
+        .ok_or_else(|| "No texture folder configured".to_string())?;
+
+    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
+    let version_path = versions_dir.join(&version_id);
+
+    if !version_path.exists() {
+        return Err("Version not found".to_string());
+    }
+
+    std::fs::remove_dir_all(&version_path).map_err(|e| format!("Failed to delete: {}", e))?;
+
+    Ok("Version deleted".to_string())
+}
+
+// ============ App State ============
+
+pub struct AppState {
+    pub overlay_visible: Mutex<bool>,
+    pub toggle_menu_item: Mutex<Option<MenuItem<tauri::Wry>>>,
+    pub topic_suggestions_cache: Mutex<Option<(String, std::time::Instant, Vec<String>)>>,
+    pub overlay_min_size: Mutex<(u32, u32)>,
+    pub overlay_event_log: AtomicBool,
+    pub tray_menu: Mutex<Option<Menu<tauri::Wry>>>,
+    pub tray_action_items: Mutex<Vec<(String, String, MenuItem<tauri::Wry>)>>,
+    pub action_shortcuts: Mutex<Vec<(Shortcut, String)>>,
+    pub textbox_shortcut: Mutex<Option<Shortcut>>,
+    pub last_snapped_corner: Mutex<Option<String>>,
+    pub overlay_move_generation: AtomicU64,
+    pub auto_hide_enabled: AtomicBool,
+    pub hidden_by_auto_hide: AtomicBool,
+    pub overlay_window_id: Mutex<Option<u64>>,
+    pub http_client: reqwest::Client,
+    pub event_hooks: Mutex<Vec<EventHook>>,
+    pub followup_cache: Mutex<HashMap<i64, Vec<String>>>,
+    pub chat_stream_cancel: AtomicBool,
+    pub request_semaphore: Mutex<Arc<tokio::sync::Semaphore>>,
+    pub response_cache: Mutex<HashMap<String, (String, std::time::Instant)>>,
+    pub response_cache_ttl_secs: AtomicU64,
+    pub active_session_id: Mutex<String>,
+    pub summarizing: AtomicBool,
+    pub unread_count: AtomicU32,
+    pub gaze_calibration_points: Mutex<Vec<(f64, f64, f64, f64)>>,
+    pub gaze_transform: Mutex<Option<GazeTransform>>,
+    pub last_connectivity_check: Mutex<Option<std::time::Instant>>,
+    pub network_online: Mutex<Option<bool>>,
+    pub texture_task: Mutex<Option<tokio::task::JoinHandle<Result<String, String>>>>,
+    /// Cancellation token of the running texture generation, tagged with its generation ID
+    pub texture_cancel_token: Mutex<Option<(u64, tokio_util::sync::CancellationToken)>>,
+    pub always_on_top_refresh_enabled: AtomicBool,
+    pub always_on_top_refresh_ms: AtomicU64,
+    pub app_events: Mutex<VecDeque<AppEvent>>,
+    pub db_pool: db::DbPoolSlot,
+    /// Drag start cursor position and overlay position: (cursor_x, cursor_y, window_x, window_y)
+    pub overlay_drag: Mutex<Option<(f64, f64, i32, i32)>>,
+    pub overlay_scale: Mutex<f64>,
+    /// Progress of each model download, keyed by URL
+    pub download_status: Mutex<HashMap<String, DownloadStatus>>,
+    pub error_ring: Mutex<VecDeque<AppError>>,
+    /// Version IDs to restore on undo/redo, most recent last (in-memory only)
+    pub texture_undo_stack: Mutex<VecDeque<String>>,
+    pub texture_redo_stack: Mutex<VecDeque<String>>,
+    /// Resolved by `notify_model_loaded` while `reload_character` is waiting
+    pub model_loaded_tx: Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
+    /// Duration in milliseconds of each `init_app` phase
+    pub startup_metrics: Mutex<HashMap<String, u64>>,
+    /// Device types ("mouse", "keyboard", "scroll") the listener reports; keyboard only
+    /// gates `typing-activity`
+    pub device_filter: Mutex<HashSet<String>>,
+    /// Monitor names seen on the last poll, primary first
+    pub known_monitors: Mutex<Vec<String>>,
+    pub auto_reposition_enabled: AtomicBool,
+    /// Held for the duration of `reload_character`
+    pub character_reload_lock: tokio::sync::Mutex<()>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            overlay_visible: Mutex::default(),
+            toggle_menu_item: Mutex::default(),
+            topic_suggestions_cache: Mutex::default(),
+            overlay_min_size: Mutex::default(),
+            overlay_event_log: AtomicBool::default(),
+            tray_menu: Mutex::default(),
+            tray_action_items: Mutex::default(),
+            action_shortcuts: Mutex::default(),
+            textbox_shortcut: Mutex::default(),
+            last_snapped_corner: Mutex::default(),
+            overlay_move_generation: AtomicU64::default(),
+            auto_hide_enabled: AtomicBool::default(),
+            hidden_by_auto_hide: AtomicBool::default(),
+            overlay_window_id: Mutex::default(),
+            http_client: reqwest::Client::default(),
+            event_hooks: Mutex::default(),
+            followup_cache: Mutex::default(),
+            chat_stream_cancel: AtomicBool::default(),
+            request_semaphore: Mutex::new(Arc::new(tokio::sync::Semaphore::new(
+                DEFAULT_API_CONCURRENCY,
+            ))),
+            response_cache: Mutex::default(),
+            response_cache_ttl_secs: AtomicU64::new(DEFAULT_RESPONSE_CACHE_TTL_SECS),
+            active_session_id: Mutex::new(DEFAULT_SESSION_ID.to_string()),
+            summarizing: AtomicBool::default(),
+            unread_count: AtomicU32::default(),
+            gaze_calibration_points: Mutex::default(),
+            gaze_transform: Mutex::default(),
+            last_connectivity_check: Mutex::default(),
+            network_online: Mutex::default(),
+            texture_task: Mutex::default(),
+            texture_cancel_token: Mutex::default(),
+            always_on_top_refresh_enabled: AtomicBool::new(true),
+            always_on_top_refresh_ms: AtomicU64::new(DEFAULT_ALWAYS_ON_TOP_REFRESH_MS),
+            app_events: Mutex::default(),
+            db_pool: db::DbPoolSlot::default(),
+            overlay_drag: Mutex::default(),
+            overlay_scale: Mutex::new(load_overlay_scale()),
+            download_status: Mutex::default(),
+            error_ring: Mutex::default(),
+            texture_undo_stack: Mutex::default(),
+            texture_redo_stack: Mutex::default(),
+            model_loaded_tx: Mutex::default(),
+            startup_metrics: Mutex::default(),
+            device_filter: Mutex::new(all_device_types()),
+            known_monitors: Mutex::default(),
+            auto_reposition_enabled: AtomicBool::default(),
+            character_reload_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+}
+
+/// Identifier of the system tray icon
+const TRAY_ID: &str = "main";
+
+// ============ Overlay Window Commands ============
+
+#[cfg(target_os = "macos")]
+fn configure_overlay(window: &tauri::WebviewWindow) -> Result<(), String> {
+    let app = window.app_handle().clone();
+    window
+        .with_webview(move |webview| unsafe {
+            let ns_window_ptr = webview.ns_window();
+            let ns_window: Retained<NSWindow> =
+                Retained::retain(ns_window_ptr as *mut NSWindow).unwrap();
+
+            // The window number doubles as the CGWindowID
+            *app.state::<AppState>().overlay_window_id.lock().unwrap() =
+                Some(ns_window.windowNumber() as u64);
+
+            let behavior = NSWindowCollectionBehavior::CanJoinAllSpaces
+                | NSWindowCollectionBehavior::FullScreenAuxiliary;
+            ns_window.setCollectionBehavior(behavior);
+            ns_window.setLevel(1000);
+        })
+        .map_err(|e| format!("Failed to configure overlay: {}", e))?;
+    label_overlay_for_accessibility(window);
+    Ok(())
+}
+
+/// Applies the saved accessibility label; failures only mean screen readers use a default name
+fn label_overlay_for_accessibility(window: &tauri::WebviewWindow) {
+    if let Err(e) = apply_overlay_accessibility_label(window, &load_overlay_accessibility_label()) {
+        warn!("[overlay] {}", e);
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn configure_overlay(window: &tauri::WebviewWindow) -> Result<(), String> {
+    let hwnd = window
+        .hwnd()
+        .map_err(|e| format!("Failed to get HWND: {}", e))?;
+    *window.state::<AppState>().overlay_window_id.lock().unwrap() = Some(hwnd.0 as u64);
+    label_overlay_for_accessibility(window);
+    ensure_always_on_top(window)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn configure_overlay(window: &tauri::WebviewWindow) -> Result<(), String> {
+    label_overlay_for_accessibility(window);
+    Ok(())
+}
+
+/// Display server backing the current Linux session
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DisplayServer {
+    X11,
+    Wayland,
+    Unknown,
+}
+
+#[cfg(target_os = "linux")]
+fn detect_display_server() -> DisplayServer {
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        DisplayServer::Wayland
+    } else if std::env::var_os("DISPLAY").is_some() {
+        DisplayServer::X11
+    } else {
+        DisplayServer::Unknown
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn detect_wayland() -> bool {
+    detect_display_server() == DisplayServer::Wayland
+}
+
+/// Marks an X11 window as an always-on-top dock that is visible on every desktop
+#[cfg(target_os = "linux")]
+fn set_x11_dock_hints(xid: u32) -> Result<(), String> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, PropMode};
+    use x11rb::wrapper::ConnectionExt as _;
+
+    let (conn, _) = x11rb::connect(None).map_err(|e| format!("Failed to connect to X11: {}", e))?;
+    let intern = |name: &str| -> Result<u32, String> {
+        conn.intern_atom(false, name.as_bytes())
+            .map_err(|e| format!("Failed to intern {}: {}", name, e))?
+            .reply()
+            .map(|reply| reply.atom)
+            .map_err(|e| format!("Failed to intern {}: {}", name, e))
+    };
+
+    let window_type = intern("_NET_WM_WINDOW_TYPE")?;
+    let dock = intern("_NET_WM_WINDOW_TYPE_DOCK")?;
+    let state = intern("_NET_WM_STATE")?;
+    let above = intern("_NET_WM_STATE_ABOVE")?;
+    let sticky = intern("_NET_WM_STATE_STICKY")?;
+    let desktop = intern("_NET_WM_DESKTOP")?;
+
+    // The overlay is configured while hidden, so the window manager picks these up on map
+    conn.change_property32(PropMode::REPLACE, xid, window_type, AtomEnum::ATOM, &[dock])
+        .map_err(|e| format!("Failed to set window type: {}", e))?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        xid,
+        state,
+        AtomEnum::ATOM,
+        &[above, sticky],
+    )
+    .map_err(|e| format!("Failed to set window state: {}", e))?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        xid,
+        desktop,
+        AtomEnum::CARDINAL,
+        &[0xFFFF_FFFF],
+    )
+    .map_err(|e| format!("Failed to set window desktop: {}", e))?;
+    conn.flush()
+        .map_err(|e| format!("Failed to flush X11 connection: {}", e))?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn configure_overlay(window: &tauri::WebviewWindow) -> Result<(), String> {
+    let app = window.app_handle().clone();
+    let display_server = detect_display_server();
+    window
+        .with_webview(move |webview| {
+            use gtk::prelude::*;
+
+            let Some(gtk_window) = webview
+                .inner()
+                .toplevel()
+                .and_then(|w| w.downcast::<gtk::Window>().ok())
+            else {
+                return;
+            };
+
+            // Same as joining all spaces on macOS: stay on top on every workspace
+            gtk_window.set_keep_above(true);
+            gtk_window.stick();
+            gtk_window.set_type_hint(gtk::gdk::WindowTypeHint::Dock);
+
+            if display_server == DisplayServer::X11 {
+                let xid = gtk_window
+                    .window()
+                    .and_then(|w| w.downcast::<gdkx11::X11Window>().ok())
+                    .map(|w| w.xid() as u32);
+                if let Some(xid) = xid {
+                    *app.state::<AppState>().overlay_window_id.lock().unwrap() = Some(xid as u64);
+                    if let Err(e) = set_x11_dock_hints(xid) {
+                        warn!("[overlay] Failed to set X11 dock hints: {}", e);
+                    }
+                }
+            }
+            // On Wayland, layer-shell has to be set up before GTK realizes the window,
+            // which Tauri has already done, so the GDK hints above are the fallback
+        })
+        .map_err(|e| format!("Failed to configure overlay: {}", e))?;
+    label_overlay_for_accessibility(window);
+    Ok(())
+}
+
+#[command]
+async fn show_overlay(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    info!("show_overlay called");
+    if let Some(window) = app.get_webview_window("overlay") {
+        info!("show_overlay: overlay window found");
+        configure_overlay(&window)?;
+
+        position_overlay(&window);
+
+        window.show().map_err(|e| e.to_string())?;
+        info!("show_overlay: window.show() completed");
+        window.set_focus().map_err(|e| e.to_string())?;
+
+        // Update state
+        *state.overlay_visible.lock().unwrap() = true;
+        set_unread_count(&app, 0);
+
+        // Update tray menu text
+        if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
+            let _ = menu_item.set_text("Hide Character");
+        }
+
+        // Emit event
+        emit_event(
+            &app,
+            "overlay-visibility-changed",
+            json!({ "visible": true }),
+        );
+        info!("show_overlay: completed successfully");
+    } else {
+        info!("show_overlay: overlay window NOT found");
+    }
+    Ok(())
+}
+
+#[command]
+async fn hide_overlay(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window("overlay") {
+        window.hide().map_err(|e| e.to_string())?;
+
+        // Update state
+        *state.overlay_visible.lock().unwrap() = false;
+
+        // Update tray menu text
+        if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
+            let _ = menu_item.set_text("Show Character");
+        }
+
+        // Emit event
+        emit_event(
+            &app,
+            "overlay-visibility-changed",
+            json!({ "visible": false }),
+        );
+    }
+    Ok(())
+}
+
+#[command]
+async fn toggle_overlay(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    let is_visible = *state.overlay_visible.lock().unwrap();
+
+    if is_visible {
+        hide_overlay(app, state).await?;
+        Ok(false)
+    } else {
+        show_overlay(app, state).await?;
+        Ok(true)
+    }
+}
+
+// Sync version for use in tray handlers (non-async context)
+fn toggle_overlay_sync(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let is_visible = *state.overlay_visible.lock().unwrap();
+
+    if is_visible {
+        if let Some(window) = app.get_webview_window("overlay") {
+            let _ = window.hide();
+            *state.overlay_visible.lock().unwrap() = false;
+            emit_event(
+                app,
+                "overlay-visibility-changed",
+                json!({ "visible": false }),
+            );
+
+            // Update tray menu text
+            if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
+                let _ = menu_item.set_text("Show Character");
+            }
+        }
+    } else if let Some(window) = app.get_webview_window("overlay") {
+        let _ = configure_overlay(&window);
+
+        position_overlay(&window);
+
+        let _ = window.show();
+        let _ = window.set_focus();
+        *state.overlay_visible.lock().unwrap() = true;
+        emit_event(
+            app,
+            "overlay-visibility-changed",
+            json!({ "visible": true }),
+        );
+        set_unread_count(app, 0);
+
+        // Update tray menu text
+        if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
+            let _ = menu_item.set_text("Hide Character");
+        }
+    }
+}
+
+#[command]
+async fn get_overlay_visible(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    Ok(*state.overlay_visible.lock().unwrap())
+}
+
+// ============ Event Hooks ============
+
+/// Maximum number of registered event hooks
+const MAX_EVENT_HOOKS: usize = 10;
+
+/// Emits an app-wide event and fires any webhooks registered for it
+fn emit_event<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    fire_event_hooks(app, event, &payload);
+    let _ = app.emit(event, payload);
+}
+
+/// Sends the event payload to every enabled webhook registered for `event`
+fn fire_event_hooks<S: Serialize>(app: &AppHandle, event: &str, payload: &S) {
+    let state = app.state::<AppState>();
+    let hooks: Vec<EventHook> = state
+        .event_hooks
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|hook| hook.enabled && hook.event_name == event)
+        .cloned()
+        .collect();
+
+    if hooks.is_empty() {
+        return;
+    }
+
+    let body = json!({
+        "event": event,
+        "payload": serde_json::to_value(payload).unwrap_or(Value::Null),
+    });
+    let client = state.http_client.clone();
+
+    tauri::async_runtime::spawn(async move {
+        for hook in hooks {
+            let request = if hook.method == "GET" {
+                client.get(&hook.webhook_url)
+            } else {
+                client.post(&hook.webhook_url).json(&body)
+            };
+            if let Err(e) = request.send().await {
+                warn!("[hooks] Failed to call hook {}: {}", hook.id, e);
+            }
+        }
+    });
+}
+
+/// Reloads the in-memory hook cache from the database
+fn refresh_event_hooks(state: &AppState) -> Result<(), String> {
+    let hooks = list_event_hooks_internal(&state.db_pool)?;
+    *state.event_hooks.lock().unwrap() = hooks;
+    Ok(())
+}
+
+#[command]
+async fn register_event_hook(
+    state: tauri::State<'_, AppState>,
+    event_name: String,
+    webhook_url: String,
+    method: String,
+) -> Result<String, String> {
+    let method = method.to_uppercase();
+    if method != "GET" && method != "POST" {
+        return Err(format!("Unsupported method: {} (use GET or POST)", method));
+    }
+    if !webhook_url.starts_with("http://") && !webhook_url.starts_with("https://") {
+        return Err("Webhook URL must start with http:// or https://".to_string());
+    }
+    if list_event_hooks_internal(&state.db_pool)?.len() >= MAX_EVENT_HOOKS {
+        return Err(format!("Hook limit reached ({} max)", MAX_EVENT_HOOKS));
+    }
+
+    let hook = EventHook {
+        id: uuid::Uuid::new_v4().to_string(),
+        event_name,
+        webhook_url,
+        method,
+        enabled: true,
+    };
+    insert_event_hook(&state.db_pool, &hook)?;
+    refresh_event_hooks(&state)?;
+
+    Ok(hook.id)
+}
+
+#[command]
+async fn list_event_hooks(state: tauri::State<'_, AppState>) -> Result<Vec<EventHook>, String> {
+    list_event_hooks_internal(&state.db_pool)
+}
+
+#[command]
+async fn delete_event_hook(state: tauri::State<'_, AppState>, id: String) -> Result<(), String> {
+    delete_event_hook_internal(&state.db_pool, &id)?;
+    refresh_event_hooks(&state)
+}
+
+#[command]
+async fn toggle_event_hook(state: tauri::State<'_, AppState>, id: String) -> Result<bool, String> {
+    let enabled = toggle_event_hook_internal(&state.db_pool, &id)?;
+    refresh_event_hooks(&state)?;
+    Ok(enabled)
+}
+
+// ============ Overlay Event Log ============
+
+/// Number of days of overlay event logs to keep
+const OVERLAY_EVENT_LOG_RETENTION_DAYS: i64 = 7;
+
+/// Appends an event sent to the overlay window to today's event log (if enabled)
+fn log_overlay_event<S: Serialize>(app: &AppHandle, event: &str, payload: &S) {
+    if !app
+        .state::<AppState>()
+        .overlay_event_log
+        .load(Ordering::Relaxed)
+    {
+        return;
+    }
+
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let Ok(log_path) = paths::get_overlay_event_log_path(&date) else {
+        return;
+    };
+    if let Some(parent) = log_path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+
+    let line = json!({
+        "ts": chrono::Utc::now().to_rfc3339(),
+        "event": event,
+        "payload": serde_json::to_value(payload).unwrap_or(Value::Null),
+    });
+
+    if let Ok(mut file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+    {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Emits an event to all windows and records it in the overlay event log
+fn emit_overlay_event<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    log_overlay_event(app, event, &payload);
+    emit_event(app, event, payload);
+}
+
+/// Deletes overlay event logs older than the retention window
+fn prune_overlay_event_logs() -> Result<(), String> {
+    let logs_dir = get_logs_dir()?;
+    if !logs_dir.exists() {
+        return Ok(());
+    }
+
+    let cutoff = chrono::Local::now().date_naive()
+        - chrono::Duration::days(OVERLAY_EVENT_LOG_RETENTION_DAYS);
+
+    for entry in std::fs::read_dir(&logs_dir)
+        .map_err(|e| format!("Failed to read logs directory: {}", e))?
+        .filter_map(|e| e.ok())
+    {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let date = name
+            .strip_prefix("overlay_events_")
+            .and_then(|rest| rest.strip_suffix(".jsonl"))
+            .and_then(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+
+        if let Some(date) = date {
+            if date < cutoff {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn set_overlay_event_log(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut settings = load_display_settings().unwrap_or_default();
+    settings.overlay_event_log = enabled;
+    save_display_settings(&settings)?;
+
+    state.overlay_event_log.store(enabled, Ordering::Relaxed);
+    if enabled {
+        prune_overlay_event_logs()?;
+    }
+    Ok(())
+}
+
+#[command]
+async fn get_overlay_event_log_path() -> Result<String, String> {
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let log_path = paths::get_overlay_event_log_path(&date)?;
+    Ok(log_path.to_string_lossy().to_string())
+}
+
+/// Allowed range for the overlay zoom scale
+const MIN_OVERLAY_SCALE: f64 = 0.25;
+const MAX_OVERLAY_SCALE: f64 = 4.0;
+
+/// Load saved overlay scale (returns 1.0 if not saved)
+fn load_overlay_scale() -> f64 {
+    if let Ok(path) = paths::get_overlay_scale_path() {
+        if let Ok(content) = std::fs::read_to_string(&path) {
+            if let Ok(scale) = content.trim().parse::<f64>() {
+                return scale.clamp(MIN_OVERLAY_SCALE, MAX_OVERLAY_SCALE);
+            }
+        }
+    }
+    1.0
+}
+
+/// Save overlay scale to file
+fn save_overlay_scale_to_file(scale: f64) -> Result<(), String> {
+    let path = paths::get_overlay_scale_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, scale.to_string())
+        .map_err(|e| format!("Failed to save overlay scale: {}", e))
+}
+
+/// Updates the in-memory overlay scale and saves it
+fn persist_overlay_scale(state: &AppState, scale: f64) -> Result<(), String> {
+    *state.overlay_scale.lock().unwrap() = scale;
+    save_overlay_scale_to_file(scale)
+}
+
+/// Load saved overlay geometry (returns None if not saved)
+fn load_overlay_geometry_from_file() -> Option<OverlayGeometry> {
+    let path = paths::get_overlay_geometry_path().ok()?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Save overlay geometry to file
+fn save_overlay_geometry_to_file(geometry: &OverlayGeometry) -> Result<(), String> {
+    let path = paths::get_overlay_geometry_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(geometry)
+        .map_err(|e| format!("Failed to serialize overlay geometry: {}", e))?;
+    std::fs::write(&path, content).map_err(|e| format!("Failed to save overlay geometry: {}", e))
+}
+
+/// Moves the overlay to its saved position, or the bottom right of the pinned (else current) monitor
+fn position_overlay(window: &tauri::WebviewWindow) {
+    // A pinned monitor wins unless the saved position is already on it
+    let pinned_monitor = load_pinned_monitor().and_then(|name| find_monitor(window, &name));
+
+    if let Some(geometry) = load_overlay_geometry_from_file() {
+        let on_pinned_monitor = pinned_monitor.as_ref().is_none_or(|monitor| {
+            let position = monitor.position().to_logical::<f64>(monitor.scale_factor());
+            let size = monitor.size().to_logical::<f64>(monitor.scale_factor());
+            (geometry.x as f64) >= position.x
+                && (geometry.x as f64) < position.x + size.width
+                && (geometry.y as f64) >= position.y
+                && (geometry.y as f64) < position.y + size.height
+        });
+        if on_pinned_monitor {
+            let _ = window.set_position(tauri::Position::Logical(tauri::LogicalPosition {
+                x: geometry.x as f64,
+                y: geometry.y as f64,
+            }));
+            return;
+        }
+    }
+
+    let monitor = pinned_monitor.or_else(|| window.current_monitor().ok().flatten());
+    if let Some(monitor) = monitor {
+        let _ = move_overlay_to_monitor(window, &monitor);
+    }
+}
+
+/// Persists the overlay's current position and size after it was moved or resized
+fn persist_overlay_geometry(window: &tauri::Window) {
+    let scale_factor = window.scale_factor().unwrap_or(1.0);
+    let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size()) else {
+        return;
+    };
+    let position: tauri::LogicalPosition<f64> = position.to_logical(scale_factor);
+    let size: tauri::LogicalSize<f64> = size.to_logical(scale_factor);
+
+    let geometry = OverlayGeometry {
+        x: position.x.round() as i32,
+        y: position.y.round() as i32,
+        width: size.width.round() as u32,
+        height: size.height.round() as u32,
+        opacity: load_overlay_opacity(),
+    };
+    if let Err(e) = save_overlay_geometry_to_file(&geometry) {
+        warn!("[overlay] Failed to persist geometry: {}", e);
+    }
+}
+
+#[command]
+async fn save_overlay_geometry(x: i32, y: i32, width: u32, height: u32) -> Result<(), String> {
+    save_overlay_geometry_to_file(&OverlayGeometry {
+        x,
+        y,
+        width,
+        height,
+        opacity: load_overlay_opacity(),
+    })
+}
+
+#[command]
+async fn load_overlay_geometry() -> Result<Option<OverlayGeometry>, String> {
+    Ok(load_overlay_geometry_from_file())
+}
+
+// ============ Overlay Drag ============
+
+/// Begins a drag from the overlay's drag handle, since the click-through window can't be
+/// dragged natively
+#[command]
+async fn start_overlay_drag(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    start_x: f64,
+    start_y: f64,
+) -> Result<(), String> {
+    let overlay = app
+        .get_webview_window("overlay")
+        .ok_or_else(|| "Overlay window not found".to_string())?;
+    let position = overlay
+        .outer_position()
+        .map_err(|e| format!("Failed to get overlay position: {}", e))?;
+    *state.overlay_drag.lock().unwrap() = Some((start_x, start_y, position.x, position.y));
+    Ok(())
+}
+
+/// Moves the overlay by the cursor's offset from where the drag started
+#[command]
+async fn update_overlay_drag(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    current_x: f64,
+    current_y: f64,
+) -> Result<(), String> {
+    let Some((start_x, start_y, saved_x, saved_y)) = *state.overlay_drag.lock().unwrap() else {
+        return Err("No overlay drag in progress".to_string());
+    };
+    let overlay = app
+        .get_webview_window("overlay")
+        .ok_or_else(|| "Overlay window not found".to_string())?;
+
+    // Cursor coordinates come from the webview in logical pixels
+    let scale_factor = overlay.scale_factor().unwrap_or(1.0);
+    let delta_x = ((current_x - start_x) * scale_factor).round() as i32;
+    let delta_y = ((current_y - start_y) * scale_factor).round() as i32;
+    overlay
+        .set_position(tauri::Position::Physical(tauri::PhysicalPosition {
+            x: saved_x + delta_x,
+            y: saved_y + delta_y,
+        }))
+        .map_err(|e| format!("Failed to move overlay: {}", e))
+}
+
+/// Finishes a drag and saves where the overlay ended up
+#[command]
+async fn end_overlay_drag(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
+    if state.overlay_drag.lock().unwrap().take().is_none() {
+        return Ok(());
+    }
+    let overlay = app
+        .get_webview_window("overlay")
+        .ok_or_else(|| "Overlay window not found".to_string())?;
+
+    let scale_factor = overlay.scale_factor().unwrap_or(1.0);
+    let position: tauri::LogicalPosition<f64> = overlay
+        .outer_position()
+        .map_err(|e| format!("Failed to get overlay position: {}", e))?
+        .to_logical(scale_factor);
+    let size: tauri::LogicalSize<f64> = overlay
+        .inner_size()
+        .map_err(|e| format!("Failed to get overlay size: {}", e))?
+        .to_logical(scale_factor);
+
+    save_overlay_geometry(
+        position.x.round() as i32,
+        position.y.round() as i32,
+        size.width.round() as u32,
+        size.height.round() as u32,
+    )
+    .await
+}
+
+// ============ Fullscreen Auto-Hide ============
+
+/// How often the foreground window is checked for fullscreen
+const AUTO_HIDE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// Returns true if the focused window of another application covers the whole screen
+#[cfg(target_os = "windows")]
+fn is_foreground_fullscreen(app: &AppHandle) -> bool {
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetDesktopWindow, GetForegroundWindow, GetShellWindow, GetSystemMetrics, GetWindowRect,
+        SM_CXSCREEN, SM_CYSCREEN,
+    };
+
+    let overlay_id = *app.state::<AppState>().overlay_window_id.lock().unwrap();
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.0.is_null()
+            || hwnd == GetDesktopWindow()
+            || hwnd == GetShellWindow()
+            || Some(hwnd.0 as u64) == overlay_id
+        {
+            return false;
+        }
+
+        let mut rect = RECT::default();
+        if GetWindowRect(hwnd, &mut rect).is_err() {
+            return false;
+        }
+        let screen_width = GetSystemMetrics(SM_CXSCREEN);
+        let screen_height = GetSystemMetrics(SM_CYSCREEN);
+        rect.left <= 0
+            && rect.top <= 0
+            && rect.right >= screen_width
+            && rect.bottom >= screen_height
+    }
+}
+
+/// Returns true if the focused window of another application covers the whole screen
+#[cfg(target_os = "macos")]
+fn is_foreground_fullscreen(app: &AppHandle) -> bool {
+    use objc2::MainThreadMarker;
+    use objc2_app_kit::NSScreen;
+
+    // macOS hides the menu bar and Dock for fullscreen apps, so the main screen's
+    // visible frame grows to its full frame. An auto-hiding menu bar and Dock look the same.
+    let (tx, rx) = std::sync::mpsc::channel();
+    let dispatched = app.run_on_main_thread(move || {
+        let fullscreen = MainThreadMarker::new()
+            .and_then(NSScreen::mainScreen)
+            .map(|screen| screen.frame() == screen.visibleFrame())
+            .unwrap_or(false);
+        let _ = tx.send(fullscreen);
+    });
+    dispatched.is_ok() && rx.recv().unwrap_or(false)
+}
+
+/// Returns true if the focused window of another application covers the whole screen
+#[cfg(target_os = "linux")]
+fn is_foreground_fullscreen(app: &AppHandle) -> bool {
+    let xprop = |args: &[&str]| {
+        std::process::Command::new("xprop")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+    };
+
+    // e.g. "_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3a00007"
+    let Some(window_id) = xprop(&["-root", "_NET_ACTIVE_WINDOW"])
+        .and_then(|out| out.split_whitespace().last().map(str::to_string))
+        .filter(|id| id.starts_with("0x"))
+    else {
+        return false;
+    };
+
+    let overlay_id = *app.state::<AppState>().overlay_window_id.lock().unwrap();
+    if u64::from_str_radix(window_id.trim_start_matches("0x"), 16).ok() == overlay_id {
+        return false;
+    }
+
+    xprop(&["-id", &window_id, "_NET_WM_STATE"])
+        .is_some_and(|out| out.contains("_NET_WM_STATE_FULLSCREEN"))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+fn is_foreground_fullscreen(_app: &AppHandle) -> bool {
+    false
+}
+
+/// Polls for fullscreen windows, hiding the overlay while one is focused
+async fn run_fullscreen_auto_hide(app: AppHandle) {
+    let mut was_fullscreen = false;
+    loop {
+        tokio::time::sleep(AUTO_HIDE_POLL_INTERVAL).await;
+
+        let enabled = app
+            .state::<AppState>()
+            .auto_hide_enabled
+            .load(Ordering::Relaxed);
+        let fullscreen = enabled && {
+            let app = app.clone();
+            tauri::async_runtime::spawn_blocking(move || is_foreground_fullscreen(&app))
+                .await
+                .unwrap_or(false)
+        };
+
+        let state = app.state::<AppState>();
+        let visible = *state.overlay_visible.lock().unwrap();
+        if fullscreen && !was_fullscreen && visible {
+            info!("[auto_hide] Fullscreen window detected, hiding overlay");
+            match hide_overlay(app.clone(), app.state::<AppState>()).await {
+                Ok(()) => state.hidden_by_auto_hide.store(true, Ordering::Relaxed),
+                Err(e) => warn!("[auto_hide] Failed to hide overlay: {}", e),
+            }
+        } else if !fullscreen && state.hidden_by_auto_hide.swap(false, Ordering::Relaxed) {
+            // Only re-show if the user didn't bring the overlay back in the meantime
+            if !visible {
+                info!("[auto_hide] Fullscreen ended, showing overlay");
+                if let Err(e) = show_overlay(app.clone(), app.state::<AppState>()).await {
+                    warn!("[auto_hide] Failed to show overlay: {}", e);
+                }
+            }
+        }
+        was_fullscreen = fullscreen;
+    }
+}
+
+#[command]
+async fn set_auto_hide_on_fullscreen(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut settings = load_display_settings().unwrap_or_default();
+    settings.auto_hide_on_fullscreen = enabled;
+    save_display_settings(&settings)?;
+
+    state.auto_hide_enabled.store(enabled, Ordering::Relaxed);
+    Ok(())
+}
+
+// ============ Monitor Change Detection ============
+
+/// How often the connected monitors are compared against the last known set
+const MONITOR_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Names of the connected monitors, primary first
+fn monitor_names(window: &tauri::WebviewWindow) -> Vec<String> {
+    let primary = window
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .and_then(|m| m.name().cloned());
+    let mut names: Vec<String> = window
+        .available_monitors()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|m| m.name().cloned())
+        .collect();
+    names.sort();
+    if let Some(primary) = primary {
+        names.retain(|name| *name != primary);
+        names.insert(0, primary);
+    }
+    names
+}
+
+/// Watches for monitors being connected, disconnected or a new primary being chosen
+async fn run_monitor_change_watch(app: AppHandle) {
+    loop {
+        tokio::time::sleep(MONITOR_POLL_INTERVAL).await;
+
+        let Some(overlay) = app.get_webview_window("overlay") else {
+            continue;
+        };
+        let monitors = monitor_names(&overlay);
+        let state = app.state::<AppState>();
+        {
+            let mut known = state.known_monitors.lock().unwrap();
+            if *known == monitors {
+                continue;
+            }
+            // The first poll only records the starting layout
+            let first_poll = known.is_empty();
+            *known = monitors.clone();
+            if first_poll {
+                continue;
+            }
+        }
+
+        info!("[monitors] Monitors changed: {:?}", monitors);
+        emit_event(&app, "monitors-changed", json!({ "monitors": monitors }));
+
+        if state.auto_reposition_enabled.load(Ordering::Relaxed) {
+            if let Some(primary) = overlay.primary_monitor().ok().flatten() {
+                // The overlay's Moved handler persists the new position
+                if let Err(e) = move_overlay_to_monitor(&overlay, &primary) {
+                    warn!("[monitors] Failed to reposition overlay: {}", e);
+                }
+            }
+        }
+    }
+}
+
+#[command]
+async fn save_auto_reposition_setting(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+) -> Result<(), String> {
+    let mut settings = load_display_settings().unwrap_or_default();
+    settings.auto_reposition_on_monitor_change = enabled;
+    save_display_settings(&settings)?;
+
+    state
+        .auto_reposition_enabled
+        .store(enabled, Ordering::Relaxed);
+    Ok(())
+}
+
+#[command]
+async fn get_auto_reposition_setting(state: tauri::State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.auto_reposition_enabled.load(Ordering::Relaxed))
+}
+
+// ============ Always On Top Refresh ============
+
+/// Default interval between topmost refreshes on Windows
+const DEFAULT_ALWAYS_ON_TOP_REFRESH_MS: u64 = 2000;
+/// Shortest allowed refresh interval
+const MIN_ALWAYS_ON_TOP_REFRESH_MS: u64 = 250;
+
+/// Re-asserts `HWND_TOPMOST`, which other windows can supersede after focus changes
+#[cfg(target_os = "windows")]
+fn ensure_always_on_top(window: &tauri::WebviewWindow) -> Result<(), String> {
+    let hwnd = window
+        .hwnd()
+        .map_err(|e| format!("Failed to get HWND: {}", e))?;
+    unsafe {
+        SetWindowPos(
+            HWND(hwnd.0),
+            HWND_TOPMOST,
+            0,
+            0,
+            0,
+            0,
+            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+        )
+        .map_err(|e| format!("SetWindowPos failed: {}", e))?;
+    }
+    Ok(())
+}
+
+/// The overlay's window level already keeps it on top on other platforms
+#[cfg(not(target_os = "windows"))]
+fn ensure_always_on_top(_window: &tauri::WebviewWindow) -> Result<(), String> {
+    Ok(())
+}
+
+/// Periodically re-applies the topmost flag while the overlay is visible
+#[cfg(target_os = "windows")]
+async fn run_always_on_top_refresh(app: AppHandle) {
+    loop {
+        let (enabled, interval_ms) = {
+            let state = app.state::<AppState>();
+            (
+                state.always_on_top_refresh_enabled.load(Ordering::Relaxed),
+                state.always_on_top_refresh_ms.load(Ordering::Relaxed),
+            )
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(interval_ms)).await;
+
+        let visible = *app.state::<AppState>().overlay_visible.lock().unwrap();
+        if !enabled || !visible {
+            continue;
+        }
+        if let Some(overlay) = app.get_webview_window("overlay") {
+            if let Err(e) = ensure_always_on_top(&overlay) {
+                warn!("[always_on_top] Failed to refresh topmost state: {}", e);
+            }
+        }
+    }
+}
+
+#[command]
+async fn set_always_on_top_refresh(
+    state: tauri::State<'_, AppState>,
+    enabled: bool,
+    interval_ms: u64,
+) -> Result<(), String> {
+    let interval_ms = interval_ms.max(MIN_ALWAYS_ON_TOP_REFRESH_MS);
+    let mut settings = load_display_settings().unwrap_or_default();
+    settings.always_on_top_refresh = enabled;
+    settings.always_on_top_refresh_ms = interval_ms;
+    save_display_settings(&settings)?;
+
+    state
+        .always_on_top_refresh_enabled
+        .store(enabled, Ordering::Relaxed);
+    state
+        .always_on_top_refresh_ms
+        .store(interval_ms, Ordering::Relaxed);
+    Ok(())
+}
+
+#[command]
+async fn get_always_on_top_refresh() -> Result<Value, String> {
+    let settings = load_display_settings()?;
+    Ok(json!({
+        "enabled": settings.always_on_top_refresh,
+        "interval_ms": settings.always_on_top_refresh_ms
+    }))
+}
+
+// ============ Monitor Pinning ============
+
+/// Name of the monitor the overlay is pinned to, if any
+fn load_pinned_monitor() -> Option<String> {
+    paths::get_pinned_monitor_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Looks up a connected monitor by name
+fn find_monitor(window: &tauri::WebviewWindow, name: &str) -> Option<tauri::Monitor> {
+    window
+        .available_monitors()
+        .ok()?
+        .into_iter()
+        .find(|m| m.name().is_some_and(|n| n == name))
+}
+
+/// Moves the overlay to the bottom-right corner of a monitor
+fn move_overlay_to_monitor(
+    window: &tauri::WebviewWindow,
+    monitor: &tauri::Monitor,
+) -> Result<(), String> {
+    let window_size = window
+        .outer_size()
+        .map_err(|e| format!("Failed to get overlay size: {}", e))?;
+    let screen_size = monitor.size();
+    let screen_pos = monitor.position();
+    let x = screen_pos.x + (screen_size.width as i32) - (window_size.width as i32);
+    let y = screen_pos.y + (screen_size.height as i32) - (window_size.height as i32);
+    window
+        .set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }))
+        .map_err(|e| format!("Failed to move overlay: {}", e))
+}
+
+#[command]
+async fn list_monitors(app: AppHandle) -> Result<Vec<MonitorInfo>, String> {
+    let window = app
+        .get_webview_window("overlay")
+        .ok_or_else(|| "Overlay window not found".to_string())?;
+    let primary_name = window
+        .primary_monitor()
+        .ok()
+        .flatten()
+        .and_then(|m| m.name().cloned());
+
+    let monitors = window
+        .available_monitors()
+        .map_err(|e| format!("Failed to list monitors: {}", e))?;
+    Ok(monitors
+        .iter()
+        .map(|m| {
+            let name = m.name().cloned().unwrap_or_default();
+            MonitorInfo {
+                is_primary: primary_name.as_ref() == Some(&name),
+                name,
+                width: m.size().width,
+                height: m.size().height,
+                x: m.position().x,
+                y: m.position().y,
+            }
+        })
+        .collect())
+}
+
+#[command]
+async fn pin_overlay_to_monitor(app: AppHandle, monitor_name: String) -> Result<(), String> {
+    let window = app
+        .get_webview_window("overlay")
+        .ok_or_else(|| "Overlay window not found".to_string())?;
+    let monitor = find_monitor(&window, &monitor_name)
+        .ok_or_else(|| format!("Monitor '{}' not found", monitor_name))?;
+
+    let path = paths::get_pinned_monitor_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, &monitor_name)
+        .map_err(|e| format!("Failed to save pinned monitor: {}", e))?;
+
+    move_overlay_to_monitor(&window, &monitor)
+}
+
+#[command]
+async fn unpin_overlay_monitor() -> Result<(), String> {
+    let path = paths::get_pinned_monitor_path()?;
+    if path.exists() {
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to clear pinned monitor: {}", e))?;
+    }
+    Ok(())
+}
+
+#[command]
+async fn get_pinned_monitor() -> Result<Option<String>, String> {
+    Ok(load_pinned_monitor())
+}
+
+// ============ Unread Badge ============
+
+/// Shows the unread count on the tray icon. macOS status items have no badge API, so the
+/// count is shown as the item's title; elsewhere a dot is drawn onto the icon.
+fn update_tray_badge(app: &AppHandle, count: u32) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+
+    #[cfg(target_os = "macos")]
+    {
+        let title = (count > 0).then(|| count.to_string());
+        let _ = tray.set_title(title.as_deref());
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(icon) = app.default_window_icon() else {
+            return;
+        };
+        let (width, height) = (icon.width(), icon.height());
+        let mut rgba = icon.rgba().to_vec();
+
+        if count > 0 {
+            // Red dot in the top-right quarter of the icon
+            let radius = (width.min(height) / 4) as i64;
+            let (cx, cy) = (width as i64 - radius, radius);
+            for y in 0..height as i64 {
+                for x in 0..width as i64 {
+                    if (x - cx).pow(2) + (y - cy).pow(2) <= radius.pow(2) {
+                        let offset = ((y * width as i64 + x) * 4) as usize;
+                        rgba[offset..offset + 4].copy_from_slice(&[220, 38, 38, 255]);
+                    }
+                }
+            }
+        }
+
+        let _ = tray.set_icon(Some(tauri::image::Image::new_owned(rgba, width, height)));
+    }
+}
+
+fn set_unread_count(app: &AppHandle, count: u32) {
+    let previous = app
+        .state::<AppState>()
+        .unread_count
+        .swap(count, Ordering::SeqCst);
+    if previous == count {
+        return;
+    }
+    update_tray_badge(app, count);
+    emit_event(app, "unread-count-changed", json!({ "count": count }));
+}
+
+#[command]
+async fn get_unread_count(state: tauri::State<'_, AppState>) -> Result<u32, String> {
+    Ok(state.unread_count.load(Ordering::SeqCst))
+}
+
+#[command]
+async fn reset_unread_count(app: AppHandle) -> Result<(), String> {
+    set_unread_count(&app, 0);
+    Ok(())
+}
+
+// ============ Overlay Corner Snapping ============
+
+/// How long the overlay must stay still after a move before it counts as released
+const SNAP_RELEASE_DELAY: std::time::Duration = std::time::Duration::from_millis(300);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SnapSettings {
+    pub enabled: bool,
+    /// Distance from a corner (logical pixels) within which the overlay snaps
+    pub margin: u32,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            margin: 32,
+        }
+    }
+}
+
+fn load_snap_settings() -> Result<SnapSettings, String> {
+    let settings_path = paths::get_snap_settings_path()?;
+    if settings_path.exists() {
+        let content = std::fs::read_to_string(&settings_path)
+            .map_err(|e| format!("Failed to read snap settings: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse snap settings: {}", e))
+    } else {
+        Ok(SnapSettings::default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverlayCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl OverlayCorner {
+    const ALL: [OverlayCorner; 4] = [
+        OverlayCorner::TopLeft,
+        OverlayCorner::TopRight,
+        OverlayCorner::BottomLeft,
+        OverlayCorner::BottomRight,
+    ];
+
+    fn parse(corner: &str) -> Result<Self, String> {
+        match corner {
+            "top-left" => Ok(OverlayCorner::TopLeft),
+            "top-right" => Ok(OverlayCorner::TopRight),
+            "bottom-left" => Ok(OverlayCorner::BottomLeft),
+            "bottom-right" => Ok(OverlayCorner::BottomRight),
+            other => Err(format!("Unknown corner: {}", other)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            OverlayCorner::TopLeft => "top-left",
+            OverlayCorner::TopRight => "top-right",
+            OverlayCorner::BottomLeft => "bottom-left",
+            OverlayCorner::BottomRight => "bottom-right",
+        }
+    }
+
+    /// Window position that puts it flush against this corner of the monitor
+    fn position(
+        self,
+        monitor: &tauri::Monitor,
+        window_size: tauri::PhysicalSize<u32>,
+    ) -> tauri::PhysicalPosition<i32> {
+        let screen_pos = monitor.position();
+        let screen_size = monitor.size();
+        let left = screen_pos.x;
+        let top = screen_pos.y;
+        let right = screen_pos.x + screen_size.width as i32 - window_size.width as i32;
+        let bottom = screen_pos.y + screen_size.height as i32 - window_size.height as i32;
+        let (x, y) = match self {
+            OverlayCorner::TopLeft => (left, top),
+            OverlayCorner::TopRight => (right, top),
+            OverlayCorner::BottomLeft => (left, bottom),
+            OverlayCorner::BottomRight => (right, bottom),
+        };
+        tauri::PhysicalPosition { x, y }
+    }
+}
+
+/// Snaps the overlay to the nearest corner if it was released within `margin` of one
+fn snap_overlay_if_near_corner(window: &tauri::Window, margin: u32) {
+    let (Ok(Some(monitor)), Ok(position), Ok(size)) = (
+        window.current_monitor(),
+        window.outer_position(),
+        window.outer_size(),
+    ) else {
+        return;
+    };
+    let margin = (margin as f64 * monitor.scale_factor()).round() as i32;
+
+    let nearest = OverlayCorner::ALL
+        .into_iter()
+        .map(|corner| (corner, corner.position(&monitor, size)))
+        .map(|(corner, target)| {
+            let distance = (target.x - position.x)
+                .abs()
+                .max((target.y - position.y).abs());
+            (corner, target, distance)
+        })
+        .filter(|(_, _, distance)| *distance <= margin)
+        .min_by_key(|(_, _, distance)| *distance);
+
+    let Some((corner, target, distance)) = nearest else {
+        return;
+    };
+    if distance > 0 {
+        let _ = window.set_position(tauri::Position::Physical(target));
+    }
+    *window
+        .app_handle()
+        .state::<AppState>()
+        .last_snapped_corner
+        .lock()
+        .unwrap() = Some(corner.as_str().to_string());
+}
+
+/// Auto-snaps once the overlay stops moving, when snapping is enabled
+fn schedule_overlay_snap(window: &tauri::Window) {
+    let settings = load_snap_settings().unwrap_or_default();
+    if !settings.enabled {
+        return;
+    }
+
+    // Each move bumps the generation; only the last move of a drag triggers a snap
+    let generation = window
+        .app_handle()
+        .state::<AppState>()
+        .overlay_move_generation
+        .fetch_add(1, Ordering::SeqCst)
+        + 1;
+    let window = window.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(SNAP_RELEASE_DELAY).await;
+        let latest = window
+            .app_handle()
+            .state::<AppState>()
+            .overlay_move_generation
+            .load(Ordering::SeqCst);
+        if latest == generation {
+            snap_overlay_if_near_corner(&window, settings.margin);
+        }
+    });
+}
+
+#[command]
+async fn save_snap_settings(enabled: bool, margin: u32) -> Result<(), String> {
+    let settings_path = paths::get_snap_settings_path()?;
+    if let Some(parent) = settings_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&SnapSettings { enabled, margin })
+        .map_err(|e| format!("Failed to serialize snap settings: {}", e))?;
+    std::fs::write(&settings_path, content)
+        .map_err(|e| format!("Failed to save snap settings: {}", e))
+}
+
+#[command]
+async fn get_snap_settings() -> Result<SnapSettings, String> {
+    load_snap_settings()
+}
+
+#[command]
+async fn snap_overlay_to_corner(app: AppHandle, corner: String) -> Result<(), String> {
+    let corner = OverlayCorner::parse(&corner)?;
+    let window = app
+        .get_webview_window("overlay")
+        .ok_or_else(|| "Overlay window not found".to_string())?;
+    let monitor = window
+        .current_monitor()
+        .map_err(|e| format!("Failed to get monitor: {}", e))?
+        .ok_or_else(|| "No monitor found for overlay".to_string())?;
+    let size = window
+        .outer_size()
+        .map_err(|e| format!("Failed to get overlay size: {}", e))?;
+
+    window
+        .set_position(tauri::Position::Physical(corner.position(&monitor, size)))
+        .map_err(|e| format!("Failed to move overlay: {}", e))?;
+    *app.state::<AppState>().last_snapped_corner.lock().unwrap() =
+        Some(corner.as_str().to_string());
+    Ok(())
+}
+
+#[command]
+async fn get_last_snapped_corner(app: AppHandle) -> Result<Option<String>, String> {
+    Ok(app
+        .state::<AppState>()
+        .last_snapped_corner
+        .lock()
+        .unwrap()
+        .clone())
+}
+
+/// Load saved overlay opacity (returns 1.0 if not saved)
+fn load_overlay_opacity() -> f64 {
+    paths::get_overlay_opacity_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| content.trim().parse::<f64>().ok())
+        .map(|opacity| opacity.clamp(0.0, 1.0))
+        .unwrap_or(1.0)
+}
+
+#[cfg(target_os = "macos")]
+fn apply_overlay_opacity(window: &tauri::WebviewWindow, opacity: f64) -> Result<(), String> {
+    window
+        .with_webview(move |webview| unsafe {
+            let ns_window_ptr = webview.ns_window();
+            let ns_window: Retained<NSWindow> =
+                Retained::retain(ns_window_ptr as *mut NSWindow).unwrap();
+            ns_window.setAlphaValue(opacity);
+        })
+        .map_err(|e| format!("Failed to set overlay opacity: {}", e))
+}
+
+#[cfg(target_os = "windows")]
+fn apply_overlay_opacity(window: &tauri::WebviewWindow, opacity: f64) -> Result<(), String> {
+    let hwnd = window
+        .hwnd()
+        .map_err(|e| format!("Failed to get HWND: {}", e))?;
+    unsafe {
+        // Layered windows are required for per-window alpha
+        let ex_style = GetWindowLongPtrW(HWND(hwnd.0), GWL_EXSTYLE);
+        SetWindowLongPtrW(
+            HWND(hwnd.0),
+            GWL_EXSTYLE,
+            ex_style | WS_EX_LAYERED.0 as isize,
+        );
+        SetLayeredWindowAttributes(
+            HWND(hwnd.0),
+            COLORREF(0),
+            (opacity * 255.0).round() as u8,
+            LWA_ALPHA,
+        )
+        .map_err(|e| format!("SetLayeredWindowAttributes failed: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn apply_overlay_opacity(window: &tauri::WebviewWindow, opacity: f64) -> Result<(), String> {
+    window
+        .with_webview(move |webview| {
+            use gtk::prelude::*;
+
+            if let Some(gtk_window) = webview
+                .inner()
+                .toplevel()
+                .and_then(|w| w.downcast::<gtk::Window>().ok())
+            {
+                gtk_window.set_opacity(opacity);
+            }
+        })
+        .map_err(|e| format!("Failed to set overlay opacity: {}", e))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn apply_overlay_opacity(_window: &tauri::WebviewWindow, _opacity: f64) -> Result<(), String> {
+    Ok(())
+}
+
+/// Name screen readers announce for the overlay unless the user sets their own
+const DEFAULT_OVERLAY_ACCESSIBILITY_LABEL: &str = "Miku character overlay";
+
+/// Load saved overlay accessibility label (returns the default if not saved)
+fn load_overlay_accessibility_label() -> String {
+    paths::get_overlay_accessibility_label_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty())
+        .unwrap_or_else(|| DEFAULT_OVERLAY_ACCESSIBILITY_LABEL.to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn apply_overlay_accessibility_label(
+    window: &tauri::WebviewWindow,
+    label: &str,
+) -> Result<(), String> {
+    use objc2_app_kit::{NSAccessibility, NSAccessibilityWindowRole};
+    use objc2_foundation::NSString;
+
+    let label = label.to_string();
+    window
+        .with_webview(move |webview| unsafe {
+            let ns_window_ptr = webview.ns_window();
+            let Some(ns_window) = Retained::retain(ns_window_ptr as *mut NSWindow) else {
+                return;
+            };
+            ns_window.setAccessibilityRole(Some(NSAccessibilityWindowRole));
+            ns_window.setAccessibilityLabel(Some(&NSString::from_str(&label)));
+        })
+        .map_err(|e| format!("Failed to set overlay accessibility label: {}", e))
+}
+
+#[cfg(target_os = "windows")]
+fn apply_overlay_accessibility_label(
+    window: &tauri::WebviewWindow,
+    label: &str,
+) -> Result<(), String> {
+    use windows::Win32::UI::WindowsAndMessaging::SetWindowTextA;
+
+    let hwnd = window
+        .hwnd()
+        .map_err(|e| format!("Failed to get HWND: {}", e))?;
+    let label = std::ffi::CString::new(label)
+        .map_err(|_| "Accessibility label cannot contain NUL characters".to_string())?;
+    // Narrator reads the window title as the window's name
+    unsafe {
+        SetWindowTextA(
+            HWND(hwnd.0),
+            windows::core::PCSTR::from_raw(label.as_ptr() as *const u8),
+        )
+        .map_err(|e| format!("SetWindowTextA failed: {}", e))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn apply_overlay_accessibility_label(
+    window: &tauri::WebviewWindow,
+    label: &str,
+) -> Result<(), String> {
+    let label = label.to_string();
+    window
+        .with_webview(move |webview| {
+            use gtk::prelude::*;
+
+            // Orca reads the window title as the window's name
+            if let Some(gtk_window) = webview
+                .inner()
+                .toplevel()
+                .and_then(|w| w.downcast::<gtk::Window>().ok())
+            {
+                gtk_window.set_title(&label);
+            }
+        })
+        .map_err(|e| format!("Failed to set overlay accessibility label: {}", e))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn apply_overlay_accessibility_label(
+    _window: &tauri::WebviewWindow,
+    _label: &str,
+) -> Result<(), String> {
+    Ok(())
+}
+
+/// Changes the name screen readers announce for the overlay
+#[command]
+async fn set_overlay_accessibility_label(app: AppHandle, label: String) -> Result<(), String> {
+    let label = label.trim().to_string();
+    if label.is_empty() {
+        return Err("Accessibility label cannot be empty".to_string());
+    }
+
+    let path = paths::get_overlay_accessibility_label_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, &label)
+        .map_err(|e| format!("Failed to save accessibility label: {}", e))?;
+
+    if let Some(window) = app.get_webview_window("overlay") {
+        apply_overlay_accessibility_label(&window, &label)?;
+    }
+    Ok(())
+}
+
+#[command]
+async fn set_overlay_opacity(app: AppHandle, opacity: f64) -> Result<(), String> {
+    if !(0.0..=1.0).contains(&opacity) {
+        return Err("Opacity must be between 0.0 and 1.0".to_string());
+    }
+
+    if let Some(window) = app.get_webview_window("overlay") {
+        apply_overlay_opacity(&window, opacity)?;
+    }
+
+    let path = paths::get_overlay_opacity_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    std::fs::write(&path, opacity.to_string())
+        .map_err(|e| format!("Failed to save overlay opacity: {}", e))?;
+
+    // Keep the geometry file in sync so it holds the full overlay appearance
+    if let Some(mut geometry) = load_overlay_geometry_from_file() {
+        geometry.opacity = opacity;
+        save_overlay_geometry_to_file(&geometry)?;
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn get_overlay_opacity() -> Result<f64, String> {
+    Ok(load_overlay_opacity())
+}
+
+/// Resizes the overlay like `set_overlay_scale`, then moves it to the bottom right of its
+/// monitor
+#[command]
+async fn resize_overlay(app: AppHandle, scale: f64) -> Result<(), String> {
+    set_overlay_scale(app.clone(), app.state::<AppState>(), scale).await?;
+
+    if let Some(window) = app.get_webview_window("overlay") {
+        if let Ok(Some(monitor)) = window.current_monitor() {
+            // The new size may not be applied yet, so work it out from the clamped scale
+            let scale = *app.state::<AppState>().overlay_scale.lock().unwrap();
+            let scale_factor = monitor.scale_factor();
+            let physical_width = (paths::DEFAULT_OVERLAY_WIDTH * scale * scale_factor) as i32;
+            let physical_height = (paths::DEFAULT_OVERLAY_HEIGHT * scale * scale_factor) as i32;
+            let screen_size = monitor.size();
+            let screen_pos = monitor.position();
+            let x = screen_pos.x + screen_size.width as i32 - physical_width;
+            let y = screen_pos.y + screen_size.height as i32 - physical_height;
+            let _ =
+                window.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }));
+        }
+    }
+
+    Ok(())
+}
+
+/// Zooms the overlay relative to its default size, e.g. from scroll wheel input
+#[command]
+async fn set_overlay_scale(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    scale: f64,
+) -> Result<(), String> {
+    let scale = scale.clamp(MIN_OVERLAY_SCALE, MAX_OVERLAY_SCALE);
+
+    if let Some(overlay) = app.get_webview_window("overlay") {
+        // Logical size works correctly on Retina displays
+        overlay
+            .set_size(tauri::Size::Logical(tauri::LogicalSize {
+                width: paths::DEFAULT_OVERLAY_WIDTH * scale,
+                height: paths::DEFAULT_OVERLAY_HEIGHT * scale,
+            }))
+            .map_err(|e| format!("Failed to resize overlay: {}", e))?;
+    }
+
+    persist_overlay_scale(&state, scale)
+}
+
+#[command]
+async fn get_overlay_scale(state: tauri::State<'_, AppState>) -> Result<f64, String> {
+    Ok(*state.overlay_scale.lock().unwrap())
+}
+
+#[command]
+async fn set_talking_speed(app: AppHandle, wpm: u32) -> Result<(), String> {
+    if wpm == 0 {
+        return Err("Talking speed must be greater than 0".to_string());
+    }
+
+    let mut settings = load_display_settings().unwrap_or_default();
+    settings.talking_speed_wpm = wpm;
+    save_display_settings(&settings)?;
+
+    // The overlay scales its lip sync timing by the same multiplier passed to TTS
+    emit_event(
+        &app,
+        "talking-speed-changed",
+        json!({ "wpm": wpm, "speed": talking_speed_multiplier(wpm) }),
+    );
+
+    Ok(())
+}
+
+#[command]
+async fn get_talking_speed() -> Result<Value, String> {
+    let wpm = load_display_settings()
+        .unwrap_or_default()
+        .talking_speed_wpm;
+    Ok(json!({ "wpm": wpm, "speed": talking_speed_multiplier(wpm) }))
+}
+
+/// Snaps the overlay back up to the enforced minimum size if it was shrunk below it
+fn enforce_overlay_min_size(window: &tauri::Window, size: &tauri::PhysicalSize<u32>) {
+    let (min_width, min_height) = *window.state::<AppState>().overlay_min_size.lock().unwrap();
+    let scale_factor = window.scale_factor().unwrap_or(1.0);
+    let logical: tauri::LogicalSize<f64> = size.to_logical(scale_factor);
+
+    if logical.width < min_width as f64 || logical.height < min_height as f64 {
+        let width = logical.width.max(min_width as f64);
+        let height = logical.height.max(min_height as f64);
+        let _ = window.set_size(tauri::Size::Logical(tauri::LogicalSize { width, height }));
+    }
+}
+
+#[command]
+async fn set_overlay_minimum_size(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    min_width: u32,
+    min_height: u32,
+) -> Result<(), String> {
+    let mut settings = load_display_settings().unwrap_or_default();
+    settings.overlay_min_width = min_width;
+    settings.overlay_min_height = min_height;
+    save_display_settings(&settings)?;
+
+    *state.overlay_min_size.lock().unwrap() = (min_width, min_height);
+
+    // Apply immediately in case the overlay is already smaller than the new minimum
+    if let Some(window) = app.get_window("overlay") {
+        if let Ok(size) = window.inner_size() {
+            enforce_overlay_min_size(&window, &size);
+        }
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn hide_main_window(app: AppHandle) -> Result<(), String> {
+    info!("[hide_main_window] Attempting to hide main window");
+    if let Some(window) = app.get_webview_window("main") {
+        window.hide().map_err(|e| {
+            error!("[hide_main_window] Failed to hide window: {}", e);
+            e.to_string()
+        })?;
+        info!("[hide_main_window] Window hidden, emitting event");
+        emit_event(
+            &app,
+            "main-window-visibility-changed",
+            json!({ "visible": false }),
+        );
+        info!("[hide_main_window] Event emitted successfully");
+    } else {
+        warn!("[hide_main_window] Main window not found");
+    }
+    Ok(())
+}
+
+#[command]
+async fn show_main_window(app: AppHandle) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window("main") {
+        window.show().map_err(|e| e.to_string())?;
+        window.set_focus().map_err(|e| e.to_string())?;
+        emit_event(
+            &app,
+            "main-window-visibility-changed",
+            json!({ "visible": true }),
+        );
+    }
+    Ok(())
+}
+
+#[command]
+async fn toggle_main_window(app: AppHandle) -> Result<bool, String> {
+    if let Some(window) = app.get_webview_window("main") {
+        let is_visible = window.is_visible().map_err(|e| e.to_string())?;
+        if is_visible {
+            window.hide().map_err(|e| e.to_string())?;
+            emit_event(
+                &app,
+                "main-window-visibility-changed",
+                json!({ "visible": false }),
+            );
+            Ok(false)
+        } else {
+            window.show().map_err(|e| e.to_string())?;
+            window.set_focus().map_err(|e| e.to_string())?;
+            emit_event(
+                &app,
+                "main-window-visibility-changed",
+                json!({ "visible": true }),
+            );
+            Ok(true)
+        }
+    } else {
+        Ok(false)
+    }
+}
+
+#[command]
+async fn is_main_window_visible(app: AppHandle) -> Result<bool, String> {
+    if let Some(window) = app.get_webview_window("main") {
+        window.is_visible().map_err(|e| e.to_string())
+    } else {
+        Ok(false)
+    }
+}
+
+// ============ Tray Settings ============
+
+/// Runs a tray menu action (also triggered by the action's global shortcut)
+fn run_tray_action(app: &AppHandle, action: &str) {
+    match action {
+        "toggle" => {
+            toggle_overlay_sync(app);
+        }
+        "chat_history" => {
+            // Show overlay and emit event to open history modal
+            if let Some(window) = app.get_webview_window("overlay") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            emit_event(app, "show-chat-history", ());
+        }
+        "bookmarks" => {
+            // Show overlay and emit event to open the bookmarks view
+            if let Some(window) = app.get_webview_window("overlay") {
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            emit_event(app, "show-bookmarks", ());
+        }
+        "settings" => {
+            // Show main window (for API key entry, etc.)
+            open_settings_window(app);
+        }
+        "screenshots" => {
+            // Open screenshots folder
+            std::thread::spawn(|| {
+                let _ = tauri::async_runtime::block_on(open_screenshots_folder());
+            });
+        }
+        "clear_data" => {
+            if let Err(e) = clear_app_data() {
+                error!("Error clearing app data: {}", e);
+            }
+        }
+        "quit" => {
+            std::process::exit(0);
+        }
+        _ => {}
+    }
+}
+
+/// Shows and focuses the main (settings) window
+fn open_settings_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        emit_event(
+            app,
+            "main-window-visibility-changed",
+            json!({ "visible": true }),
+        );
+    }
+}
 
-    // Position in bottom right of screen
-    if let Ok(Some(monitor)) = overlay.current_monitor() {
-        let screen_size = monitor.size();
-        let screen_pos = monitor.position();
-        if let Ok(window_size) = overlay.outer_size() {
-            let x = screen_pos.x + (screen_size.width as i32) - (window_size.width as i32);
-            let y = screen_pos.y + (screen_size.height as i32) - (window_size.height as i32);
-            let _ =
-                overlay.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }));
+/// Runs the configured action for a left click on the tray icon
+fn handle_tray_left_click(app: &AppHandle) {
+    let settings = load_tray_settings().unwrap_or_default();
+    match settings.left_click_action.as_str() {
+        "show_overlay" => {
+            let is_visible = *app.state::<AppState>().overlay_visible.lock().unwrap();
+            if !is_visible {
+                toggle_overlay_sync(app);
+            }
+        }
+        "open_settings" => open_settings_window(app),
+        "open_chat" => {
+            let is_visible = *app.state::<AppState>().overlay_visible.lock().unwrap();
+            if !is_visible {
+                toggle_overlay_sync(app);
+            }
+            if let Some(window) = app.get_webview_window("overlay") {
+                let _ = window.set_focus();
+            }
+            emit_overlay_event(app, "toggle-textbox", ());
         }
+        _ => toggle_overlay_sync(app),
     }
+}
 
-    // Show the overlay
-    overlay
-        .show()
-        .map_err(|e| format!("Failed to show overlay: {}", e))?;
+#[command]
+async fn save_tray_settings(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    left_click_action: String,
+    right_click_shows_menu: bool,
+) -> Result<(), String> {
+    if !TRAY_LEFT_CLICK_ACTIONS.contains(&left_click_action.as_str()) {
+        return Err(format!(
+            "Invalid left click action: {} (expected one of {})",
+            left_click_action,
+            TRAY_LEFT_CLICK_ACTIONS.join(", ")
+        ));
+    }
 
-    // Update state
-    *state.overlay_visible.lock().unwrap() = true;
+    let settings = TraySettings {
+        left_click_action,
+        right_click_shows_menu,
+    };
 
-    // Wait for page to fully load before emitting init-complete
-    println!("[Rust] Waiting for overlay page to load...");
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    let settings_path = paths::get_tray_settings_path()?;
+    if let Some(parent) = settings_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize tray settings: {}", e))?;
+    std::fs::write(&settings_path, content)
+        .map_err(|e| format!("Failed to save tray settings: {}", e))?;
+
+    // Attach or detach the context menu so right-click follows the new setting
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let menu = if settings.right_click_shows_menu {
+            state.tray_menu.lock().unwrap().clone()
+        } else {
+            None
+        };
+        tray.set_menu(menu)
+            .map_err(|e| format!("Failed to update tray menu: {}", e))?;
+    }
 
-    // Emit init-complete to trigger model loading
-    println!("[Rust] Emitting init-complete to load model");
-    overlay
-        .emit("init-complete", json!({}))
-        .map_err(|e| format!("Failed to emit init-complete: {}", e))?;
+    Ok(())
+}
 
-    println!("[Rust] Overlay recreated successfully");
-    Ok("Character reloaded!".to_string())
+#[command]
+async fn get_tray_settings() -> Result<TraySettings, String> {
+    load_tray_settings()
 }
 
-// TextureVersion struct is in models.rs
+// ============ Tray Action Shortcuts ============
+
+/// Optional global shortcuts for tray menu actions (e.g. "Alt+,")
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct ShortcutsConfig {
+    pub open_settings: Option<String>,
+    pub open_chat_window: Option<String>,
+    pub open_screenshots_folder: Option<String>,
+    pub clear_data: Option<String>,
+    pub quit: Option<String>,
+}
 
-#[command]
-async fn get_texture_versions() -> Result<Vec<TextureVersion>, String> {
-    let config = load_model_config()?;
-    let texture_folder = config
-        .texture_folder
-        .ok_or_else(|| "No texture folder configured".to_string())?;
+impl ShortcutsConfig {
+    /// Pairs each configured shortcut with the tray menu action it triggers
+    fn actions(&self) -> Vec<(&'static str, &Option<String>)> {
+        vec![
+            ("settings", &self.open_settings),
+            ("chat_history", &self.open_chat_window),
+            ("screenshots", &self.open_screenshots_folder),
+            ("clear_data", &self.clear_data),
+            ("quit", &self.quit),
+        ]
+    }
+}
 
-    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
-    let originals_dir = get_originals_dir_for_model(&config.folder, &texture_folder)?;
+fn load_shortcuts_config() -> Result<ShortcutsConfig, String> {
+    let config_path = paths::get_shortcuts_config_path()?;
+    if config_path.exists() {
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read shortcuts config: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse shortcuts config: {}", e))
+    } else {
+        Ok(ShortcutsConfig::default())
+    }
+}
 
-    let mut versions = Vec::new();
+/// Registers the configured tray action shortcuts and shows them in the tray menu
+fn register_action_shortcuts(app: &AppHandle) -> Result<(), String> {
+    let config = load_shortcuts_config()?;
+    let state = app.state::<AppState>();
+    let mut registered = Vec::new();
 
-    // Add generated versions
-    if versions_dir.exists() {
-        for entry in std::fs::read_dir(&versions_dir).map_err(|e| e.to_string())? {
-            let entry = entry.map_err(|e| e.to_string())?;
-            if entry.path().is_dir() {
-                let id = entry.file_name().to_string_lossy().to_string();
-                let metadata_path = entry.path().join("metadata.json");
-                let (created_at, prompt) = if metadata_path.exists() {
-                    let content = std::fs::read_to_string(&metadata_path).unwrap_or_default();
-                    let json: Value = serde_json::from_str(&content).unwrap_or(json!({}));
-                    (
-                        json["created_at"].as_str().unwrap_or(&id).to_string(),
-                        json["prompt"].as_str().map(|s| s.to_string()),
-                    )
-                } else {
-                    (id.clone(), None)
-                };
-                versions.push(TextureVersion {
-                    id,
-                    created_at,
-                    prompt,
-                });
-            }
+    for (action, accelerator) in config.actions() {
+        let Some(accelerator) = accelerator.as_deref() else {
+            continue;
+        };
+        match accelerator.parse::<Shortcut>() {
+            Ok(shortcut) => match app.global_shortcut().register(shortcut) {
+                Ok(_) => registered.push((shortcut, action.to_string())),
+                Err(e) => warn!(
+                    "[shortcuts] Failed to register {} for {}: {}",
+                    accelerator, action, e
+                ),
+            },
+            Err(e) => warn!(
+                "[shortcuts] Invalid shortcut {} for {}: {}",
+                accelerator, action, e
+            ),
         }
     }
 
-    versions.sort_by(|a, b| b.id.cmp(&a.id)); // Newest first
+    // Show active shortcuts next to their tray menu labels
+    for (action, label, item) in state.tray_action_items.lock().unwrap().iter() {
+        let accelerator = config
+            .actions()
+            .into_iter()
+            .find(|(a, _)| a == action)
+            .and_then(|(_, s)| s.clone())
+            .filter(|_| registered.iter().any(|(_, a)| a == action));
+        let text = match accelerator {
+            Some(accelerator) => format!("{}\t{}", label, accelerator),
+            None => label.clone(),
+        };
+        let _ = item.set_text(text);
+    }
 
-    // Add "original" as the last option if originals exist
-    if originals_dir.exists() {
-        // Check if any png files exist in originals
-        let has_originals = std::fs::read_dir(&originals_dir)
-            .ok()
-            .map(|entries| {
-                entries
-                    .filter_map(|e| e.ok())
-                    .any(|e| e.path().extension().is_some_and(|ext| ext == "png"))
-            })
-            .unwrap_or(false);
+    *state.action_shortcuts.lock().unwrap() = registered;
+    Ok(())
+}
 
-        if has_originals {
-            versions.push(TextureVersion {
-                id: "original".to_string(),
-                created_at: "Original".to_string(),
-                prompt: Some("Original textures".to_string()),
-            });
-        }
+/// Unregisters all tray action shortcuts
+fn unregister_action_shortcuts(app: &AppHandle) {
+    let state = app.state::<AppState>();
+    let registered = std::mem::take(&mut *state.action_shortcuts.lock().unwrap());
+    for (shortcut, _) in registered {
+        let _ = app.global_shortcut().unregister(shortcut);
     }
+}
 
-    Ok(versions)
+/// Returns the tray action bound to a shortcut, if any
+fn action_for_shortcut(app: &AppHandle, shortcut: &Shortcut) -> Option<String> {
+    app.state::<AppState>()
+        .action_shortcuts
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(s, _)| s == shortcut)
+        .map(|(_, action)| action.clone())
 }
 
 #[command]
-async fn apply_texture_version(version_id: String) -> Result<String, String> {
-    let config = load_model_config()?;
-    let texture_folder = config
-        .texture_folder
-        .ok_or_else(|| "No texture folder configured".to_string())?;
+async fn save_shortcuts_config(config: ShortcutsConfig) -> Result<(), String> {
+    let config_path = paths::get_shortcuts_config_path()?;
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize shortcuts config: {}", e))?;
+    std::fs::write(&config_path, content)
+        .map_err(|e| format!("Failed to save shortcuts config: {}", e))
+}
 
-    let texture_dir = get_texture_dir_for_model(&config.folder, &texture_folder)?;
+#[command]
+async fn get_shortcuts_config() -> Result<ShortcutsConfig, String> {
+    load_shortcuts_config()
+}
 
-    // Handle "original" as a special case
-    let source_dir = if version_id == "original" {
-        get_originals_dir_for_model(&config.folder, &texture_folder)?
-    } else {
-        get_versions_dir_for_model(&config.folder, &texture_folder)?.join(&version_id)
-    };
+#[command]
+async fn reload_shortcuts(app: AppHandle) -> Result<(), String> {
+    unregister_action_shortcuts(&app);
+    register_action_shortcuts(&app)
+}
 
-    if !source_dir.exists() {
-        return Err("Version not found".to_string());
+// ============ Textbox Shortcut ============
+
+/// Parses a modifier string such as "Alt" or "Ctrl+Shift"
+fn parse_shortcut_modifiers(modifier: &str) -> Result<Modifiers, String> {
+    let mut modifiers = Modifiers::empty();
+    for part in modifier.split('+').map(str::trim).filter(|p| !p.is_empty()) {
+        modifiers |= match part.to_lowercase().as_str() {
+            "alt" | "option" => Modifiers::ALT,
+            "ctrl" | "control" => Modifiers::CONTROL,
+            "shift" => Modifiers::SHIFT,
+            "super" | "cmd" | "command" | "meta" | "win" => Modifiers::SUPER,
+            _ => return Err(format!("Unknown modifier: {}", part)),
+        };
     }
+    Ok(modifiers)
+}
 
-    // Discover and copy all texture files from the source
-    for entry in
-        std::fs::read_dir(&source_dir).map_err(|e| format!("Failed to read source: {}", e))?
-    {
-        let entry = entry.map_err(|e| e.to_string())?;
-        if entry.path().extension().is_some_and(|ext| ext == "png") {
-            let file_name = entry.file_name();
-            let dst = texture_dir.join(&file_name);
-            std::fs::copy(entry.path(), &dst)
-                .map_err(|e| format!("Failed to apply {:?}: {}", file_name, e))?;
+/// Parses a key name such as "Space", "K" or "F5"
+fn parse_shortcut_code(key: &str) -> Result<Code, String> {
+    let key = key.trim();
+    let name = match key.chars().next() {
+        Some(c) if key.len() == 1 && c.is_ascii_alphabetic() => {
+            format!("Key{}", c.to_ascii_uppercase())
         }
-    }
+        Some(c) if key.len() == 1 && c.is_ascii_digit() => format!("Digit{}", c),
+        _ => key.to_string(),
+    };
+    name.parse::<Code>()
+        .map_err(|_| format!("Unknown key: {}", key))
+}
 
-    Ok(format!(
-        "Applied {}",
-        if version_id == "original" {
-            "original textures"
-        } else {
-            &version_id
-        }
+fn parse_shortcut_config(config: &ShortcutConfig) -> Result<Shortcut, String> {
+    let modifiers = parse_shortcut_modifiers(&config.modifier)?;
+    let code = parse_shortcut_code(&config.key)?;
+    Ok(Shortcut::new(
+        (!modifiers.is_empty()).then_some(modifiers),
+        code,
     ))
 }
 
-#[command]
-async fn delete_texture_version(version_id: String) -> Result<String, String> {
-    // Prevent deleting the original
-    if version_id == "original" {
-        return Err("Cannot delete original textures".to_string());
+fn load_shortcut_config() -> ShortcutConfig {
+    let Ok(config_path) = paths::get_shortcut_config_path() else {
+        return ShortcutConfig::default();
+    };
+    std::fs::read_to_string(config_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_shortcut_config_to_file(config: &ShortcutConfig) -> Result<(), String> {
+    let config_path = paths::get_shortcut_config_path()?;
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory: {}", e))?;
     }
+    let content = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize shortcut config: {}", e))?;
+    std::fs::write(&config_path, content)
+        .map_err(|e| format!("Failed to save shortcut config: {}", e))
+}
 
-    let config = load_model_config()?;
-    let texture_folder = config
-        .texture_folder
-        .ok_or_else(|| "No texture folder configured".to_string())?;
+/// Returns the saved textbox shortcut, falling back to the platform default
+fn load_textbox_shortcut() -> Shortcut {
+    let config = load_shortcut_config();
+    parse_shortcut_config(&config).unwrap_or_else(|e| {
+        warn!("[shortcuts] Invalid textbox shortcut, using default: {}", e);
+        parse_shortcut_config(&ShortcutConfig::default())
+            .expect("default textbox shortcut is valid")
+    })
+}
 
-    let versions_dir = get_versions_dir_for_model(&config.folder, &texture_folder)?;
-    let version_path = versions_dir.join(&version_id);
+#[command]
+async fn save_shortcut_config(modifier: String, key: String) -> Result<(), String> {
+    let config = ShortcutConfig { modifier, key };
+    parse_shortcut_config(&config)?;
+    save_shortcut_config_to_file(&config)
+}
 
-    if !version_path.exists() {
-        return Err("Version not found".to_string());
+#[command]
+async fn get_shortcut_config() -> Result<ShortcutConfig, String> {
+    Ok(load_shortcut_config())
+}
+
+/// Swaps the textbox shortcut at runtime and persists it
+#[command]
+async fn update_shortcut(app: AppHandle, modifier: String, key: String) -> Result<(), String> {
+    let config = ShortcutConfig { modifier, key };
+    let shortcut = parse_shortcut_config(&config)?;
+
+    let state = app.state::<AppState>();
+    let previous = *state.textbox_shortcut.lock().unwrap();
+    if previous == Some(shortcut) {
+        return save_shortcut_config_to_file(&config);
     }
 
-    std::fs::remove_dir_all(&version_path).map_err(|e| format!("Failed to delete: {}", e))?;
+    if let Some(previous) = previous {
+        let _ = app.global_shortcut().unregister(previous);
+    }
+    if let Err(e) = app.global_shortcut().register(shortcut) {
+        // Restore the old binding so the textbox stays reachable
+        if let Some(previous) = previous {
+            let _ = app.global_shortcut().register(previous);
+        }
+        return Err(format!("Failed to register shortcut: {}", e));
+    }
+    *state.textbox_shortcut.lock().unwrap() = Some(shortcut);
 
-    Ok("Version deleted".to_string())
+    info!(
+        "[shortcuts] Textbox shortcut changed to {}+{}",
+        config.modifier, config.key
+    );
+    save_shortcut_config_to_file(&config)
 }
 
-// ============ App State ============
+// ============ Gaze Calibration ============
 
-#[derive(Default)]
-pub struct AppState {
-    pub overlay_visible: Mutex<bool>,
-    pub toggle_menu_item: Mutex<Option<MenuItem<tauri::Wry>>>,
+/// Minimum calibration points needed to fit a gaze transform
+const MIN_GAZE_CALIBRATION_POINTS: usize = 4;
+
+/// Least-squares fit of `model = scale * screen + offset` for one axis
+fn fit_gaze_axis(samples: impl Iterator<Item = (f64, f64)> + Clone) -> Option<(f64, f64)> {
+    let n = samples.clone().count() as f64;
+    let mean_screen = samples.clone().map(|(s, _)| s).sum::<f64>() / n;
+    let mean_model = samples.clone().map(|(_, m)| m).sum::<f64>() / n;
+    let (covariance, variance) = samples.fold((0.0, 0.0), |(cov, var), (s, m)| {
+        let ds = s - mean_screen;
+        (cov + ds * (m - mean_model), var + ds * ds)
+    });
+    if variance.abs() < f64::EPSILON {
+        return None;
+    }
+    let scale = covariance / variance;
+    Some((scale, mean_model - scale * mean_screen))
 }
 
-// ============ Overlay Window Commands ============
+#[command]
+async fn calibrate_gaze(
+    state: tauri::State<'_, AppState>,
+    screen_x: f64,
+    screen_y: f64,
+    model_x: f64,
+    model_y: f64,
+) -> Result<usize, String> {
+    let mut points = state.gaze_calibration_points.lock().unwrap();
+    points.push((screen_x, screen_y, model_x, model_y));
+    Ok(points.len())
+}
 
-#[cfg(target_os = "macos")]
-fn configure_overlay(window: &tauri::WebviewWindow) -> Result<(), String> {
-    window
-        .with_webview(|webview| unsafe {
-            let ns_window_ptr = webview.ns_window();
-            let ns_window: Retained<NSWindow> =
-                Retained::retain(ns_window_ptr as *mut NSWindow).unwrap();
+#[command]
+async fn compute_gaze_transform(
+    state: tauri::State<'_, AppState>,
+) -> Result<GazeTransform, String> {
+    let points = state.gaze_calibration_points.lock().unwrap().clone();
+    if points.len() < MIN_GAZE_CALIBRATION_POINTS {
+        return Err(format!(
+            "At least {} calibration points are required, got {}",
+            MIN_GAZE_CALIBRATION_POINTS,
+            points.len()
+        ));
+    }
 
-            let behavior = NSWindowCollectionBehavior::CanJoinAllSpaces
-                | NSWindowCollectionBehavior::FullScreenAuxiliary;
-            ns_window.setCollectionBehavior(behavior);
-            ns_window.setLevel(1000);
-        })
-        .map_err(|e| format!("Failed to configure overlay: {}", e))?;
-    Ok(())
+    let (scale_x, offset_x) = fit_gaze_axis(points.iter().map(|p| (p.0, p.2)))
+        .ok_or("Calibration points must differ horizontally")?;
+    let (scale_y, offset_y) = fit_gaze_axis(points.iter().map(|p| (p.1, p.3)))
+        .ok_or("Calibration points must differ vertically")?;
+
+    let transform = GazeTransform {
+        scale_x,
+        scale_y,
+        offset_x,
+        offset_y,
+    };
+    *state.gaze_transform.lock().unwrap() = Some(transform.clone());
+    info!("[gaze] Fitted transform from {} points", points.len());
+    Ok(transform)
 }
 
-#[cfg(target_os = "windows")]
-fn configure_overlay(window: &tauri::WebviewWindow) -> Result<(), String> {
-    let hwnd = window
-        .hwnd()
-        .map_err(|e| format!("Failed to get HWND: {}", e))?;
-    unsafe {
-        SetWindowPos(
-            HWND(hwnd.0),
-            HWND_TOPMOST,
-            0,
-            0,
-            0,
-            0,
-            SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
-        )
-        .map_err(|e| format!("SetWindowPos failed: {}", e))?;
-    }
+#[command]
+async fn clear_gaze_calibration(state: tauri::State<'_, AppState>) -> Result<(), String> {
+    state.gaze_calibration_points.lock().unwrap().clear();
+    *state.gaze_transform.lock().unwrap() = None;
     Ok(())
 }
 
-#[cfg(not(any(target_os = "macos", target_os = "windows")))]
-fn configure_overlay(_window: &tauri::WebviewWindow) -> Result<(), String> {
-    Ok(())
+// ============ Device Listening ============
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceEvent {
+    kind: String,
+    value: Value,
 }
 
-#[command]
-async fn show_overlay(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
-    info!("show_overlay called");
-    if let Some(window) = app.get_webview_window("overlay") {
-        info!("show_overlay: overlay window found");
-        configure_overlay(&window)?;
+static IS_LISTENING: AtomicBool = AtomicBool::new(false);
 
-        // Position in bottom right of screen
-        if let Ok(Some(monitor)) = window.current_monitor() {
-            let screen_size = monitor.size();
-            let screen_pos = monitor.position();
-            if let Ok(window_size) = window.outer_size() {
-                let x = screen_pos.x + (screen_size.width as i32) - (window_size.width as i32);
-                let y = screen_pos.y + (screen_size.height as i32) - (window_size.height as i32);
-                let _ = window
-                    .set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }));
-                info!("show_overlay: positioned at ({}, {})", x, y);
-            }
-        }
+/// Minimum gap between `typing-activity` events while keys are being pressed
+const TYPING_ACTIVITY_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(50);
 
-        window.show().map_err(|e| e.to_string())?;
-        info!("show_overlay: window.show() completed");
-        window.set_focus().map_err(|e| e.to_string())?;
+/// Input device types the listener can be filtered by
+const DEVICE_TYPES: [&str; 3] = ["mouse", "keyboard", "scroll"];
 
-        // Update state
-        *state.overlay_visible.lock().unwrap() = true;
+fn all_device_types() -> HashSet<String> {
+    DEVICE_TYPES.iter().map(|t| t.to_string()).collect()
+}
 
-        // Update tray menu text
-        if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
-            let _ = menu_item.set_text("Hide Character");
-        }
+fn parse_device_filter(device_types: Vec<String>) -> Result<HashSet<String>, String> {
+    device_types
+        .into_iter()
+        .map(|t| {
+            let t = t.trim().to_lowercase();
+            if DEVICE_TYPES.contains(&t.as_str()) {
+                Ok(t)
+            } else {
+                Err(format!("Unknown device type: {}", t))
+            }
+        })
+        .collect()
+}
 
-        // Emit event
-        let _ = app.emit("overlay-visibility-changed", json!({ "visible": true }));
-        info!("show_overlay: completed successfully");
-    } else {
-        info!("show_overlay: overlay window NOT found");
-    }
-    Ok(())
+/// Whether `device-changed` events are enabled for the given device type
+fn device_enabled(app: &AppHandle, device_type: &str) -> bool {
+    app.state::<AppState>()
+        .device_filter
+        .lock()
+        .unwrap()
+        .contains(device_type)
 }
 
 #[command]
-async fn hide_overlay(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("overlay") {
-        window.hide().map_err(|e| e.to_string())?;
+async fn start_device_listening(app: AppHandle) -> Result<(), String> {
+    if IS_LISTENING.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    IS_LISTENING.store(true, Ordering::SeqCst);
 
-        // Update state
-        *state.overlay_visible.lock().unwrap() = false;
+    std::thread::spawn(move || {
+        let mut last_typing_activity: Option<std::time::Instant> = None;
 
-        // Update tray menu text
-        if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
-            let _ = menu_item.set_text("Show Character");
-        }
+        let callback = move |event: Event| match event.event_type {
+            // Mouse tracking for head movement
+            EventType::MouseMove { x, y } => {
+                if !device_enabled(&app, "mouse") {
+                    return;
+                }
+                let (x, y) = match *app.state::<AppState>().gaze_transform.lock().unwrap() {
+                    Some(ref t) => (t.scale_x * x + t.offset_x, t.scale_y * y + t.offset_y),
+                    None => (x, y),
+                };
+                let device_event = DeviceEvent {
+                    kind: "MouseMove".to_string(),
+                    value: json!({ "x": x, "y": y }),
+                };
+                emit_overlay_event(&app, "device-changed", device_event);
+            }
+            // Keyboard activity for typing reactions. Which key was pressed is never
+            // reported, and the event skips the overlay event log and webhooks.
+            EventType::KeyPress(_) => {
+                if !device_enabled(&app, "keyboard") {
+                    return;
+                }
+
+                // Debounce: at most one typing-activity event per 50ms burst of keys
+                let now = std::time::Instant::now();
+                let debounced = last_typing_activity
+                    .is_some_and(|last| now.duration_since(last) < TYPING_ACTIVITY_DEBOUNCE);
+                if !debounced {
+                    last_typing_activity = Some(now);
+                    let _ = app.emit("typing-activity", json!({ "active": true }));
+                }
+            }
+            // Scroll wheel for zooming the overlay
+            EventType::Wheel { delta_x, delta_y } => {
+                if !device_enabled(&app, "scroll") {
+                    return;
+                }
+                // Too frequent for the overlay event log and webhooks
+                let device_event = DeviceEvent {
+                    kind: "Scroll".to_string(),
+                    value: json!({ "delta_x": delta_x as f64, "delta_y": delta_y as f64 }),
+                };
+                let _ = app.emit("device-changed", device_event);
+            }
+            _ => {}
+        };
+        listen(callback).ok();
+    });
 
-        // Emit event
-        let _ = app.emit("overlay-visibility-changed", json!({ "visible": false }));
-    }
     Ok(())
 }
 
+/// Starts listening with `device-changed` events limited to the given device types
 #[command]
-async fn toggle_overlay(app: AppHandle, state: tauri::State<'_, AppState>) -> Result<bool, String> {
-    let is_visible = *state.overlay_visible.lock().unwrap();
+async fn start_device_listening_filtered(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    device_types: Vec<String>,
+) -> Result<(), String> {
+    *state.device_filter.lock().unwrap() = parse_device_filter(device_types)?;
+    start_device_listening(app).await
+}
 
-    if is_visible {
-        hide_overlay(app, state).await?;
-        Ok(false)
-    } else {
-        show_overlay(app, state).await?;
-        Ok(true)
-    }
+/// Changes which device types emit `device-changed` without restarting the listener
+#[command]
+async fn update_device_filter(
+    app: AppHandle,
+    state: tauri::State<'_, AppState>,
+    device_types: Vec<String>,
+) -> Result<(), String> {
+    let filter = parse_device_filter(device_types)?;
+    let mut enabled: Vec<&String> = filter.iter().collect();
+    enabled.sort();
+    emit_event(
+        &app,
+        "device-filter-updated",
+        json!({ "device_types": enabled }),
+    );
+    *state.device_filter.lock().unwrap() = filter;
+    Ok(())
 }
 
-// Sync version for use in tray handlers (non-async context)
-fn toggle_overlay_sync(app: &AppHandle) {
-    let state = app.state::<AppState>();
-    let is_visible = *state.overlay_visible.lock().unwrap();
+// ============ Screenshot ============
 
-    if is_visible {
-        if let Some(window) = app.get_webview_window("overlay") {
-            let _ = window.hide();
-            *state.overlay_visible.lock().unwrap() = false;
-            let _ = app.emit("overlay-visibility-changed", json!({ "visible": false }));
+// Native macOS screen capture permission APIs
+#[cfg(target_os = "macos")]
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGPreflightScreenCaptureAccess() -> bool;
+    fn CGRequestScreenCaptureAccess() -> bool;
+}
 
-            // Update tray menu text
-            if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
-                let _ = menu_item.set_text("Show Character");
-            }
-        }
-    } else if let Some(window) = app.get_webview_window("overlay") {
-        let _ = configure_overlay(&window);
+#[cfg(target_os = "macos")]
+const SCREEN_RECORDING_PERMISSION_MESSAGE: &str = "Screen recording permission required. Go to System Settings > Privacy & Security > Screen Recording and enable Oto Desktop.";
 
-        // Position in bottom right of screen
-        if let Ok(Some(monitor)) = window.current_monitor() {
-            let screen_size = monitor.size();
-            let screen_pos = monitor.position();
-            if let Ok(window_size) = window.outer_size() {
-                let x = screen_pos.x + (screen_size.width as i32) - (window_size.width as i32);
-                let y = screen_pos.y + (screen_size.height as i32) - (window_size.height as i32);
-                let _ = window
-                    .set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }));
+/// Capture attempts made while waiting for screen recording permission
+#[cfg(target_os = "macos")]
+const SCREENSHOT_PERMISSION_ATTEMPTS: u32 = 3;
+/// Delay between permission checks
+#[cfg(target_os = "macos")]
+const PERMISSION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+/// How long `request_screen_permission_and_wait` waits for the user to grant access
+#[cfg(target_os = "macos")]
+const PERMISSION_WAIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+#[command]
+async fn check_screen_permission() -> Result<bool, String> {
+    #[cfg(target_os = "macos")]
+    {
+        unsafe {
+            // First check if we already have permission
+            if CGPreflightScreenCaptureAccess() {
+                return Ok(true);
             }
+            // If not, request permission (triggers system dialog)
+            Ok(CGRequestScreenCaptureAccess())
         }
+    }
 
-        let _ = window.show();
-        let _ = window.set_focus();
-        *state.overlay_visible.lock().unwrap() = true;
-        let _ = app.emit("overlay-visibility-changed", json!({ "visible": true }));
-
-        // Update tray menu text
-        if let Some(menu_item) = state.toggle_menu_item.lock().unwrap().as_ref() {
-            let _ = menu_item.set_text("Hide Character");
-        }
+    #[cfg(not(target_os = "macos"))]
+    {
+        Ok(true)
     }
 }
 
+/// Requests screen recording access and waits for the user to grant it
 #[command]
-async fn get_overlay_visible(state: tauri::State<'_, AppState>) -> Result<bool, String> {
-    Ok(*state.overlay_visible.lock().unwrap())
-}
-
-/// Load saved overlay scale (returns 1.0 if not saved)
-fn load_overlay_scale() -> f64 {
-    if let Ok(path) = paths::get_overlay_scale_path() {
-        if let Ok(content) = std::fs::read_to_string(&path) {
-            if let Ok(scale) = content.trim().parse::<f64>() {
-                return scale.clamp(0.5, 2.0);
+async fn request_screen_permission_and_wait(app: AppHandle) -> Result<bool, String> {
+    #[cfg(target_os = "macos")]
+    {
+        if unsafe { CGPreflightScreenCaptureAccess() } {
+            return Ok(true);
+        }
+        // Triggers the system dialog the first time; later calls are no-ops
+        unsafe { CGRequestScreenCaptureAccess() };
+
+        let started = std::time::Instant::now();
+        while started.elapsed() < PERMISSION_WAIT_TIMEOUT {
+            tokio::time::sleep(PERMISSION_POLL_INTERVAL).await;
+            if unsafe { CGPreflightScreenCaptureAccess() } {
+                emit_event(
+                    &app,
+                    "permission-granted",
+                    json!({ "permission": "screen_recording" }),
+                );
+                return Ok(true);
             }
         }
+        Ok(false)
     }
-    1.0
-}
 
-/// Save overlay scale to file
-fn save_overlay_scale_to_file(scale: f64) -> Result<(), String> {
-    let path = paths::get_overlay_scale_path()?;
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create directory: {}", e))?;
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = app;
+        Ok(true)
     }
-    std::fs::write(&path, scale.to_string())
-        .map_err(|e| format!("Failed to save overlay scale: {}", e))
 }
 
 #[command]
-async fn resize_overlay(app: AppHandle, scale: f64) -> Result<(), String> {
-    let scale = scale.clamp(0.5, 2.0);
+async fn open_screen_recording_settings() -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("open")
+            .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture")
+            .spawn()
+            .map_err(|e| format!("Failed to open settings: {}", e))?;
+    }
+    Ok(())
+}
 
-    if let Some(window) = app.get_webview_window("overlay") {
-        let width = paths::DEFAULT_OVERLAY_WIDTH * scale;
-        let height = paths::DEFAULT_OVERLAY_HEIGHT * scale;
+// ============ Platform Permissions ============
 
-        // Resize the window using logical size (works correctly on Retina displays)
-        window
-            .set_size(tauri::Size::Logical(tauri::LogicalSize { width, height }))
-            .map_err(|e| format!("Failed to resize overlay: {}", e))?;
+/// Checks whether the current process holds SeDebugPrivilege (used for window enumeration)
+#[cfg(target_os = "windows")]
+fn has_debug_privilege() -> bool {
+    use windows::Win32::Foundation::{CloseHandle, BOOL, HANDLE, LUID};
+    use windows::Win32::Security::{
+        LookupPrivilegeValueW, PrivilegeCheck, LUID_AND_ATTRIBUTES, PRIVILEGE_SET, SE_DEBUG_NAME,
+        SE_PRIVILEGE_ENABLED, TOKEN_QUERY,
+    };
+    use windows::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
 
-        // Reposition to bottom right
-        if let Ok(Some(monitor)) = window.current_monitor() {
-            let scale_factor = monitor.scale_factor();
-            let screen_size = monitor.size();
-            let screen_pos = monitor.position();
-            // Convert logical width/height to physical for position calculation
-            let physical_width = (width * scale_factor) as i32;
-            let physical_height = (height * scale_factor) as i32;
-            let x = screen_pos.x + (screen_size.width as i32) - physical_width;
-            let y = screen_pos.y + (screen_size.height as i32) - physical_height;
-            let _ =
-                window.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }));
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
         }
 
-        // Save the scale
-        save_overlay_scale_to_file(scale)?;
-    }
+        let mut luid = LUID::default();
+        if LookupPrivilegeValueW(None, SE_DEBUG_NAME, &mut luid).is_err() {
+            let _ = CloseHandle(token);
+            return false;
+        }
 
-    Ok(())
-}
+        let mut privileges = PRIVILEGE_SET {
+            PrivilegeCount: 1,
+            Control: 1, // PRIVILEGE_SET_ALL_NECESSARY
+            Privilege: [LUID_AND_ATTRIBUTES {
+                Luid: luid,
+                Attributes: SE_PRIVILEGE_ENABLED,
+            }],
+        };
+        let mut result = BOOL::default();
+        let checked = PrivilegeCheck(token, &mut privileges, &mut result).is_ok();
+        let _ = CloseHandle(token);
 
-#[command]
-async fn get_overlay_scale() -> Result<f64, String> {
-    Ok(load_overlay_scale())
+        checked && result.as_bool()
+    }
 }
 
-#[command]
-async fn hide_main_window(app: AppHandle) -> Result<(), String> {
-    info!("[hide_main_window] Attempting to hide main window");
-    if let Some(window) = app.get_webview_window("main") {
-        window.hide().map_err(|e| {
-            error!("[hide_main_window] Failed to hide window: {}", e);
-            e.to_string()
-        })?;
-        info!("[hide_main_window] Window hidden, emitting event");
-        let _ = app.emit(
-            "main-window-visibility-changed",
-            json!({ "visible": false }),
-        );
-        info!("[hide_main_window] Event emitted successfully");
-    } else {
-        warn!("[hide_main_window] Main window not found");
+/// Checks whether the X11 socket for `$DISPLAY` exists
+#[cfg(target_os = "linux")]
+fn has_x11_socket() -> bool {
+    let display = match std::env::var("DISPLAY") {
+        Ok(display) => display,
+        Err(_) => return false,
+    };
+
+    // Remote displays (e.g. "localhost:10.0") are reached over TCP, not a local socket
+    let (host, rest) = display.split_once(':').unwrap_or(("", display.as_str()));
+    if !host.is_empty() && !host.starts_with('/') {
+        return true;
     }
-    Ok(())
+
+    let number = rest.split('.').next().unwrap_or("0");
+    Path::new(&format!("/tmp/.X11-unix/X{}", number)).exists()
 }
 
-#[command]
-async fn show_main_window(app: AppHandle) -> Result<(), String> {
-    if let Some(window) = app.get_webview_window("main") {
-        window.show().map_err(|e| e.to_string())?;
-        window.set_focus().map_err(|e| e.to_string())?;
-        let _ = app.emit("main-window-visibility-changed", json!({ "visible": true }));
+/// Collects the status of every permission the app relies on for this platform
+fn collect_platform_permissions() -> Vec<PermissionStatus> {
+    #[allow(unused_mut)]
+    let mut statuses = Vec::new();
+
+    #[cfg(target_os = "macos")]
+    {
+        let granted = unsafe { CGPreflightScreenCaptureAccess() };
+        statuses.push(PermissionStatus {
+            permission: "screen_recording".to_string(),
+            granted,
+            message: SCREEN_RECORDING_PERMISSION_MESSAGE.to_string(),
+        });
     }
-    Ok(())
+
+    #[cfg(target_os = "windows")]
+    {
+        statuses.push(PermissionStatus {
+            permission: "debug_privilege".to_string(),
+            granted: has_debug_privilege(),
+            message: "SeDebugPrivilege is not enabled. Some windows may not be detected; try running Oto Desktop as administrator.".to_string(),
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        statuses.push(PermissionStatus {
+            permission: "xauthority".to_string(),
+            granted: std::env::var_os("XAUTHORITY").is_some(),
+            message:
+                "XAUTHORITY is not set. Screenshots and overlay positioning may fail under X11."
+                    .to_string(),
+        });
+        statuses.push(PermissionStatus {
+            permission: "x11_socket".to_string(),
+            granted: has_x11_socket(),
+            message:
+                "No X11 display socket found. Check that $DISPLAY points to a running X server."
+                    .to_string(),
+        });
+    }
+
+    statuses
 }
 
-#[command]
-async fn toggle_main_window(app: AppHandle) -> Result<bool, String> {
-    if let Some(window) = app.get_webview_window("main") {
-        let is_visible = window.is_visible().map_err(|e| e.to_string())?;
-        if is_visible {
-            window.hide().map_err(|e| e.to_string())?;
-            let _ = app.emit(
-                "main-window-visibility-changed",
-                json!({ "visible": false }),
+/// Checks platform permissions at startup and notifies the main window about missing ones
+async fn check_platform_permissions(app: AppHandle) {
+    let statuses = collect_platform_permissions();
+
+    for status in statuses.iter().filter(|s| !s.granted) {
+        warn!(
+            "[permissions] {} not granted: {}",
+            status.permission, status.message
+        );
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.emit(
+                "permission-required",
+                json!({ "permission": status.permission, "message": status.message }),
             );
-            Ok(false)
-        } else {
-            window.show().map_err(|e| e.to_string())?;
-            window.set_focus().map_err(|e| e.to_string())?;
-            let _ = app.emit("main-window-visibility-changed", json!({ "visible": true }));
-            Ok(true)
         }
-    } else {
-        Ok(false)
     }
 }
 
 #[command]
-async fn is_main_window_visible(app: AppHandle) -> Result<bool, String> {
-    if let Some(window) = app.get_webview_window("main") {
-        window.is_visible().map_err(|e| e.to_string())
-    } else {
-        Ok(false)
+async fn get_required_permissions() -> Result<Vec<PermissionStatus>, String> {
+    Ok(collect_platform_permissions())
+}
+
+/// Display server name for the current session
+fn platform_display_server() -> String {
+    #[cfg(target_os = "linux")]
+    {
+        match detect_display_server() {
+            DisplayServer::Wayland => "wayland",
+            DisplayServer::X11 => "x11",
+            DisplayServer::Unknown => "unknown",
+        }
+        .to_string()
+    }
+    #[cfg(target_os = "macos")]
+    {
+        "quartz".to_string()
+    }
+    #[cfg(target_os = "windows")]
+    {
+        "windows".to_string()
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        "unknown".to_string()
     }
 }
 
-// ============ Device Listening ============
+/// Whether a default audio output device is available
+#[cfg(feature = "audio-detect")]
+fn has_audio_output() -> bool {
+    use cpal::traits::HostTrait;
 
-#[derive(Debug, Clone, Serialize)]
-pub struct DeviceEvent {
-    kind: String,
-    value: Value,
+    cpal::default_host().default_output_device().is_some()
 }
 
-static IS_LISTENING: AtomicBool = AtomicBool::new(false);
-
-#[command]
-async fn start_device_listening(app: AppHandle) -> Result<(), String> {
-    if IS_LISTENING.load(Ordering::SeqCst) {
-        return Ok(());
+/// Without the `audio-detect` feature, Linux reads the ALSA card list and other
+/// platforms assume an output device is present
+#[cfg(not(feature = "audio-detect"))]
+fn has_audio_output() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/asound/cards")
+            .map(|cards| !cards.trim().is_empty() && !cards.contains("no soundcards"))
+            .unwrap_or(false)
     }
-    IS_LISTENING.store(true, Ordering::SeqCst);
+    #[cfg(not(target_os = "linux"))]
+    {
+        true
+    }
+}
 
-    std::thread::spawn(move || {
-        let callback = move |event: Event| {
-            // Mouse tracking for head movement
-            if let EventType::MouseMove { x, y } = event.event_type {
-                let device_event = DeviceEvent {
-                    kind: "MouseMove".to_string(),
-                    value: json!({ "x": x, "y": y }),
-                };
-                let _ = app.emit("device-changed", device_event);
-            }
-        };
-        listen(callback).ok();
-    });
+/// Returns OS, display and capability details so the frontend can gate platform features
+#[command]
+async fn get_platform_info() -> Result<PlatformInfo, String> {
+    #[cfg(target_os = "linux")]
+    let is_wsl = is_wsl();
+    #[cfg(not(target_os = "linux"))]
+    let is_wsl = false;
 
-    Ok(())
+    #[cfg(target_os = "macos")]
+    let has_screen_capture = unsafe { CGPreflightScreenCaptureAccess() };
+    #[cfg(not(target_os = "macos"))]
+    let has_screen_capture = true;
+
+    Ok(PlatformInfo {
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        is_wsl,
+        display_server: platform_display_server(),
+        has_screen_capture,
+        has_audio: has_audio_output(),
+    })
 }
 
-// ============ Screenshot ============
+#[cfg(target_os = "linux")]
+fn is_wsl() -> bool {
+    std::fs::read_to_string("/proc/version")
+        .map(|v| v.to_lowercase().contains("microsoft") || v.to_lowercase().contains("wsl"))
+        .unwrap_or(false)
+}
 
-// Native macOS screen capture permission APIs
-#[cfg(target_os = "macos")]
-#[link(name = "CoreGraphics", kind = "framework")]
-extern "C" {
-    fn CGPreflightScreenCaptureAccess() -> bool;
-    fn CGRequestScreenCaptureAccess() -> bool;
+/// Returns true if `tool` is an executable on `$PATH`
+#[cfg(target_os = "linux")]
+fn tool_in_path(tool: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(tool).is_file()))
 }
 
-#[command]
-async fn check_screen_permission() -> Result<bool, String> {
+/// Name of the tool `capture_screen` will use, without taking a screenshot
+fn detect_screenshot_backend() -> &'static str {
     #[cfg(target_os = "macos")]
     {
-        unsafe {
-            // First check if we already have permission
-            if CGPreflightScreenCaptureAccess() {
-                return Ok(true);
-            }
-            // If not, request permission (triggers system dialog)
-            Ok(CGRequestScreenCaptureAccess())
-        }
+        "screencapture"
     }
 
-    #[cfg(not(target_os = "macos"))]
+    #[cfg(target_os = "windows")]
     {
-        Ok(true)
+        "gdi"
     }
-}
 
-#[command]
-async fn open_screen_recording_settings() -> Result<(), String> {
-    #[cfg(target_os = "macos")]
+    #[cfg(target_os = "linux")]
     {
-        std::process::Command::new("open")
-            .arg("x-apple.systempreferences:com.apple.preference.security?Privacy_ScreenCapture")
-            .spawn()
-            .map_err(|e| format!("Failed to open settings: {}", e))?;
+        if is_wsl() {
+            "powershell"
+        } else if detect_wayland() {
+            if tool_in_path("grim") {
+                "grim"
+            } else {
+                "gnome-screenshot"
+            }
+        } else if tool_in_path("gnome-screenshot") || !tool_in_path("scrot") {
+            "gnome-screenshot"
+        } else {
+            "scrot"
+        }
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        "none"
     }
-    Ok(())
 }
 
 #[command]
-async fn take_screenshot(app: AppHandle) -> Result<String, String> {
-    use std::time::{SystemTime, UNIX_EPOCH};
+async fn get_screenshot_backend() -> Result<String, String> {
+    Ok(detect_screenshot_backend().to_string())
+}
 
-    // Generate filename with timestamp hash
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map_err(|e| format!("Time error: {}", e))?
-        .as_millis();
-    let filename = format!("{:x}.png", timestamp);
+/// Captures a Wayland session with grim, falling back to headless gnome-screenshot (GNOME 43+).
+/// Returns the backend that was used.
+#[cfg(target_os = "linux")]
+fn capture_wayland_screen(
+    app: &AppHandle,
+    filepath: &Path,
+    region: Option<&ScreenRegion>,
+) -> Result<&'static str, String> {
+    let mut grim = std::process::Command::new("grim");
+    if let Some(region) = region {
+        grim.arg("-g").arg(format!(
+            "{},{} {}x{}",
+            region.x, region.y, region.width, region.height
+        ));
+    } else if let Some(output) = app
+        .get_webview_window("overlay")
+        .and_then(|window| window.current_monitor().ok().flatten())
+        .and_then(|monitor| monitor.name().cloned())
+    {
+        // Only capture the output the overlay is on
+        grim.arg("-o").arg(output);
+    }
 
-    // Get screenshots directory and create if needed
-    let screenshots_dir = get_screenshots_dir()?;
-    std::fs::create_dir_all(&screenshots_dir)
-        .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+    match grim.arg(filepath).output() {
+        Ok(output) if output.status.success() => return Ok("grim"),
+        Ok(output) => warn!(
+            "[screenshot] grim failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => info!(
+            "[screenshot] grim unavailable ({}), trying gnome-screenshot",
+            e
+        ),
+    }
 
-    let filepath = screenshots_dir.join(&filename);
+    let output = std::process::Command::new("gnome-screenshot")
+        .arg("--headless")
+        .arg("-f")
+        .arg(filepath)
+        .output()
+        .map_err(|e| {
+            format!(
+                "Failed to capture screenshot (install grim or gnome-screenshot): {}",
+                e
+            )
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "gnome-screenshot failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    if let Some(region) = region {
+        crop_screenshot(filepath, region)?;
+    }
+    Ok("gnome-screenshot")
+}
 
+/// Captures the screen the overlay is on (or just `region`) into `filepath` using the native
+/// platform tool. Returns the name of the backend that was used.
+#[allow(unused_variables, unreachable_code)]
+fn capture_screen(
+    app: &AppHandle,
+    filepath: &Path,
+    region: Option<&ScreenRegion>,
+) -> Result<&'static str, String> {
     // Use native screencapture on macOS (fast, captures all windows like cmd+shift+4)
     #[cfg(target_os = "macos")]
     {
@@ -2077,10 +9634,17 @@ async fn take_screenshot(app: AppHandle) -> Result<String, String> {
             1
         };
 
-        let output = std::process::Command::new("screencapture")
-            .arg("-x") // no sound
-            .arg("-D")
-            .arg(display_index.to_string())
+        let mut command = std::process::Command::new("screencapture");
+        command.arg("-x"); // no sound
+        if let Some(region) = region {
+            command.arg(format!(
+                "-R{},{},{},{}",
+                region.x, region.y, region.width, region.height
+            ));
+        } else {
+            command.arg("-D").arg(display_index.to_string());
+        }
+        let output = command
             .arg(&filepath)
             .output()
             .map_err(|e| format!("Failed to run screencapture: {}", e))?;
@@ -2088,10 +9652,11 @@ async fn take_screenshot(app: AppHandle) -> Result<String, String> {
         if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
             if stderr.contains("could not create image") {
-                return Err("Screen recording permission required. Go to System Settings > Privacy & Security > Screen Recording and enable Oto Desktop.".to_string());
+                return Err(SCREEN_RECORDING_PERMISSION_MESSAGE.to_string());
             }
             return Err(format!("screencapture failed: {}", stderr));
         }
+        return Ok("screencapture");
     }
 
     #[cfg(target_os = "windows")]
@@ -2099,8 +9664,15 @@ async fn take_screenshot(app: AppHandle) -> Result<String, String> {
         use windows::Win32::Graphics::Gdi::*;
         use windows::Win32::UI::WindowsAndMessaging::*;
 
-        // Get monitor bounds from overlay window
-        let (left, top, width, height) = if let Some(window) = app.get_webview_window("overlay") {
+        // Get capture bounds from the requested region, or the overlay's monitor
+        let (left, top, width, height) = if let Some(region) = region {
+            (
+                region.x,
+                region.y,
+                region.width as i32,
+                region.height as i32,
+            )
+        } else if let Some(window) = app.get_webview_window("overlay") {
             if let Ok(Some(monitor)) = window.current_monitor() {
                 let pos = monitor.position();
                 let size = monitor.size();
@@ -2201,16 +9773,12 @@ async fn take_screenshot(app: AppHandle) -> Result<String, String> {
             img.save(&filepath)
                 .map_err(|e| format!("Failed to save screenshot: {}", e))?;
         }
+        return Ok("gdi");
     }
 
     #[cfg(target_os = "linux")]
     {
-        // Check if running in WSL
-        let is_wsl = std::fs::read_to_string("/proc/version")
-            .map(|v| v.to_lowercase().contains("microsoft") || v.to_lowercase().contains("wsl"))
-            .unwrap_or(false);
-
-        if is_wsl {
+        if is_wsl() {
             // In WSL, use PowerShell to capture Windows desktop
             // Save to Windows temp first, then copy to WSL location
             let temp_filename = format!("oto_screenshot_{}.png", std::process::id());
@@ -2250,47 +9818,391 @@ async fn take_screenshot(app: AppHandle) -> Result<String, String> {
                 )
             })?;
 
-            // Clean up temp file
-            let _ = std::fs::remove_file(&temp_path);
-        } else {
-            // Native Linux: use gnome-screenshot or scrot
-            let output = std::process::Command::new("gnome-screenshot")
-                .arg("-f")
-                .arg(&filepath)
-                .output();
+            // Clean up temp file
+            let _ = std::fs::remove_file(&temp_path);
+
+            if let Some(region) = region {
+                crop_screenshot(filepath, region)?;
+            }
+            return Ok("powershell");
+        } else if detect_wayland() {
+            return capture_wayland_screen(app, filepath, region);
+        } else if let Some(region) = region {
+            // scrot grabs a rectangle directly; gnome-screenshot's --area is interactive only,
+            // so fall back to a full gnome-screenshot capture and crop it
+            let geometry = format!(
+                "{},{},{},{}",
+                region.x, region.y, region.width, region.height
+            );
+            let output = std::process::Command::new("scrot")
+                .arg("-a")
+                .arg(&geometry)
+                .arg(&filepath)
+                .output();
+
+            if output.is_err() || !output.as_ref().unwrap().status.success() {
+                std::process::Command::new("gnome-screenshot")
+                    .arg("-f")
+                    .arg(&filepath)
+                    .output()
+                    .map_err(|e| {
+                        format!(
+                            "Failed to capture screenshot (install gnome-screenshot or scrot): {}",
+                            e
+                        )
+                    })?;
+                crop_screenshot(filepath, region)?;
+                return Ok("gnome-screenshot");
+            }
+            return Ok("scrot");
+        } else {
+            // Native Linux: use gnome-screenshot or scrot
+            let output = std::process::Command::new("gnome-screenshot")
+                .arg("-f")
+                .arg(&filepath)
+                .output();
+
+            if output.is_err() || !output.as_ref().unwrap().status.success() {
+                std::process::Command::new("scrot")
+                    .arg(&filepath)
+                    .output()
+                    .map_err(|e| {
+                        format!(
+                            "Failed to capture screenshot (install gnome-screenshot or scrot): {}",
+                            e
+                        )
+                    })?;
+                return Ok("scrot");
+            }
+            return Ok("gnome-screenshot");
+        }
+    }
+
+    Ok("none")
+}
+
+/// Crops a full-screen capture at `filepath` down to `region`
+#[cfg(target_os = "linux")]
+fn crop_screenshot(filepath: &Path, region: &ScreenRegion) -> Result<(), String> {
+    let img =
+        image::open(filepath).map_err(|e| format!("Failed to open screenshot for crop: {}", e))?;
+    let cropped = img.crop_imm(
+        region.x.max(0) as u32,
+        region.y.max(0) as u32,
+        region.width,
+        region.height,
+    );
+    cropped
+        .save(filepath)
+        .map_err(|e| format!("Failed to save cropped screenshot: {}", e))
+}
+
+/// Hides the overlay before a capture; returns true if it was visible and got hidden
+async fn hide_overlay_for_capture(app: &AppHandle) -> bool {
+    let is_visible = *app.state::<AppState>().overlay_visible.lock().unwrap();
+    if !is_visible {
+        return false;
+    }
+    match app.get_webview_window("overlay") {
+        Some(window) if window.hide().is_ok() => {
+            // Give the compositor a moment to remove the window from the screen
+            tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Captures the screen, retrying while macOS screen recording permission is missing.
+/// If it never gets granted the frontend is told so it can prompt the user.
+async fn capture_screen_with_permission_retry(
+    app: &AppHandle,
+    filepath: &Path,
+    region: Option<&ScreenRegion>,
+) -> Result<&'static str, String> {
+    #[cfg(target_os = "macos")]
+    {
+        for attempt in 1..=SCREENSHOT_PERMISSION_ATTEMPTS {
+            if unsafe { CGPreflightScreenCaptureAccess() } {
+                match capture_screen(app, filepath, region) {
+                    Ok(backend) => return Ok(backend),
+                    Err(e) if e == SCREEN_RECORDING_PERMISSION_MESSAGE => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            if attempt < SCREENSHOT_PERMISSION_ATTEMPTS {
+                tokio::time::sleep(PERMISSION_POLL_INTERVAL).await;
+            }
+        }
+
+        warn!("[screenshot] Screen recording permission not granted");
+        emit_event(
+            app,
+            "screenshot-permission-required",
+            json!({ "message": SCREEN_RECORDING_PERMISSION_MESSAGE }),
+        );
+        Err(SCREEN_RECORDING_PERMISSION_MESSAGE.to_string())
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        capture_screen(app, filepath, region)
+    }
+}
+
+#[command]
+async fn take_screenshot(app: AppHandle, region: Option<ScreenRegion>) -> Result<String, String> {
+    capture_screenshot_file(app.clone(), region)
+        .await
+        .map(|(path, _, _)| path)
+        .inspect_err(|e| record_error!(app, "take_screenshot", e, "error"))
+}
+
+/// Captures the full screen without the overlay: the window is hidden for the capture when
+/// the exclude_overlay setting allows it, and painted black otherwise
+async fn take_screenshot_without_overlay(app: &AppHandle) -> Result<String, String> {
+    // Measured before the capture, which may hide the window
+    let overlay = overlay_desktop_bounds(app);
+    let (filepath, overlay_hidden, backend) = capture_screenshot_file(app.clone(), None)
+        .await
+        .inspect_err(|e| record_error!(app, "take_screenshot", e, "error"))?;
+    if let Some(overlay) = overlay.filter(|_| !overlay_hidden) {
+        // The capture may cover one monitor or the whole desktop depending on the backend
+        let origin = capture_origin(app, backend);
+        let exclude = ScreenRegion {
+            x: overlay.x - origin.x,
+            y: overlay.y - origin.y,
+            ..overlay
+        };
+        blank_image_region(Path::new(&filepath), &exclude)?;
+    }
+    Ok(filepath)
+}
+
+/// Saves a screenshot, returning its path, whether the overlay was hidden for it and the
+/// backend that captured it
+async fn capture_screenshot_file(
+    app: AppHandle,
+    region: Option<ScreenRegion>,
+) -> Result<(String, bool, &'static str), String> {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    // Generate filename with timestamp hash
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| format!("Time error: {}", e))?
+        .as_millis();
+    let filename = format!("{:x}.png", timestamp);
+
+    // Get screenshots directory and create if needed
+    let screenshots_dir = get_screenshots_dir()?;
+    std::fs::create_dir_all(&screenshots_dir)
+        .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+
+    let filepath = screenshots_dir.join(&filename);
+
+    // Keep the character out of her own screenshots
+    let settings = load_screenshot_settings().unwrap_or_default();
+    let overlay_hidden = settings.exclude_overlay && hide_overlay_for_capture(&app).await;
+
+    let result = capture_screen_with_permission_retry(&app, &filepath, region.as_ref()).await;
+
+    if overlay_hidden {
+        if let Some(window) = app.get_webview_window("overlay") {
+            let _ = window.show();
+        }
+    }
+    let backend = result?;
+
+    info!("[screenshot] Saved to: {:?} (via {})", filepath, backend);
+    emit_event(&app, "screenshot-backend", json!({ "backend": backend }));
+
+    if settings.auto_cleanup {
+        match cleanup_screenshots_dir(settings.max_count) {
+            Ok(0) => {}
+            Ok(deleted) => info!(
+                "[screenshot] Auto-cleanup removed {} old screenshots",
+                deleted
+            ),
+            Err(e) => warn!("[screenshot] Auto-cleanup failed: {}", e),
+        }
+    }
+
+    Ok((
+        filepath.to_string_lossy().to_string(),
+        overlay_hidden,
+        backend,
+    ))
+}
+
+#[command]
+async fn take_screenshot_region(app: AppHandle, region: ScreenRegion) -> Result<String, String> {
+    take_screenshot(app, Some(region)).await
+}
+
+/// Captures the full screen with `exclude` painted black. The region is in pixels of the
+/// captured image: relative to the top-left of the captured monitor on macOS, Windows and
+/// with grim, and of the whole desktop with the other Linux tools.
+#[command]
+async fn take_screenshot_excluding_region(
+    app: AppHandle,
+    exclude: ScreenRegion,
+) -> Result<String, String> {
+    let filepath = take_screenshot(app, None).await?;
+    blank_image_region(Path::new(&filepath), &exclude)?;
+    Ok(filepath)
+}
+
+/// Fills a rectangle of a saved image with black, clipped to the image bounds
+fn blank_image_region(path: &Path, region: &ScreenRegion) -> Result<(), String> {
+    let mut img = image::open(path)
+        .map_err(|e| format!("Failed to open screenshot: {}", e))?
+        .to_rgba8();
+
+    let (img_width, img_height) = img.dimensions();
+    let x_start = region.x.max(0) as u32;
+    let y_start = region.y.max(0) as u32;
+    let x_end = (region.x as i64 + region.width as i64).clamp(0, img_width as i64) as u32;
+    let y_end = (region.y as i64 + region.height as i64).clamp(0, img_height as i64) as u32;
+
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            img.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+        }
+    }
+
+    img.save(path)
+        .map_err(|e| format!("Failed to save screenshot: {}", e))
+}
+
+/// The visible overlay's bounds in desktop coordinates
+fn overlay_desktop_bounds(app: &AppHandle) -> Option<ScreenRegion> {
+    if !*app.state::<AppState>().overlay_visible.lock().unwrap() {
+        return None;
+    }
+    let overlay = app.get_webview_window("overlay")?;
+    let position = overlay.outer_position().ok()?;
+    let size = overlay.outer_size().ok()?;
+    Some(ScreenRegion {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Desktop position of the top-left pixel of a full-screen capture taken by `backend`.
+/// macOS, Windows and grim (run with `-o` for the overlay's output) capture the overlay's
+/// monitor; the other Linux tools capture the whole desktop, which starts at the
+/// top-left-most monitor.
+fn capture_origin(app: &AppHandle, backend: &str) -> tauri::PhysicalPosition<i32> {
+    let Some(overlay) = app.get_webview_window("overlay") else {
+        return Default::default();
+    };
+    let monitor = overlay.current_monitor().ok().flatten();
+    let single_output = backend == "grim" && monitor.as_ref().is_some_and(|m| m.name().is_some());
+
+    if cfg!(target_os = "linux") && !single_output {
+        overlay.available_monitors().ok().and_then(|monitors| {
+            monitors
+                .iter()
+                .map(|monitor| *monitor.position())
+                .reduce(|a, b| tauri::PhysicalPosition::new(a.x.min(b.x), a.y.min(b.y)))
+        })
+    } else {
+        monitor.map(|monitor| *monitor.position())
+    }
+    .unwrap_or_default()
+}
+
+/// Lists the PNG screenshots with their modification times
+fn list_screenshots() -> Result<Vec<(PathBuf, std::time::SystemTime)>, String> {
+    let screenshots_dir = get_screenshots_dir()?;
+    if !screenshots_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(std::fs::read_dir(&screenshots_dir)
+        .map_err(|e| format!("Failed to read screenshots directory: {}", e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "png"))
+        .filter_map(|p| {
+            let modified = std::fs::metadata(&p).and_then(|m| m.modified()).ok()?;
+            Some((p, modified))
+        })
+        .collect())
+}
+
+/// Deletes the oldest screenshots until at most `max_count` remain; returns how many were deleted
+fn cleanup_screenshots_dir(max_count: u32) -> Result<u32, String> {
+    let mut screenshots = list_screenshots()?;
+    if screenshots.len() <= max_count as usize {
+        return Ok(0);
+    }
+
+    // Oldest first
+    screenshots.sort_by_key(|(_, modified)| *modified);
+    let excess = screenshots.len() - max_count as usize;
 
-            if output.is_err() || !output.as_ref().unwrap().status.success() {
-                std::process::Command::new("scrot")
-                    .arg(&filepath)
-                    .output()
-                    .map_err(|e| {
-                        format!(
-                            "Failed to capture screenshot (install gnome-screenshot or scrot): {}",
-                            e
-                        )
-                    })?;
-            }
+    let mut deleted = 0;
+    for (path, _) in screenshots.iter().take(excess) {
+        match std::fs::remove_file(path) {
+            Ok(()) => deleted += 1,
+            Err(e) => warn!("[screenshot] Failed to delete {:?}: {}", path, e),
         }
     }
+    Ok(deleted)
+}
 
-    println!("[screenshot] Saved to: {:?}", filepath);
+#[command]
+async fn cleanup_screenshots(max_count: u32) -> Result<u32, String> {
+    cleanup_screenshots_dir(max_count)
+}
 
-    Ok(filepath.to_string_lossy().to_string())
+#[command]
+async fn get_screenshot_count() -> Result<u32, String> {
+    Ok(list_screenshots()?.len() as u32)
 }
 
 #[command]
-async fn open_screenshots_folder() -> Result<(), String> {
-    let screenshots_dir = get_screenshots_dir()?;
+async fn save_screenshot_cleanup_settings(
+    max_count: u32,
+    auto_cleanup: bool,
+) -> Result<(), String> {
+    let mut settings = load_screenshot_settings().unwrap_or_default();
+    settings.max_count = max_count;
+    settings.auto_cleanup = auto_cleanup;
+    save_screenshot_settings(&settings)
+}
 
-    // Create directory if it doesn't exist
-    std::fs::create_dir_all(&screenshots_dir)
-        .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+#[command]
+async fn get_screenshot_cleanup_settings() -> Result<Value, String> {
+    let settings = load_screenshot_settings()?;
+    Ok(json!({
+        "max_count": settings.max_count,
+        "auto_cleanup": settings.auto_cleanup
+    }))
+}
 
-    // Open in file manager
+#[command]
+async fn set_screenshot_exclude_overlay(exclude_overlay: bool) -> Result<(), String> {
+    let mut settings = load_screenshot_settings().unwrap_or_default();
+    settings.exclude_overlay = exclude_overlay;
+    save_screenshot_settings(&settings)
+}
+
+#[command]
+async fn get_screenshot_settings() -> Result<ScreenshotSettings, String> {
+    load_screenshot_settings()
+}
+
+/// Opens a directory in the native file manager
+fn open_in_file_manager(dir: &Path) -> Result<(), String> {
     #[cfg(target_os = "macos")]
     {
         std::process::Command::new("open")
-            .arg(&screenshots_dir)
+            .arg(dir)
             .spawn()
             .map_err(|e| format!("Failed to open folder: {}", e))?;
     }
@@ -2298,7 +10210,7 @@ async fn open_screenshots_folder() -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         std::process::Command::new("explorer")
-            .arg(&screenshots_dir)
+            .arg(dir)
             .spawn()
             .map_err(|e| format!("Failed to open folder: {}", e))?;
     }
@@ -2306,7 +10218,7 @@ async fn open_screenshots_folder() -> Result<(), String> {
     #[cfg(target_os = "linux")]
     {
         std::process::Command::new("xdg-open")
-            .arg(&screenshots_dir)
+            .arg(dir)
             .spawn()
             .map_err(|e| format!("Failed to open folder: {}", e))?;
     }
@@ -2314,9 +10226,302 @@ async fn open_screenshots_folder() -> Result<(), String> {
     Ok(())
 }
 
+#[command]
+async fn open_screenshots_folder() -> Result<(), String> {
+    let screenshots_dir = get_screenshots_dir()?;
+
+    // Create directory if it doesn't exist
+    std::fs::create_dir_all(&screenshots_dir)
+        .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+
+    // Open in file manager
+    open_in_file_manager(&screenshots_dir)
+}
+
+#[command]
+async fn export_chat_history(
+    state: tauri::State<'_, AppState>,
+    format: String,
+    path: Option<String>,
+) -> Result<String, String> {
+    let format = export::ExportFormat::parse(&format)?;
+
+    let export_path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let date = chrono::Local::now().format("%Y-%m-%d");
+            get_exports_dir()?.join(format!("oto-chat-export-{}.{}", date, format.extension()))
+        }
+    };
+
+    let messages = get_chat_history_internal(&state.db_pool, &active_session_id(&state), i64::MAX)?;
+    export::write_export(&messages, format, &export_path)?;
+    info!(
+        "[export] Exported {} messages to {:?}",
+        messages.len(),
+        export_path
+    );
+
+    // Show the export in the file manager
+    if let Some(parent) = export_path.parent() {
+        if let Err(e) = open_in_file_manager(parent) {
+            warn!("[export] {}", e);
+        }
+    }
+
+    Ok(export_path.to_string_lossy().to_string())
+}
+
+/// Queues one imported message for `store_chat_messages`, skipping empty ones
+fn queue_imported_message(
+    pending: &mut Vec<(String, String, String, u8)>,
+    timestamp: Option<&str>,
+    role: &str,
+    content: &str,
+    context_level: u8,
+    result: &mut ImportResult,
+) {
+    let content = content.trim();
+    if content.is_empty() {
+        result.skipped_count += 1;
+        return;
+    }
+    let timestamp = timestamp
+        .map(str::to_string)
+        .unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+    pending.push((
+        timestamp,
+        role.to_string(),
+        content.to_string(),
+        context_level,
+    ));
+}
+
+/// Imports a plain-text conversation where each message starts with a prefix such as
+/// "User:"; lines without a prefix continue the previous message
+#[command]
+async fn import_chat_from_text(
+    state: tauri::State<'_, AppState>,
+    path: String,
+    role_prefix_map: HashMap<String, String>,
+) -> Result<ImportResult, String> {
+    if role_prefix_map.is_empty() {
+        return Err("No role prefixes given".to_string());
+    }
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read chat log: {}", e))?;
+
+    // Longest prefixes first so "User (2):" wins over "User"
+    let mut prefixes: Vec<(&String, &String)> = role_prefix_map.iter().collect();
+    prefixes.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+    let mut result = ImportResult::default();
+    let mut pending = Vec::new();
+    let mut current: Option<(&str, String)> = None;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        match prefixes
+            .iter()
+            .find(|(prefix, _)| trimmed.starts_with(prefix.as_str()))
+        {
+            Some((prefix, role)) => {
+                if let Some((role, body)) = current.take() {
+                    queue_imported_message(&mut pending, None, role, &body, 0, &mut result);
+                }
+                current = Some((role.as_str(), trimmed[prefix.len()..].trim().to_string()));
+            }
+            None => match current.as_mut() {
+                Some((_, body)) => {
+                    body.push('\n');
+                    body.push_str(line);
+                }
+                // Text before the first prefix has no role to import it under
+                None if !line.trim().is_empty() => result.skipped_count += 1,
+                None => {}
+            },
+        }
+    }
+    if let Some((role, body)) = current {
+        queue_imported_message(&mut pending, None, role, &body, 0, &mut result);
+    }
+    result.imported_count =
+        store_chat_messages(&state.db_pool, &active_session_id(&state), &pending)?;
+
+    info!(
+        "[import] Imported {} messages from {} ({} skipped)",
+        result.imported_count, path, result.skipped_count
+    );
+    Ok(result)
+}
+
+/// Imports a JSON array of chat messages, e.g. one written by `export_chat_history`
+#[command]
+async fn import_chat_from_json(
+    state: tauri::State<'_, AppState>,
+    path: String,
+) -> Result<ImportResult, String> {
+    let content =
+        std::fs::read_to_string(&path).map_err(|e| format!("Failed to read chat file: {}", e))?;
+    let entries: Vec<Value> =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse chat file: {}", e))?;
+
+    let mut result = ImportResult::default();
+    let mut pending = Vec::new();
+    for (index, entry) in entries.into_iter().enumerate() {
+        match serde_json::from_value::<ChatMessage>(entry) {
+            Ok(msg) => queue_imported_message(
+                &mut pending,
+                Some(&msg.timestamp),
+                &msg.role,
+                &msg.content,
+                msg.context_level,
+                &mut result,
+            ),
+            Err(e) => {
+                result.skipped_count += 1;
+                result.errors.push(format!("Message {}: {}", index, e));
+            }
+        }
+    }
+    result.imported_count =
+        store_chat_messages(&state.db_pool, &active_session_id(&state), &pending)?;
+
+    info!(
+        "[import] Imported {} messages from {} ({} skipped)",
+        result.imported_count, path, result.skipped_count
+    );
+    Ok(result)
+}
+
+// ============ Settings Bundle ============
+
+/// Reads a config file if it exists
+fn read_optional_config(path: &Path) -> Result<Option<String>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    std::fs::read_to_string(path)
+        .map(Some)
+        .map_err(|e| format!("Failed to read {:?}: {}", path, e))
+}
+
+#[command]
+async fn export_settings() -> Result<String, String> {
+    use settings_bundle::{AppSettingsBundle, BundledKeyProfile, BundledPrompts};
+
+    let machine_id = settings_bundle::machine_id();
+    let profiles = load_key_profiles()?;
+    let api_key =
+        active_key_profile()?.map(|p| settings_bundle::obfuscate(&p.api_key, &machine_id));
+
+    let bundle = AppSettingsBundle {
+        schema_version: settings_bundle::SETTINGS_BUNDLE_SCHEMA_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        api_key,
+        key_profiles: profiles
+            .iter()
+            .map(|p| BundledKeyProfile::from_profile(p, &machine_id))
+            .collect(),
+        active_profile: read_optional_config(&get_active_profile_path()?)?
+            .map(|name| name.trim().to_string()),
+        prompts: BundledPrompts {
+            system: read_optional_config(&get_system_prompt_path()?)?,
+            character: read_optional_config(&get_character_prompt_path()?)?,
+            deep_research: read_optional_config(&get_deep_research_prompt_path()?)?,
+            dialogue: read_optional_config(&get_dialogue_prompt_path()?)?,
+        },
+        overlay_geometry: load_overlay_geometry_from_file(),
+        hitbox: read_hitbox_file()?,
+        shortcuts: Some(load_shortcuts_config()?),
+    };
+
+    let exports_dir = get_exports_dir()?;
+    std::fs::create_dir_all(&exports_dir)
+        .map_err(|e| format!("Failed to create exports directory: {}", e))?;
+    let export_path = exports_dir.join(settings_bundle::SETTINGS_BUNDLE_FILE_NAME);
+
+    let content = serde_json::to_string_pretty(&bundle)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    std::fs::write(&export_path, content)
+        .map_err(|e| format!("Failed to write settings export: {}", e))?;
+
+    info!("[settings] Exported settings to {:?}", export_path);
+    Ok(export_path.to_string_lossy().to_string())
+}
+
+#[command]
+async fn import_settings(path: String) -> Result<(), String> {
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read settings file: {}", e))?;
+    let bundle: settings_bundle::AppSettingsBundle =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse settings: {}", e))?;
+
+    if bundle.schema_version > settings_bundle::SETTINGS_BUNDLE_SCHEMA_VERSION {
+        return Err(format!(
+            "Unsupported settings schema version: {} (expected {} or lower)",
+            bundle.schema_version,
+            settings_bundle::SETTINGS_BUNDLE_SCHEMA_VERSION
+        ));
+    }
+
+    let machine_id = settings_bundle::machine_id();
+
+    let mut profiles = bundle
+        .key_profiles
+        .into_iter()
+        .map(|p| p.into_profile(&machine_id))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(api_key) = bundle.api_key {
+        let api_key = settings_bundle::deobfuscate(&api_key, &machine_id)?;
+        match profiles.iter_mut().find(|p| p.name == DEFAULT_PROFILE_NAME) {
+            Some(profile) => profile.api_key = api_key,
+            None => profiles.push(KeyProfile {
+                api_key,
+                ..default_key_profile()
+            }),
+        }
+    }
+    if !profiles.is_empty() {
+        save_key_profiles(&profiles)?;
+    }
+    if let Some(name) = bundle.active_profile {
+        set_active_profile(name).await?;
+    }
+
+    if let Some(prompt) = bundle.prompts.system {
+        save_system_prompt(prompt).await?;
+    }
+    if let Some(prompt) = bundle.prompts.character {
+        save_character_prompt(prompt).await?;
+    }
+    if let Some(prompt) = bundle.prompts.deep_research {
+        save_deep_research_prompt(prompt).await?;
+    }
+    if let Some(prompt) = bundle.prompts.dialogue {
+        save_dialogue_prompt(prompt).await?;
+    }
+
+    if let Some(geometry) = bundle.overlay_geometry {
+        save_overlay_geometry_to_file(&geometry)?;
+    }
+    if let Some(hitbox) = bundle.hitbox {
+        write_hitbox_file(hitbox.zones)?;
+    }
+    if let Some(shortcuts) = bundle.shortcuts {
+        save_shortcuts_config(shortcuts).await?;
+    }
+
+    info!("[settings] Imported settings from {}", path);
+    Ok(())
+}
+
 // ============ Main ============
 
 fn main() {
+    paths::init_path_resolver(paths::PathResolver::for_current_os());
+    // Runs before AppState loads any settings from the new location
+    let config_migration = paths::migrate_legacy_config_files();
+
     #[allow(unused_mut)]
     let mut builder = tauri::Builder::default();
 
@@ -2377,9 +10582,39 @@ fn main() {
 
     builder
         .manage(AppState::default())
-        .setup(|app| {
+        .setup(move |app| {
+            // Wrap the log plugin's logger so records also land in the app log viewer
+            let (log_plugin, max_level, logger) = tauri_plugin_log::Builder::new()
+                .target(tauri_plugin_log::Target::new(
+                    tauri_plugin_log::TargetKind::LogDir {
+                        file_name: Some("oto.log".into()),
+                    },
+                ))
+                .level(log::LevelFilter::Info)
+                .split(app.handle())?;
+            log::set_boxed_logger(Box::new(AppLogSink {
+                app: app.handle().clone(),
+                inner: logger,
+            }))?;
+            log::set_max_level(max_level);
+            app.handle().plugin(log_plugin)?;
+
             // Log startup information
             info!("=== OTO Desktop Starting ===");
+            match &config_migration {
+                Ok(0) => {}
+                Ok(moved) => info!(
+                    "[startup] Moved {} settings files to the config directory",
+                    moved
+                ),
+                Err(e) => error!("[startup] Failed to migrate settings files: {}", e),
+            }
+            let t = std::time::Instant::now();
+            let state = app.state::<AppState>();
+            if let Err(e) = db::init_db_pool(&state.db_pool) {
+                error!("[startup] Failed to initialize database: {}", e);
+            }
+            record_startup_metric(&state, "db_init_ms", t.elapsed());
             if let Ok(models_dir) = get_models_dir() {
                 info!("[startup] Models directory: {:?}", models_dir);
                 info!("[startup] Models directory exists: {}", models_dir.exists());
@@ -2391,63 +10626,134 @@ fn main() {
                 );
             }
 
+            // Load the enforced overlay minimum size
+            let display_settings = load_display_settings().unwrap_or_default();
+            *app.state::<AppState>().overlay_min_size.lock().unwrap() = (
+                display_settings.overlay_min_width,
+                display_settings.overlay_min_height,
+            );
+            app.state::<AppState>()
+                .overlay_event_log
+                .store(display_settings.overlay_event_log, Ordering::Relaxed);
+            if display_settings.overlay_event_log {
+                if let Err(e) = prune_overlay_event_logs() {
+                    warn!("[startup] Failed to prune overlay event logs: {}", e);
+                }
+            }
+
+            // Hide the overlay while fullscreen apps are focused
+            app.state::<AppState>()
+                .auto_hide_enabled
+                .store(display_settings.auto_hide_on_fullscreen, Ordering::Relaxed);
+            tauri::async_runtime::spawn(run_fullscreen_auto_hide(app.handle().clone()));
+
+            // Snap the overlay back onto the primary monitor after docking changes
+            app.state::<AppState>().auto_reposition_enabled.store(
+                display_settings.auto_reposition_on_monitor_change,
+                Ordering::Relaxed,
+            );
+            tauri::async_runtime::spawn(run_monitor_change_watch(app.handle().clone()));
+
+            // Keep the overlay above other windows that steal the topmost slot
+            {
+                let state = app.state::<AppState>();
+                state
+                    .always_on_top_refresh_enabled
+                    .store(display_settings.always_on_top_refresh, Ordering::Relaxed);
+                state.always_on_top_refresh_ms.store(
+                    display_settings
+                        .always_on_top_refresh_ms
+                        .max(MIN_ALWAYS_ON_TOP_REFRESH_MS),
+                    Ordering::Relaxed,
+                );
+            }
+            #[cfg(target_os = "windows")]
+            tauri::async_runtime::spawn(run_always_on_top_refresh(app.handle().clone()));
+
+            // Restore the overlay's saved opacity
+            if let Some(overlay) = app.get_webview_window("overlay") {
+                if let Err(e) = apply_overlay_opacity(&overlay, load_overlay_opacity()) {
+                    warn!("[startup] Failed to apply overlay opacity: {}", e);
+                }
+            }
+
+            // Restore the overlay's saved position and size
+            if let (Some(overlay), Some(geometry)) = (
+                app.get_webview_window("overlay"),
+                load_overlay_geometry_from_file(),
+            ) {
+                let _ = overlay.set_size(tauri::Size::Logical(tauri::LogicalSize {
+                    width: geometry.width as f64,
+                    height: geometry.height as f64,
+                }));
+                let _ = overlay.set_position(tauri::Position::Logical(tauri::LogicalPosition {
+                    x: geometry.x as f64,
+                    y: geometry.y as f64,
+                }));
+            }
+
+            // Apply the saved API concurrency limit
+            *app.state::<AppState>().request_semaphore.lock().unwrap() =
+                Arc::new(tokio::sync::Semaphore::new(load_api_concurrency()));
+            app.state::<AppState>()
+                .response_cache_ttl_secs
+                .store(load_response_cache_ttl(), Ordering::Relaxed);
+
+            // Resume the last active conversation session
+            *app.state::<AppState>().active_session_id.lock().unwrap() =
+                load_active_session_id(&app.state::<AppState>());
+
+            // Report storage usage and compact the database in the background
+            let stats_app = app.handle().clone();
+            tauri::async_runtime::spawn_blocking(move || check_database_stats(&stats_app));
+
+            // Load registered event hooks
+            if let Err(e) = refresh_event_hooks(&app.state::<AppState>()) {
+                warn!("[startup] Failed to load event hooks: {}", e);
+            }
+
+            // Check platform permissions before anything depends on them
+            tauri::async_runtime::spawn(check_platform_permissions(app.handle().clone()));
+
             // Create tray menu
             let toggle_item =
                 MenuItem::with_id(app, "toggle", "Show Character", true, None::<&str>)?;
             let settings_item = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+            let bookmarks_item =
+                MenuItem::with_id(app, "bookmarks", "View Bookmarks", true, None::<&str>)?;
             let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
 
             // Store toggle item in state for later text updates
             let state = app.state::<AppState>();
             *state.toggle_menu_item.lock().unwrap() = Some(toggle_item.clone());
+            *state.tray_action_items.lock().unwrap() = vec![
+                (
+                    "settings".to_string(),
+                    "Settings".to_string(),
+                    settings_item.clone(),
+                ),
+                ("quit".to_string(), "Quit".to_string(), quit_item.clone()),
+            ];
+
+            let menu = Menu::with_items(
+                app,
+                &[&toggle_item, &bookmarks_item, &settings_item, &quit_item],
+            )?;
+            *state.tray_menu.lock().unwrap() = Some(menu.clone());
 
-            let menu = Menu::with_items(app, &[&toggle_item, &settings_item, &quit_item])?;
+            let tray_settings = load_tray_settings().unwrap_or_default();
 
             // Create tray icon
-            let _tray = TrayIconBuilder::new()
+            let mut tray_builder = TrayIconBuilder::with_id(TRAY_ID)
                 .icon(app.default_window_icon().unwrap().clone())
-                .menu(&menu)
-                .show_menu_on_left_click(false)
+                .show_menu_on_left_click(false);
+            if tray_settings.right_click_shows_menu {
+                tray_builder = tray_builder.menu(&menu);
+            }
+
+            let _tray = tray_builder
                 .on_menu_event(move |_app, event| {
-                    match event.id.as_ref() {
-                        "toggle" => {
-                            toggle_overlay_sync(_app);
-                        }
-                        "chat_history" => {
-                            // Show overlay and emit event to open history modal
-                            if let Some(window) = _app.get_webview_window("overlay") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                            }
-                            let _ = _app.emit("show-chat-history", ());
-                        }
-                        "settings" => {
-                            // Show main window (for API key entry, etc.)
-                            if let Some(window) = _app.get_webview_window("main") {
-                                let _ = window.show();
-                                let _ = window.set_focus();
-                                let _ = _app.emit(
-                                    "main-window-visibility-changed",
-                                    json!({ "visible": true }),
-                                );
-                            }
-                        }
-                        "screenshots" => {
-                            // Open screenshots folder
-                            std::thread::spawn(|| {
-                                let _ = tauri::async_runtime::block_on(open_screenshots_folder());
-                            });
-                        }
-                        "clear_data" => {
-                            if let Err(e) = clear_app_data() {
-                                error!("Error clearing app data: {}", e);
-                            }
-                        }
-                        "quit" => {
-                            std::process::exit(0);
-                        }
-                        _ => {}
-                    }
+                    run_tray_action(_app, event.id.as_ref());
                 })
                 .on_tray_icon_event(|tray, event| {
                     if let tauri::tray::TrayIconEvent::Click {
@@ -2456,17 +10762,20 @@ fn main() {
                         ..
                     } = event
                     {
-                        toggle_overlay_sync(tray.app_handle());
+                        handle_tray_left_click(tray.app_handle());
                     }
                 })
                 .build(app)?;
 
-            // Register global shortcut (Option+Space on macOS, Super+Space on Linux/WSL, Alt+Space on Windows)
-            #[cfg(target_os = "linux")]
-            let shortcut = Shortcut::new(Some(Modifiers::SUPER), Code::Space);
-            #[cfg(not(target_os = "linux"))]
-            let shortcut = Shortcut::new(Some(Modifiers::ALT), Code::Space);
+            // Register global shortcut (saved config, else Option+Space on macOS, Super+Space on Linux/WSL, Alt+Space on Windows)
+            let shortcut = load_textbox_shortcut();
             app.global_shortcut().register(shortcut)?;
+            *app.state::<AppState>().textbox_shortcut.lock().unwrap() = Some(shortcut);
+
+            // Register per-action shortcuts for tray menu items
+            if let Err(e) = register_action_shortcuts(app.handle()) {
+                warn!("[startup] Failed to register action shortcuts: {}", e);
+            }
 
             Ok(())
         })
@@ -2481,27 +10790,36 @@ fn main() {
                         serde_json::json!({ "visible": false }),
                     );
                 }
+            } else if window.label() == "overlay" {
+                match event {
+                    tauri::WindowEvent::Resized(size) => {
+                        enforce_overlay_min_size(window, size);
+                        persist_overlay_geometry(window);
+                    }
+                    tauri::WindowEvent::Moved(_) => {
+                        persist_overlay_geometry(window);
+                        schedule_overlay_snap(window);
+                    }
+                    _ => {}
+                }
             }
         })
         .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_fs::init())
-        .plugin(
-            tauri_plugin_log::Builder::new()
-                .target(tauri_plugin_log::Target::new(
-                    tauri_plugin_log::TargetKind::LogDir {
-                        file_name: Some("oto.log".into()),
-                    },
-                ))
-                .level(log::LevelFilter::Info)
-                .build(),
-        )
         .plugin(
             tauri_plugin_global_shortcut::Builder::new()
                 .with_handler(|app, shortcut, event| {
-                    if event.state() == ShortcutState::Pressed
-                        && (shortcut.matches(Modifiers::ALT, Code::Space)
-                            || shortcut.matches(Modifiers::SUPER, Code::Space))
-                    {
+                    if event.state() == ShortcutState::Pressed {
+                        if let Some(action) = action_for_shortcut(app, shortcut) {
+                            run_tray_action(app, &action);
+                            return;
+                        }
+                    }
+                    let is_textbox_shortcut =
+                        *app.state::<AppState>().textbox_shortcut.lock().unwrap()
+                            == Some(*shortcut);
+                    if event.state() == ShortcutState::Pressed && is_textbox_shortcut {
                         // Show overlay if hidden
                         let is_visible = {
                             let state = app.state::<AppState>();
@@ -2515,40 +10833,115 @@ fn main() {
                         if let Some(window) = app.get_webview_window("overlay") {
                             let _ = window.set_focus();
                         }
-                        let _ = app.emit("toggle-textbox", ());
+                        emit_overlay_event(app, "toggle-textbox", ());
                     }
                 })
                 .build(),
         )
         .invoke_handler(tauri::generate_handler![
             init_app,
+            get_download_status,
+            get_startup_metrics,
+            set_init_parallelism,
+            get_init_parallelism,
             get_paths,
             read_file_as_text,
             read_file_as_bytes,
+            read_file_as_bytes_limited,
+            get_file_size,
             is_initialized,
             get_model_config,
+            verify_model_integrity,
+            check_model_health,
             change_model,
             reset_model,
             load_model_from_folder,
+            download_character_model,
+            list_available_character_models,
+            set_active_character_model,
             show_overlay,
             hide_overlay,
             toggle_overlay,
             get_overlay_visible,
             resize_overlay,
+            set_overlay_opacity,
+            get_overlay_opacity,
+            set_overlay_accessibility_label,
+            save_overlay_geometry,
+            load_overlay_geometry,
+            save_snap_settings,
+            get_snap_settings,
+            snap_overlay_to_corner,
+            get_last_snapped_corner,
+            get_unread_count,
+            reset_unread_count,
+            list_monitors,
+            pin_overlay_to_monitor,
+            unpin_overlay_monitor,
+            get_pinned_monitor,
+            set_auto_hide_on_fullscreen,
+            save_auto_reposition_setting,
+            get_auto_reposition_setting,
+            set_always_on_top_refresh,
+            get_always_on_top_refresh,
             get_overlay_scale,
+            set_overlay_scale,
+            set_overlay_minimum_size,
+            set_talking_speed,
+            get_talking_speed,
+            set_overlay_event_log,
+            get_overlay_event_log_path,
             hide_main_window,
             show_main_window,
             toggle_main_window,
             is_main_window_visible,
             start_device_listening,
+            start_device_listening_filtered,
+            update_device_filter,
             check_screen_permission,
+            request_screen_permission_and_wait,
             open_screen_recording_settings,
+            get_required_permissions,
+            get_platform_info,
             take_screenshot,
+            take_screenshot_region,
+            take_screenshot_excluding_region,
+            get_screenshot_backend,
+            set_screenshot_exclude_overlay,
+            get_screenshot_settings,
+            cleanup_screenshots,
+            get_screenshot_count,
+            save_screenshot_cleanup_settings,
+            get_screenshot_cleanup_settings,
             open_screenshots_folder,
+            export_chat_history,
+            import_chat_from_text,
+            import_chat_from_json,
+            export_settings,
+            import_settings,
             save_api_key,
             get_api_key,
             has_api_key,
+            save_key_profile,
+            get_key_profiles,
+            migrate_api_key_to_encrypted,
+            delete_key_profile,
+            set_active_profile,
+            get_active_profile,
+            validate_api_key,
+            save_anthropic_key,
+            get_anthropic_key,
+            save_search_api_key,
+            get_search_api_key,
+            has_search_api_key,
+            save_search_endpoint,
+            get_search_endpoint,
+            save_ollama_config,
+            get_ollama_config,
+            is_ollama_available,
             save_system_prompt,
+            count_prompt_tokens,
+            estimate_context_tokens,
             get_system_prompt,
             save_character_prompt,
             get_character_prompt,
@@ -2556,20 +10949,119 @@ fn main() {
             get_deep_research_prompt,
             save_dialogue_prompt,
             get_dialogue_prompt,
+            save_prompt_preset,
+            get_prompt_presets,
+            delete_prompt_preset,
+            apply_prompt_preset,
+            reset_prompt_to_default,
             send_chat_message,
+            send_chat_message_with_clipboard,
+            send_chat_message_with_attachment,
+            get_clipboard_text,
+            get_follow_up_questions,
+            cancel_chat_stream,
+            set_api_concurrency,
+            set_cache_ttl_secs,
+            clear_response_cache,
             get_chat_history,
+            create_session,
+            list_sessions,
+            get_active_session,
+            switch_session,
+            fork_session_at,
+            vacuum_database,
+            get_database_stats,
+            get_db_pool_stats,
+            set_wal_autocheckpoint,
+            get_db_pragma,
+            rename_session,
+            delete_session,
+            auto_summarize_history,
+            save_auto_summarize_threshold,
+            get_auto_summarize_threshold,
+            get_chat_history_page,
+            get_chat_history_range,
+            get_chat_history_by_role,
+            get_chat_history_sorted,
+            get_chat_history_by_level,
+            get_chat_history_count,
+            search_chat_history,
+            save_history_sort_preference,
+            save_followup_settings,
+            get_chat_settings,
             clear_chat_history,
+            delete_chat_message,
+            toggle_bookmark,
+            get_bookmarked_messages,
+            update_message_metadata,
+            delete_chat_messages_before,
+            suggest_conversation_topics,
+            get_hourly_message_distribution,
+            get_token_usage_stats,
             trigger_deep_research,
+            force_deep_research,
+            reset_deep_research_cooldown,
+            get_deep_research_history,
+            delete_deep_research_record,
+            save_deep_research_topics,
+            get_deep_research_topics,
+            save_user_prompt_variables,
+            get_user_prompt_variables,
+            calibrate_gaze,
+            compute_gaze_transform,
+            clear_gaze_calibration,
+            check_network_connectivity,
+            save_deep_research_cooldown_secs,
+            get_deep_research_cooldown_secs,
             clear_all_data,
+            clear_data_selective,
+            get_app_log,
+            clear_app_log,
+            export_app_log,
+            get_last_errors,
+            clear_error_log,
+            start_overlay_drag,
+            update_overlay_drag,
+            end_overlay_drag,
             generate_texture,
+            cancel_texture_generation,
+            is_texture_generating,
+            check_texture_generation_lock,
+            recover_texture_generation,
+            generate_texture_variations,
+            preview_texture_generation,
             get_texture_paths,
             reload_character,
+            notify_model_loaded,
+            is_character_loading,
             get_texture_versions,
+            get_total_versions_size,
+            generate_missing_thumbnails,
             apply_texture_version,
+            get_model_expected_texture_size,
+            undo_texture_generation,
+            redo_texture_generation,
+            get_texture_undo_stack,
+            compare_texture_versions,
             delete_texture_version,
+            rollback_texture,
+            save_tray_settings,
+            get_tray_settings,
+            save_shortcuts_config,
+            get_shortcuts_config,
+            reload_shortcuts,
+            save_shortcut_config,
+            get_shortcut_config,
+            update_shortcut,
+            register_event_hook,
+            list_event_hooks,
+            delete_event_hook,
+            toggle_event_hook,
             save_hitbox,
             load_hitbox,
             clear_hitbox,
+            test_point_in_hitbox,
+            test_hitbox_bounds,
             save_transform_config,
             load_transform_config,
             log_from_frontend,
@@ -2577,3 +11069,260 @@ fn main() {
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a canned reply and records the messages of every request
+    struct StubClient {
+        reply: Result<String, String>,
+        requests: Mutex<Vec<Vec<Value>>>,
+    }
+
+    impl StubClient {
+        fn replying(reply: Result<&str, &str>) -> Self {
+            Self {
+                reply: reply.map(str::to_string).map_err(str::to_string),
+                requests: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    impl ChatClient for StubClient {
+        async fn complete(
+            &self,
+            _backend: &ApiBackend,
+            _api_key: &str,
+            messages: &[Value],
+            _max_tokens: u32,
+        ) -> Result<ChatCompletion, String> {
+            self.requests.lock().unwrap().push(messages.to_vec());
+            self.reply.clone().map(|text| ChatCompletion {
+                text,
+                token_count: None,
+            })
+        }
+
+        async fn complete_streaming(
+            &self,
+            _backend: &ApiBackend,
+            _api_key: &str,
+            messages: &[Value],
+            _max_tokens: u32,
+            _role: &str,
+            _cancel: &AtomicBool,
+        ) -> Result<String, String> {
+            self.requests.lock().unwrap().push(messages.to_vec());
+            self.reply.clone()
+        }
+    }
+
+    /// Records what the pipeline emits and stores instead of touching the app
+    #[derive(Default)]
+    struct StubHost {
+        history: Vec<ChatMessage>,
+        events: Mutex<Vec<String>>,
+        stored: Mutex<Vec<(String, String)>>,
+    }
+
+    impl ChatHost for StubHost {
+        fn emit(&self, event: &str, _payload: Value) {
+            self.events.lock().unwrap().push(event.to_string());
+        }
+
+        async fn is_online(&self) -> bool {
+            true
+        }
+
+        async fn capture_screenshot(
+            &self,
+            _region: Option<ScreenRegion>,
+        ) -> Result<String, String> {
+            Err("No screen in tests".to_string())
+        }
+
+        fn load_history(&self, _session_id: &str, limit: u32) -> Result<Vec<ChatMessage>, String> {
+            let skip = self.history.len().saturating_sub(limit as usize);
+            Ok(self.history[skip..].to_vec())
+        }
+
+        fn store_message(
+            &self,
+            _session_id: &str,
+            _timestamp: &str,
+            role: &str,
+            content: &str,
+            _context_level: u8,
+            _token_count: Option<u32>,
+            _metadata: Option<&str>,
+        ) -> Result<i64, String> {
+            let mut stored = self.stored.lock().unwrap();
+            stored.push((role.to_string(), content.to_string()));
+            Ok(stored.len() as i64)
+        }
+
+        fn response_stored(&self, _session_id: &str, _backend: &ApiBackend) {}
+    }
+
+    fn history_message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            id: 0,
+            timestamp: String::new(),
+            role: role.to_string(),
+            content: content.to_string(),
+            context_level: 0,
+            token_count: None,
+            bookmarked: false,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn context_messages_filter_and_label_history_by_level() {
+        let history = vec![
+            history_message("summary", "earlier"),
+            history_message("user", "hi"),
+            history_message("character", "hello!"),
+            history_message("deep-thought", "analysis"),
+        ];
+
+        let level0 = build_context_messages("system", &history, 0, "next", Vec::new());
+        let contents: Vec<&str> = level0
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "system",
+                "[Summary of earlier conversation]: earlier",
+                "hi",
+                "[Character]: hello!",
+                "next"
+            ]
+        );
+
+        let level2 = build_context_messages("system", &history, 2, "next", Vec::new());
+        let roles: Vec<&str> = level2.iter().map(|m| m["role"].as_str().unwrap()).collect();
+        assert_eq!(roles, ["system", "assistant", "user", "assistant", "user"]);
+        assert_eq!(level2[3]["content"], "[Analysis]: analysis");
+    }
+
+    #[test]
+    fn context_messages_attach_images_to_the_user_message() {
+        let messages = build_context_messages(
+            "system",
+            &[],
+            0,
+            "what is this?",
+            vec!["data:image/png;base64,AAAA".to_string()],
+        );
+        let content = messages[1]["content"].as_array().unwrap();
+        assert_eq!(content[0]["text"], "what is this?");
+        assert_eq!(content[1]["image_url"]["url"], "data:image/png;base64,AAAA");
+    }
+
+    #[test]
+    fn cache_key_depends_on_session_and_context() {
+        let history = vec![history_message("user", "hi")];
+        let with_history = build_context_messages("system", &history, 0, "next", Vec::new());
+        let without_history = build_context_messages("system", &[], 0, "next", Vec::new());
+
+        let key = response_cache_key("default", &with_history);
+        assert_eq!(key, response_cache_key("default", &with_history));
+        assert_ne!(key, response_cache_key("other", &with_history));
+        assert_ne!(key, response_cache_key("default", &without_history));
+    }
+
+    #[tokio::test]
+    async fn character_comment_comments_on_the_main_response() {
+        let client = StubClient::replying(Ok("Nice answer!"));
+        let comment = generate_character_comment(
+            &client,
+            &ApiBackend::default(),
+            "key",
+            "You are the character",
+            "42",
+        )
+        .await;
+        assert_eq!(comment.as_deref(), Some("Nice answer!"));
+
+        let requests = client.requests.lock().unwrap();
+        assert_eq!(requests[0][0]["content"], "You are the character");
+        assert!(requests[0][1]["content"].as_str().unwrap().ends_with("42"));
+    }
+
+    #[tokio::test]
+    async fn character_comment_is_skipped_when_empty_or_failed() {
+        for reply in [Ok(""), Err("API error")] {
+            let client = StubClient::replying(reply);
+            let comment =
+                generate_character_comment(&client, &ApiBackend::default(), "key", "", "42").await;
+            assert_eq!(comment, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn follow_up_questions_are_parsed_from_the_reply() {
+        let client = StubClient::replying(Ok("```json\n[\"Why?\", \" How? \", \"\"]\n```"));
+        let questions =
+            generate_follow_up_questions(&client, &ApiBackend::default(), "key", "Q", "A").await;
+        assert_eq!(
+            questions,
+            Some(vec!["Why?".to_string(), "How?".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn execute_sends_recent_context_and_stores_the_exchange() {
+        let state = AppState::default();
+        let host = StubHost {
+            history: vec![
+                history_message("user", "too old"),
+                history_message("user", "hi"),
+                history_message("character", "hello!"),
+            ],
+            ..Default::default()
+        };
+        let client = StubClient::replying(Ok("Doing great"));
+        let mut pipeline = ChatPipeline::new(&state, 1, client, host);
+        pipeline
+            .with_api_backend(ApiBackend::Ollama {
+                base_url: "http://localhost:11434".to_string(),
+                model: "test".to_string(),
+            })
+            .with_system_prompt("You are the character".to_string())
+            .with_history(2)
+            .with_streaming(true);
+
+        let response = pipeline.execute("how are you?".to_string()).await.unwrap();
+        assert_eq!(response.main_response, "Doing great");
+        assert!(response.character_comments.is_none());
+
+        let requests = pipeline.client.requests.lock().unwrap();
+        let contents: Vec<&str> = requests[0]
+            .iter()
+            .map(|m| m["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            contents,
+            [
+                "You are the character",
+                "hi",
+                "[Character's Inner Thoughts]: hello!",
+                "how are you?"
+            ]
+        );
+
+        let stored = pipeline.host.stored.lock().unwrap();
+        assert_eq!(
+            *stored,
+            [
+                ("user".to_string(), "how are you?".to_string()),
+                ("character".to_string(), "Doing great".to_string())
+            ]
+        );
+        assert_eq!(*pipeline.host.events.lock().unwrap(), ["chat-stream-done"]);
+    }
+}
@@ -5,11 +5,24 @@ use serde::{Deserialize, Serialize};
 /// Represents a single chat message stored in the database
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
-    pub id: Option<i64>,
+    pub id: i64,
     pub timestamp: String,
     pub role: String,
     pub content: String,
     pub context_level: u8,
+    pub token_count: Option<u32>,
+    #[serde(default)]
+    pub bookmarked: bool,
+    /// Free-form JSON attached by the caller, e.g. the screenshot sent with the message
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// A chat message matched by a history search, with context around the hit
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub message: ChatMessage,
+    pub snippet: String,
 }
 
 /// Response from the chat API including optional character comments
@@ -17,6 +30,60 @@ pub struct ChatMessage {
 pub struct ChatResponse {
     pub main_response: String,
     pub character_comments: Option<Vec<String>>,
+    pub follow_up_questions: Option<Vec<String>>,
+}
+
+/// Chat completion provider (and model) used for a message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "provider")]
+pub enum ApiBackend {
+    OpenAI { model: String },
+    Anthropic { model: String },
+    Ollama { base_url: String, model: String },
+}
+
+impl Default for ApiBackend {
+    fn default() -> Self {
+        ApiBackend::OpenAI {
+            model: "gpt-4.1-2025-04-14".to_string(),
+        }
+    }
+}
+
+/// Token usage totals for the budget view
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStats {
+    pub today: u32,
+    pub this_week: u32,
+    pub total: u32,
+    pub estimated_cost_usd: f64,
+}
+
+/// A saved, named prompt that can be applied to one of the prompt slots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptPreset {
+    pub id: String,
+    pub name: String,
+    pub prompt_type: String,
+    pub content: String,
+    pub created_at: String,
+}
+
+/// A named OpenAI key with its own model settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyProfile {
+    pub name: String,
+    pub api_key: String,
+    pub model: String,
+    pub max_tokens: u32,
+}
+
+/// Incremental piece of a streamed chat response
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamChunk {
+    pub role: String,
+    pub delta: String,
+    pub done: bool,
 }
 
 /// Response from the deep research trigger
@@ -24,13 +91,312 @@ pub struct ChatResponse {
 pub struct DeepResearchResponse {
     pub on_cooldown: bool,
     pub remaining_seconds: u64,
+    pub cooldown_secs: u64,
     pub main_response: String,
 }
 
+/// A stored deep research result
+#[derive(Debug, Clone, Serialize)]
+pub struct DeepResearchRecord {
+    pub id: i64,
+    pub session_id: String,
+    pub timestamp: String,
+    pub prompt_used: String,
+    pub insights: String,
+    pub token_count: Option<u32>,
+}
+
+/// Saved overlay window position and size (logical pixels)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OverlayGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default = "default_overlay_opacity")]
+    pub opacity: f64,
+}
+
+fn default_overlay_opacity() -> f64 {
+    1.0
+}
+
+/// Rectangle of the screen to capture, in screen pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ScreenRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A point in normalized overlay coordinates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Point2D {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A named hitbox polygon that triggers a specific interaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HitboxZone {
+    pub name: String,
+    pub interaction_type: String,
+    pub points: Vec<Point2D>,
+}
+
+/// Axis-aligned bounding box of one hitbox zone, in the same units as its points
+#[derive(Debug, Clone, Serialize)]
+pub struct ZoneBounds {
+    pub name: String,
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+/// Bounding boxes of every hitbox zone, for quick rejection before polygon tests
+#[derive(Debug, Clone, Serialize)]
+pub struct HitboxBounds {
+    pub zones: Vec<ZoneBounds>,
+}
+
+/// An installed Live2D character in the models directory
+#[derive(Debug, Clone, Serialize)]
+pub struct CharacterModel {
+    pub name: String,
+    /// Path to the character's `.model3.json`
+    pub path: String,
+    pub has_textures: bool,
+}
+
 /// Version information for a saved texture
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextureVersion {
     pub id: String,
     pub created_at: String,
     pub prompt: Option<String>,
+    /// Disk space used by the version's files
+    pub size_bytes: u64,
+    pub thumbnail_path: Option<String>,
+}
+
+/// Status of a platform permission the app depends on
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionStatus {
+    pub permission: String,
+    pub granted: bool,
+    pub message: String,
+}
+
+/// Platform details used by the frontend to show or hide platform-specific features
+#[derive(Debug, Clone, Serialize)]
+pub struct PlatformInfo {
+    pub os: String,
+    pub arch: String,
+    pub is_wsl: bool,
+    pub display_server: String,
+    pub has_screen_capture: bool,
+    pub has_audio: bool,
+}
+
+/// Number of user messages sent during a given hour of the day
+#[derive(Debug, Clone, Serialize)]
+pub struct HourlyCount {
+    pub hour: u8,
+    pub count: u64,
+}
+
+/// Database file size before and after a vacuum
+#[derive(Debug, Clone, Serialize)]
+pub struct VacuumStats {
+    pub size_before_bytes: u64,
+    pub size_after_bytes: u64,
+    pub bytes_freed: u64,
+}
+
+/// Summary of what the chat database holds
+#[derive(Debug, Clone, Serialize)]
+pub struct DatabaseStats {
+    pub total_messages: u32,
+    pub size_bytes: u64,
+    pub oldest_message: Option<String>,
+    pub newest_message: Option<String>,
+    pub sessions_count: u32,
+}
+
+/// A failed command recorded for the frontend's error notifications
+#[derive(Debug, Clone, Serialize)]
+pub struct AppError {
+    pub timestamp: String,
+    pub command: String,
+    pub message: String,
+    pub severity: String,
+}
+
+/// Entry in the in-memory app log shown by the settings log viewer
+#[derive(Debug, Clone, Serialize)]
+pub struct AppEvent {
+    pub timestamp: String,
+    pub level: String,
+    pub source: String,
+    pub message: String,
+}
+
+/// Progress of one model download
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DownloadStatus {
+    pub in_progress: bool,
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+}
+
+/// Outcome of clearing one or more categories of app data
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClearResult {
+    pub deleted_files: u32,
+    pub freed_bytes: u64,
+    pub errors: Vec<String>,
+}
+
+/// Outcome of importing chat messages from a file
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportResult {
+    pub imported_count: u32,
+    pub skipped_count: u32,
+    pub errors: Vec<String>,
+}
+
+/// Database connection pool usage
+#[derive(Debug, Clone, Serialize)]
+pub struct DbPoolStats {
+    pub active: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+/// Diagnostic report on the installed character model files
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelHealthReport {
+    pub model_dir_exists: bool,
+    pub model3_json_exists: bool,
+    pub texture_00_exists: bool,
+    pub texture_01_exists: bool,
+    pub texture_00_size: Option<u64>,
+    pub texture_01_size: Option<u64>,
+    pub has_original_backup: bool,
+    pub texture_version_count: usize,
+    pub hitbox_count: usize,
+    pub missing_files: Vec<String>,
+}
+
+/// A webhook fired whenever the named app event is emitted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventHook {
+    pub id: String,
+    pub event_name: String,
+    pub webhook_url: String,
+    pub method: String,
+    pub enabled: bool,
+}
+
+/// Ordering for paged chat history queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    OldestFirst,
+    NewestFirst,
+}
+
+impl SortDirection {
+    /// SQL keyword for this direction
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortDirection::OldestFirst => "ASC",
+            SortDirection::NewestFirst => "DESC",
+        }
+    }
+}
+
+/// Global shortcut that toggles the overlay textbox
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShortcutConfig {
+    pub modifier: String,
+    pub key: String,
+}
+
+impl Default for ShortcutConfig {
+    fn default() -> Self {
+        // Super+Space on Linux/WSL, Option/Alt+Space elsewhere
+        let modifier = if cfg!(target_os = "linux") {
+            "Super"
+        } else {
+            "Alt"
+        };
+        Self {
+            modifier: modifier.to_string(),
+            key: "Space".to_string(),
+        }
+    }
+}
+
+/// Pixel rectangle within a texture
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Pixel-level change statistics between two texture versions
+#[derive(Debug, Clone, Serialize)]
+pub struct TextureDiff {
+    pub changed_pixels: u32,
+    pub total_pixels: u32,
+    pub change_percent: f32,
+    /// Largest single-channel RGBA difference
+    pub max_delta: u8,
+    pub affected_region: Option<BoundingBox>,
+}
+
+/// A named conversation thread
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatSession {
+    pub id: String,
+    pub name: String,
+    pub message_count: u32,
+    pub last_message_at: String,
+    /// Session this one was branched from with `fork_session_at`
+    pub forked_from_session_id: Option<String>,
+}
+
+/// A connected display, in physical pixels
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitorInfo {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    pub is_primary: bool,
+}
+
+/// Linear mapping from screen coordinates to Live2D model space
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GazeTransform {
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+/// Marker written while textures are being generated, so an interrupted run can be undone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationLock {
+    pub in_progress: bool,
+    /// Local timestamp that also names the version directory of this run
+    pub started_at: String,
+    pub prompt: String,
 }
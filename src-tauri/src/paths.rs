@@ -1,6 +1,7 @@
 //! Path utilities and file system helpers
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 /// URL for downloading the default Live2D model
 pub const DEFAULT_MODEL_URL: &str = "https://storage.googleapis.com/oto_bucket/live2d/Hiyori1.zip";
@@ -9,19 +10,154 @@ pub const DEFAULT_MODEL_URL: &str = "https://storage.googleapis.com/oto_bucket/l
 pub const DEFAULT_OVERLAY_WIDTH: f64 = 400.0;
 pub const DEFAULT_OVERLAY_HEIGHT: f64 = 600.0;
 
+/// Default minimum overlay window dimensions (below this the Live2D renderer breaks)
+pub const DEFAULT_OVERLAY_MIN_WIDTH: u32 = 200;
+pub const DEFAULT_OVERLAY_MIN_HEIGHT: u32 = 300;
+
+/// Directory name used for the app's files under each base directory
+pub const APP_DIR_NAME: &str = "com.oto.desktop";
+
+/// File name of a texture version's thumbnail
+pub const TEXTURE_THUMBNAIL_FILE: &str = "thumb.jpg";
+
+/// How the app's directories are laid out on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathStrategy {
+    /// XDG base directories: data, config and cache each under their own root
+    Xdg,
+    /// Everything in one per-user application data directory
+    AppData,
+}
+
+/// Kind of file, which picks its base directory under the XDG strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    /// Models, chat history, screenshots and other user data ($XDG_DATA_HOME)
+    Data,
+    /// Prompts and settings ($XDG_CONFIG_HOME)
+    Config,
+    /// Thumbnails and other files that can be regenerated ($XDG_CACHE_HOME)
+    Cache,
+}
+
+/// Resolves the base directory for each kind of file
+#[derive(Debug, Clone)]
+pub struct PathResolver {
+    pub strategy: PathStrategy,
+    pub app_name: String,
+}
+
+impl PathResolver {
+    pub fn new(strategy: PathStrategy, app_name: impl Into<String>) -> Self {
+        Self {
+            strategy,
+            app_name: app_name.into(),
+        }
+    }
+
+    /// XDG directories on Linux, the single app data directory elsewhere
+    pub fn for_current_os() -> Self {
+        let strategy = if cfg!(target_os = "linux") {
+            PathStrategy::Xdg
+        } else {
+            PathStrategy::AppData
+        };
+        Self::new(strategy, APP_DIR_NAME)
+    }
+
+    pub fn base_dir(&self, kind: PathKind) -> Result<PathBuf, String> {
+        match &self.strategy {
+            // dirs honors $XDG_*_HOME and falls back to ~/.local/share, ~/.config and ~/.cache
+            PathStrategy::Xdg => match kind {
+                PathKind::Data => dirs::data_dir(),
+                PathKind::Config => dirs::config_dir(),
+                PathKind::Cache => dirs::cache_dir(),
+            }
+            .map(|p| p.join(&self.app_name))
+            .ok_or_else(|| format!("Could not find {:?} directory", kind)),
+            PathStrategy::AppData => dirs::data_dir()
+                .map(|p| p.join(&self.app_name))
+                .ok_or_else(|| "Could not find app data directory".to_string()),
+        }
+    }
+}
+
+static PATH_RESOLVER: OnceLock<PathResolver> = OnceLock::new();
+
+/// Sets the resolver used by every path function; call once at startup
+pub fn init_path_resolver(resolver: PathResolver) {
+    let _ = PATH_RESOLVER.set(resolver);
+}
+
+/// The global resolver, defaulting to the current OS's layout
+pub fn path_resolver() -> &'static PathResolver {
+    PATH_RESOLVER.get_or_init(PathResolver::for_current_os)
+}
+
 /// Gets the application data directory
 pub fn get_app_data_dir() -> Result<PathBuf, String> {
-    dirs::data_dir()
-        .map(|p| p.join("com.oto.desktop"))
-        .ok_or_else(|| "Could not find app data directory".to_string())
+    path_resolver().base_dir(PathKind::Data)
+}
+
+/// Gets the directory for prompts and settings
+pub fn get_config_dir() -> Result<PathBuf, String> {
+    path_resolver().base_dir(PathKind::Config)
+}
+
+/// Gets the directory for regenerable files such as thumbnails
+pub fn get_cache_dir() -> Result<PathBuf, String> {
+    path_resolver().base_dir(PathKind::Cache)
+}
+
+/// Whether `path` is a settings file. Settings are the dotfiles at the top of the
+/// config directory (or of the data directory for versions before the XDG layout).
+pub fn is_settings_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Moves settings written by versions that kept them in the data directory
+/// into the config directory
+pub fn migrate_legacy_config_files() -> Result<u32, String> {
+    let data_dir = get_app_data_dir()?;
+    let config_dir = get_config_dir()?;
+    if data_dir == config_dir || !data_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut moved = 0;
+    let entries = std::fs::read_dir(&data_dir)
+        .map_err(|e| format!("Failed to read app data directory: {}", e))?;
+    for entry in entries.filter_map(|e| e.ok()) {
+        let source = entry.path();
+        if !is_settings_file(&source) {
+            continue;
+        }
+        let dest = config_dir.join(entry.file_name());
+        if dest.exists() {
+            continue;
+        }
+        std::fs::create_dir_all(&config_dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        // rename fails across filesystems, so fall back to copying
+        if std::fs::rename(&source, &dest).is_err() {
+            std::fs::copy(&source, &dest)
+                .map_err(|e| format!("Failed to migrate {:?}: {}", source, e))?;
+            let _ = std::fs::remove_file(&source);
+        }
+        moved += 1;
+    }
+    Ok(moved)
 }
 
 /// Clears all application data
 pub fn clear_app_data() -> Result<(), String> {
-    let app_dir = get_app_data_dir()?;
-    if app_dir.exists() {
-        std::fs::remove_dir_all(&app_dir)
-            .map_err(|e| format!("Failed to clear app data: {}", e))?;
+    let mut dirs = vec![get_app_data_dir()?, get_config_dir()?, get_cache_dir()?];
+    dirs.dedup();
+    for dir in dirs.iter().filter(|dir| dir.exists()) {
+        std::fs::remove_dir_all(dir).map_err(|e| format!("Failed to clear app data: {}", e))?;
     }
     Ok(())
 }
@@ -31,64 +167,229 @@ pub fn get_models_dir() -> Result<PathBuf, String> {
     get_app_data_dir().map(|p| p.join("models"))
 }
 
+/// Gets the directory for in-progress downloads
+pub fn get_downloads_dir() -> Result<PathBuf, String> {
+    get_app_data_dir().map(|p| p.join("downloads"))
+}
+
 /// Gets the screenshots directory path
 pub fn get_screenshots_dir() -> Result<PathBuf, String> {
     get_app_data_dir().map(|p| p.join("History").join("Screenshots"))
 }
 
+/// Gets the chat exports directory path
+pub fn get_exports_dir() -> Result<PathBuf, String> {
+    get_app_data_dir().map(|p| p.join("History").join("Exports"))
+}
+
+/// Gets the screenshot settings file path
+pub fn get_screenshot_settings_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".screenshot_settings.json"))
+}
+
 /// Gets the database file path
 pub fn get_db_path() -> Result<PathBuf, String> {
     get_app_data_dir().map(|p| p.join("chat_history.db"))
 }
 
+/// Gets the WAL auto-checkpoint settings file path
+pub fn get_wal_autocheckpoint_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".wal_autocheckpoint"))
+}
+
+/// Gets the logs directory path
+pub fn get_logs_dir() -> Result<PathBuf, String> {
+    get_app_data_dir().map(|p| p.join("logs"))
+}
+
+/// Gets the overlay event log file path for a given date (YYYY-MM-DD)
+pub fn get_overlay_event_log_path(date: &str) -> Result<PathBuf, String> {
+    get_logs_dir().map(|p| p.join(format!("overlay_events_{}.jsonl", date)))
+}
+
+/// Gets the chat settings file path
+pub fn get_chat_settings_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".chat_settings.json"))
+}
+
 /// Gets the API key file path
 pub fn get_api_key_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".api_key"))
+    get_config_dir().map(|p| p.join(".api_key"))
+}
+
+/// Gets the API key profiles file path
+pub fn get_key_profiles_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".key_profiles.json"))
+}
+
+/// Gets the active key profile name file path
+pub fn get_active_profile_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".active_profile"))
+}
+
+/// Gets the auto-summarize threshold file path
+pub fn get_auto_summarize_threshold_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".auto_summarize_threshold"))
+}
+
+/// Gets the active chat session file path
+pub fn get_active_session_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".active_session"))
+}
+
+/// Gets the API concurrency limit file path
+pub fn get_api_concurrency_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".api_concurrency"))
+}
+
+/// Gets the init download parallelism file path
+pub fn get_init_parallelism_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".init_parallelism"))
+}
+
+/// Gets the response cache TTL file path
+pub fn get_response_cache_ttl_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".response_cache_ttl"))
+}
+
+/// Gets the Anthropic API key file path
+pub fn get_anthropic_key_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".anthropic_key"))
+}
+
+/// Gets the web search API key file path
+pub fn get_search_key_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".search_api_key"))
+}
+
+/// Gets the web search endpoint file path
+pub fn get_search_endpoint_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".search_endpoint"))
+}
+
+/// Gets the Ollama config file path
+pub fn get_ollama_config_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".ollama_config.json"))
 }
 
 /// Gets the system prompt file path
 pub fn get_system_prompt_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".system_prompt"))
+    get_config_dir().map(|p| p.join(".system_prompt"))
 }
 
 /// Gets the character prompt file path
 pub fn get_character_prompt_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".character_prompt"))
+    get_config_dir().map(|p| p.join(".character_prompt"))
 }
 
 /// Gets the deep research prompt file path
 pub fn get_deep_research_prompt_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".deep_research_prompt"))
+    get_config_dir().map(|p| p.join(".deep_research_prompt"))
 }
 
 /// Gets the dialogue prompt file path
 pub fn get_dialogue_prompt_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".dialogue_prompt"))
+    get_config_dir().map(|p| p.join(".dialogue_prompt"))
+}
+
+/// Gets the prompt presets file path
+pub fn get_prompt_presets_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".prompt_presets.json"))
 }
 
 /// Gets the deep research cooldown timestamp file path
 pub fn get_deep_research_cooldown_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".deep_research_cooldown"))
+    get_config_dir().map(|p| p.join(".deep_research_cooldown"))
+}
+
+/// Gets the deep research cooldown period config file path
+pub fn get_deep_research_cooldown_config_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".deep_research_cooldown_secs"))
+}
+
+/// Gets the saved deep research topics file path
+pub fn get_deep_research_topics_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".deep_research_topics.json"))
+}
+
+/// Gets the user-defined prompt variables file path
+pub fn get_prompt_variables_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".prompt_variables.json"))
 }
 
 /// Gets the hitbox configuration file path
 pub fn get_hitbox_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".hitbox.json"))
+    get_config_dir().map(|p| p.join(".hitbox.json"))
+}
+
+/// Gets the texture generation lock file path
+pub fn get_texture_lock_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".texture_generation.lock"))
+}
+
+/// Gets the active character model file path
+pub fn get_active_model_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".active_model"))
 }
 
 /// Gets the model configuration file path
 pub fn get_model_config_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".model_config.json"))
+    get_config_dir().map(|p| p.join(".model_config.json"))
 }
 
 /// Gets the overlay scale file path
 pub fn get_overlay_scale_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".overlay_scale"))
+    get_config_dir().map(|p| p.join(".overlay_scale"))
+}
+
+/// Gets the overlay opacity file path
+pub fn get_overlay_opacity_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".overlay_opacity"))
+}
+
+/// Gets the overlay accessibility label file path
+pub fn get_overlay_accessibility_label_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".overlay_accessibility_label"))
+}
+
+/// Gets the overlay corner snapping settings file path
+pub fn get_snap_settings_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".snap_settings.json"))
+}
+
+/// Gets the pinned overlay monitor file path
+pub fn get_pinned_monitor_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".pinned_monitor"))
+}
+
+/// Gets the overlay geometry file path
+pub fn get_overlay_geometry_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".overlay_geometry.json"))
+}
+
+/// Gets the display settings file path
+pub fn get_display_settings_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".display_settings.json"))
+}
+
+/// Gets the tray settings file path
+pub fn get_tray_settings_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".tray_settings.json"))
+}
+
+/// Gets the keyboard shortcuts configuration file path
+pub fn get_shortcuts_config_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".shortcuts.json"))
+}
+
+/// Gets the overlay textbox shortcut configuration file path
+pub fn get_shortcut_config_path() -> Result<PathBuf, String> {
+    get_config_dir().map(|p| p.join(".shortcut_config.json"))
 }
 
 /// Gets the transform configuration file path
 pub fn get_transform_config_path() -> Result<PathBuf, String> {
-    get_app_data_dir().map(|p| p.join(".transform_config.json"))
+    get_config_dir().map(|p| p.join(".transform_config.json"))
 }
 
 /// Gets the texture directory path for a specific model
@@ -114,3 +415,18 @@ pub fn get_versions_dir_for_model(
 ) -> Result<PathBuf, String> {
     get_texture_dir_for_model(model_folder, texture_folder).map(|p| p.join("versions"))
 }
+
+/// Gets the thumbnail path for a texture version directory
+pub fn get_texture_thumbnail_path(version_dir: &Path) -> Result<PathBuf, String> {
+    if path_resolver().strategy == PathStrategy::Xdg {
+        // Mirror the version's location under the models directory in the cache
+        if let Ok(relative) = version_dir.strip_prefix(get_models_dir()?) {
+            return get_cache_dir().map(|p| {
+                p.join("thumbnails")
+                    .join(relative)
+                    .join(TEXTURE_THUMBNAIL_FILE)
+            });
+        }
+    }
+    Ok(version_dir.join(TEXTURE_THUMBNAIL_FILE))
+}
@@ -1,5 +1,7 @@
 //! Default prompt templates for AI interactions
 
+use std::collections::HashMap;
+
 /// Default system prompt for the AI assistant
 pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a helpful AI assistant. You can see the user's screen via screenshots. Be concise and helpful.";
 
@@ -37,3 +39,24 @@ Your approach:
 - Use your signature wit, but let genuine curiosity show through
 
 Share insights that feel meaningful, connections that seem interesting, or questions worth pondering. End with something that invites further exploration — whether that's a provocative thought or just something fun to consider."#;
+
+/// Replaces `{{key}}` placeholders with values from `vars`; unknown keys are left as-is
+pub fn substitute_prompt_variables(template: String, vars: HashMap<String, String>) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + len + 2;
+        let key = rest[start + 2..start + 2 + len].trim();
+        result.push_str(&rest[..start]);
+        match vars.get(key) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    result.push_str(rest);
+    result
+}
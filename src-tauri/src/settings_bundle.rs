@@ -0,0 +1,153 @@
+//! Portable settings bundle for backing up and restoring the app configuration
+
+use crate::models::{KeyProfile, OverlayGeometry};
+use crate::{HitboxData, ShortcutsConfig};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the bundle layout changes incompatibly
+pub const SETTINGS_BUNDLE_SCHEMA_VERSION: u32 = 1;
+
+/// File name used for exported bundles
+pub const SETTINGS_BUNDLE_FILE_NAME: &str = "oto-settings.json";
+
+/// All user configuration in a single JSON document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppSettingsBundle {
+    pub schema_version: u32,
+    pub exported_at: String,
+    /// Default profile key, obfuscated with the machine ID
+    #[serde(rename = "api_key_encrypted", default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub key_profiles: Vec<BundledKeyProfile>,
+    #[serde(default)]
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub prompts: BundledPrompts,
+    #[serde(default)]
+    pub overlay_geometry: Option<OverlayGeometry>,
+    #[serde(default)]
+    pub hitbox: Option<HitboxData>,
+    #[serde(default)]
+    pub shortcuts: Option<ShortcutsConfig>,
+}
+
+/// Key profile with its API key obfuscated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundledKeyProfile {
+    pub name: String,
+    #[serde(rename = "api_key_encrypted")]
+    pub api_key: String,
+    pub model: String,
+    pub max_tokens: u32,
+}
+
+/// Custom prompts; `None` means the built-in default is in use
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BundledPrompts {
+    pub system: Option<String>,
+    pub character: Option<String>,
+    pub deep_research: Option<String>,
+    pub dialogue: Option<String>,
+}
+
+impl BundledKeyProfile {
+    pub fn from_profile(profile: &KeyProfile, machine_id: &str) -> Self {
+        Self {
+            name: profile.name.clone(),
+            api_key: obfuscate(&profile.api_key, machine_id),
+            model: profile.model.clone(),
+            max_tokens: profile.max_tokens,
+        }
+    }
+
+    pub fn into_profile(self, machine_id: &str) -> Result<KeyProfile, String> {
+        Ok(KeyProfile {
+            api_key: deobfuscate(&self.api_key, machine_id)?,
+            name: self.name,
+            model: self.model,
+            max_tokens: self.max_tokens,
+        })
+    }
+}
+
+/// Returns a stable per-machine identifier used as the obfuscation key
+pub fn machine_id() -> String {
+    read_machine_id()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(|| {
+            log::warn!("[settings] Could not read machine ID, using fallback key");
+            "oto".to_string()
+        })
+}
+
+#[cfg(target_os = "linux")]
+fn read_machine_id() -> Option<String> {
+    ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+}
+
+#[cfg(target_os = "macos")]
+fn read_machine_id() -> Option<String> {
+    let output = std::process::Command::new("ioreg")
+        .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .find(|line| line.contains("IOPlatformUUID"))
+        .and_then(|line| line.split('"').nth(3))
+        .map(|id| id.to_string())
+}
+
+#[cfg(target_os = "windows")]
+fn read_machine_id() -> Option<String> {
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKLM\SOFTWARE\Microsoft\Cryptography",
+            "/v",
+            "MachineGuid",
+        ])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .find(|line| line.contains("MachineGuid"))
+        .and_then(|line| line.split_whitespace().last())
+        .map(|id| id.to_string())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn read_machine_id() -> Option<String> {
+    None
+}
+
+fn xor_with_key(data: &[u8], key: &str) -> Vec<u8> {
+    data.iter()
+        .zip(key.as_bytes().iter().cycle())
+        .map(|(b, k)| b ^ k)
+        .collect()
+}
+
+/// XORs `value` with the machine ID and base64-encodes the result
+pub fn obfuscate(value: &str, machine_id: &str) -> String {
+    BASE64.encode(xor_with_key(value.as_bytes(), machine_id))
+}
+
+/// Reverses `obfuscate`; fails if the bundle came from a different machine
+pub fn deobfuscate(value: &str, machine_id: &str) -> Result<String, String> {
+    let bytes = BASE64
+        .decode(value)
+        .map_err(|e| format!("Failed to decode API key: {}", e))?;
+    String::from_utf8(xor_with_key(&bytes, machine_id))
+        .ok()
+        .filter(|key| key.chars().all(|c| c.is_ascii_graphic()))
+        .ok_or_else(|| "API key was exported on a different machine".to_string())
+}